@@ -0,0 +1,40 @@
+// compares rows per second of the single threaded engine against the
+// sharded engine on a generated file. Run with
+// 'cargo bench --bench sharded' for meaningful figures.
+use std::collections::HashMap;
+use std::time::Instant;
+
+use tx_engine::common::Account;
+use tx_engine::engine::Engine;
+use tx_engine::generate::generate_transactions;
+use tx_engine::sharded::ShardedEngine;
+use tx_engine::sink::NullSink;
+
+fn main()
+{
+    let rows = 200000;
+    let mut csv : Vec<u8> = Vec::new();
+    generate_transactions(rows, 1000, 11, &mut csv).unwrap();
+
+    let mut single : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_slice());
+    let start = Instant::now();
+    Engine::new(&mut single).process_transactions(&mut data, &mut NullSink).unwrap();
+    let single_secs = start.elapsed().as_secs_f64();
+
+    println!("single threaded: {:.0} rows/s", rows as f64 / single_secs);
+
+    for shards in [2, 4, 8]
+    {
+        let mut sharded : HashMap<u16, Account> = HashMap::new();
+        let mut data = csv::Reader::from_reader(csv.as_slice());
+        let start = Instant::now();
+        ShardedEngine::new(&mut sharded, shards)
+            .process_transactions(&mut data, &mut NullSink).unwrap();
+        let secs = start.elapsed().as_secs_f64();
+
+        println!("{shards} shards: {:.0} rows/s ({:.2}x)",
+                 rows as f64 / secs, single_secs / secs);
+        assert_eq!(single.len(), sharded.len());
+    }
+}
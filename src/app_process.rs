@@ -1,261 +1,432 @@
 use crate::engine::Engine;
+use crate::audit::AuditMode;
+use crate::command::*;
 use crate::common::*;
+use crate::events::EventKind;
+use crate::journal::Journal;
+use crate::record::*;
+use crate::sink::EventSink;
+
+use std::time::Instant;
 
-use rust_decimal::prelude::*;
 use rust_decimal_macros::dec;
 
 impl <'a> Engine <'a>
 {
 
-    pub fn process_transactions<R>(&mut self, 
-                                data    : &mut csv::Reader<R>, 
-                                logger  : &mut Option<Logger>)
-    -> ProcessEvent 
+    // applies every row of the file, reporting the rows which were
+    // refused to the sink. Refused rows don't fail the run, only a
+    // failure of the reader or the sink does.
+    pub fn process_transactions<R>(&mut self,
+                                data    : &mut csv::Reader<R>,
+                                sink    : &mut dyn EventSink)
+    -> Result<(), ProcessError>
     where R: std::io::Read
     {
-        for(entry_num, result) in data.records().enumerate()
+        // the first event the sink failed to record, the run carries
+        // on without it and reports the failure once it's complete.
+        let mut sink_failure : Option<std::io::Error> = None;
+        let (mut rows, mut refused) = (0, 0);
+        let started = Instant::now();
+        if let Some(history) = &mut self.history
         {
-            // this shouldnt panic because we have 
-            // parsed the csv file in main, and 
-            // we can trust on the assumptions 
-            // given by the text that the file is valid.
-            let entry = result.unwrap();
+            history.begin_input();
+        }
 
+        let result = read_records(data, |line, row, record|
+        {
+            // already applied before the run was interrupted.
+            if line <= self.resume_after
             {
-                let tx_type: &str = entry[0].trim();
-
-                // these shouldn't panic since the given 
-                // text allows us assume the types are valid
-                let client_id: u16 = entry[1].trim().parse::<u16>().unwrap();
-                let tx_id: u32 = entry[2].trim().parse::<u32>().unwrap();
+                return;
+            }
 
-                match tx_type {
-                    "deposit" => 
-                    {
-                        // this shouldn't panic since the given 
-                        // text allows us assume the types are valid
-                        let amount = Decimal::from_str(entry[3].trim())
-                                     .unwrap();
-                        
-                        if amount < dec!(0.0000)
-                        {
-                            if let Some(l) = logger
-                            {
-                                l.log(&ProcessEvent::ErrAmountNegative(tx_id));
-                            }
-                            continue;
-                        }
-
-                        if let Some(_) = self.tx_history.get(&tx_id)
-                        {
-                            if let Some(l) = logger
-                            {
-                                l.log(&ProcessEvent::ErrTxIdExists(tx_id));
-                            }
-                            continue;
-                        }
-
-                        let account = self
-                            .accounts
-                            .entry(client_id)
-                            .or_insert(Account::new());
-
-                        account.deposit(&amount);
-
-                        let tx = Tx::new(tx_id, client_id, amount, false);
-                        self.tx_history.entry(tx_id).or_insert(tx);
+            let client = record.as_ref().ok().map(|record| record.client);
+            let result = match record
+            {
+                Ok(record) =>
+                {
+                    self.apply_at(record.to_command(), line)
+                        .map_err(ProcessError::from)
+                }
+                Err(event) =>
+                {
+                    self.record_event(line, EventKind::rejected(None, &event));
+                    Err(event)
+                }
+            };
+            self.count_row(client, &result);
+            rows += 1;
 
-                    }
-                    "withdrawal" => 
+            match result
+            {
+                Ok(outcome) =>
+                {
+                    let applied = RunNote::TxApplied(outcome.command.client(), outcome.command.tx());
+                    keep_first_failure(&mut sink_failure, sink.note(line, &applied));
+                    if self.audit == AuditMode::EveryTx
                     {
-                        // this shouldn't panic since the given 
-                        // text allows us assume the types are valid
-                        let amount = Decimal::from_str(entry[3].trim())
-                                     .unwrap();
-
-                        if amount < dec!(0.0000)
-                        {
-                            if let Some(l) = logger
-                            {
-                                l.log(&ProcessEvent::ErrAmountNegative(tx_id));
-                            }
-                            continue;
-                        }
-
-                        if let Some(_) = self.tx_history.get(&tx_id)
-                        {
-                            if let Some(l) = logger
-                            {
-                                l.log(&ProcessEvent::ErrTxIdExists(tx_id));
-                            }
-                            continue;
-                        }
-                
-                        let account = self
-                            .accounts
-                            .entry(client_id)
-                            .or_insert(Account::new());
-
-                        if amount > account.available 
-                        {
-                            if let Some(l) = logger
-                            {
-                                l.log(&ProcessEvent::ErrInsufficientfunds(client_id, tx_id));
-                            }
-                            continue;
-                        }
-
-                        account.withdraw(&amount);
-
-                        let tx = Tx::new(tx_id, client_id, amount, false);
-                        self.tx_history.entry(tx_id).or_insert(tx);
+                        let recorded = self.audit(line, Some(outcome.command.client()), sink);
+                        keep_first_failure(&mut sink_failure, recorded);
                     }
-                    "dispute" => 
+                }
+                Err(event) =>
+                {
+                    refused += 1;
+                    let row = row.map_or(String::new(), raw_text);
+                    keep_first_failure(&mut sink_failure, sink.record_row(line, &row, &event));
+                }
+            }
+        });
+
+        if result.is_ok()
+        {
+            let complete = RunNote::InputComplete { rows, refused };
+            keep_first_failure(&mut sink_failure, sink.note(0, &complete));
+        }
+
+        if self.audit != AuditMode::Off
+        {
+            let recorded = self.audit(0, None, sink);
+            keep_first_failure(&mut sink_failure, recorded);
+        }
+        self.close_summary(started.elapsed());
+
+        result?;
+        match sink_failure
+        {
+            Some(err) => Err(ProcessError::ExternalErr(format!("an event could not be \
+                                                                recorded: {err}"))),
+            None => Ok(())
+        }
+    }
+
+    // applies a single transaction to the accounts. All of the engine's
+    // rules live here, process_transactions calls this for every row.
+    pub fn apply(&mut self, command : TxCommand)
+    -> Result<TxOutcome, TxError>
+    {
+        self.apply_at(command, 0)
+    }
+
+    // as apply, 'line' is the line of the file the command came
+    // from, which is recorded in the journal and the event stream.
+    pub(crate) fn apply_at(&mut self, command : TxCommand, line : u64)
+    -> Result<TxOutcome, TxError>
+    {
+        let command = self.owning_command(command);
+        self.note_opening(command.client());
+        if let Some(history) = &mut self.history
+        {
+            history.open(command.client(),
+                         &self.accounts.get(&command.client()).cloned().unwrap_or_default());
+        }
+
+        let result = self.execute(command, line);
+        match &result
+        {
+            Ok(outcome) =>
+            {
+                if let Some(history) = &mut self.history
+                {
+                    history.push(line, outcome);
+                }
+                self.record_flow(&command, outcome.amount, outcome.reversed);
+                self.ledger.post_command(&command, outcome.amount, outcome.reversed);
+                self.record_event(line, EventKind::Applied
+                {
+                    command,
+                    amount   : outcome.amount,
+                    reversed : outcome.reversed
+                });
+            }
+            Err(err) =>
+            {
+                self.record_event(line, EventKind::rejected(Some(command),
+                                                            &ProcessError::from(*err)));
+            }
+        }
+        result
+    }
+
+    // under CrossClientPolicy::Owner, a dispute, resolve or chargeback
+    // of another client's transaction becomes that client's command.
+    fn owning_command(&self, command : TxCommand)
+    -> TxCommand
+    {
+        if self.cross_client != CrossClientPolicy::Owner || command.amount().is_some()
+        {
+            return command;
+        }
+        match self.tx_history.get(&command.tx())
+        {
+            Some(tx) if tx.client != command.client() =>
+            {
+                TxCommand::new(command.kind(), tx.client, command.tx(), dec!(0))
+            }
+            _ => command
+        }
+    }
+
+    // checks a command against the engine's rules and, if it passes,
+    // applies it to the accounts and transactions.
+    fn execute(&mut self, command : TxCommand, line : u64)
+    -> Result<TxOutcome, TxError>
+    {
+        let client_id: u16 = command.client();
+        let tx_id: u32 = command.tx();
+
+        // refuse anything the lock policy doesn't
+        // permit against a locked (frozen) account.
+        if let Some(account) = self.accounts.get(&client_id)
+        {
+            if account.locked && !self.lock_policy.permits(command.kind())
+            {
+                return Err(TxError::AccountLocked(client_id, tx_id));
+            }
+        }
+
+        // whether the command disputes, resolves or charges back a
+        // withdrawal under WithdrawalDisputePolicy::Reverse.
+        let mut reverses = false;
+
+        let amount = match command
+        {
+            TxCommand::Deposit { amount, .. } =>
+            {
+                let amount = amount.round_dp(self.rounding_dp);
+                if self.reject_negative && amount < dec!(0.0000)
+                {
+                    return Err(TxError::AmountNegative(tx_id));
+                }
+
+                // the balance is checked before anything is recorded.
+                let mut account = self.accounts.get(&client_id).cloned().unwrap_or_default();
+                account.deposit(&amount)
+                       .map_err(|_| TxError::BalanceOverflow(client_id, tx_id))?;
+
+                // the registry may be shared with other engines, so
+                // claiming the id also checks it hasn't been used.
+                // the journal entry is written under the same lock.
+                let journal = &mut self.journal;
+                let tx = Tx::new(tx_id, client_id, TxKind::Deposit, amount, TxState::Processed);
+                self.tx_history.insert_new_with(tx, || write_ahead(journal, line, &command))?;
+
+                self.accounts.insert(client_id, account);
+                amount
+            }
+            TxCommand::Withdrawal { amount, .. } =>
+            {
+                let amount = amount.round_dp(self.rounding_dp);
+                if self.reject_negative && amount < dec!(0.0000)
+                {
+                    return Err(TxError::AmountNegative(tx_id));
+                }
+
+                if self.tx_history.contains_key(&tx_id)
+                {
+                    return Err(TxError::TxIdExists(tx_id));
+                }
+
+                let account = self
+                    .accounts
+                    .entry(client_id)
+                    .or_default();
+
+                if amount > account.available
+                {
+                    return Err(TxError::InsufficientFunds(client_id, tx_id));
+                }
+
+                // a negative withdrawal, when they're allowed, adds to the balance.
+                let mut updated = account.clone();
+                updated.withdraw(&amount)
+                       .map_err(|_| TxError::BalanceOverflow(client_id, tx_id))?;
+
+                let journal = &mut self.journal;
+                let tx = Tx::new(tx_id, client_id, TxKind::Withdrawal, amount, TxState::Processed);
+                self.tx_history.insert_new_with(tx, || write_ahead(journal, line, &command))?;
+
+                *account = updated;
+                amount
+            }
+            TxCommand::Dispute { .. }       |
+            TxCommand::Resolve { .. }       |
+            TxCommand::Chargeback { .. }    =>
+            {
+                let kind = command.kind();
+                let policy = self.dispute_policy;
+
+                // the ownership and state checks, the journal entry and
+                // the state change are made under the registry's lock.
+                let journal = &mut self.journal;
+                let accounts = &*self.accounts;
+                let referenced = self.tx_history.update(tx_id, |tx|
+                {
+                    // this wasn't mentioned in the text
+                    // since tx_ids are globally unique
+                    // a client could reference a tx which
+                    // is not associated with their account
+                    // which shouldn't happen. Disputes, resolves
+                    // and chargebacks are all refused the same way,
+                    // unless the cross client policy made the command
+                    // the owner's (see owning_command).
+                    if tx.client != client_id
                     {
-                        match self.tx_history.get_mut(&tx_id) 
-                        {
-                            Some(tx) => 
-                            {
-                                // this wasn't mentioned in the text
-                                // since tx_ids are globally unique
-                                // a client could reference a tx which
-                                // is not associated with their account
-                                // which shouldn't happen.
-                                if tx.client != client_id
-                                {
-                                    // if logging enabled
-                                    if let Some(l) = logger
-                                    {
-                                        l.log(&ProcessEvent::ErrTxNotFound(tx_id));
-                                    }
-                                    continue;
-                                }
-
-                                let amount = tx.amount;
-                                let account = self
-                                .accounts
-                                .entry(client_id)
-                                .or_insert(Account::new());
-
-                                tx.disputed = true;
-                                account.withhold(&amount);
-                            }
-                            None =>
-                            {
-                                // if logging enabled
-                                if let Some(l) = logger
-                                {
-                                    l.log(&ProcessEvent::ErrTxNotFound(tx_id));
-                                }
-                                continue;
-                            }
-                        }
+                        return Err(TxError::UnauthorisedTx(client_id, tx_id));
                     }
-                    "resolve" => 
+
+                    if kind == TxKind::Dispute
+                    && tx.kind == TxKind::Withdrawal
+                    && policy == WithdrawalDisputePolicy::Reject
                     {
-                        match self.tx_history.get_mut(&tx_id) 
-                        {
-                            Some(tx) =>
-                            {
-                                // this wasn't mentioned in the text
-                                // since tx_ids are globally unique
-                                // a client could reference a tx which
-                                // is not associated with their account
-                                // which shouldn't happen.
-                                if tx.client != client_id
-                                {
-                                    // if logging enabled
-                                    if let Some(l) = logger
-                                    {
-                                        l.log(&ProcessEvent::ErrTxNotFound(tx_id));
-                                    }
-                                    continue;
-                                }
-
-                                if !tx.disputed
-                                {
-                                    if let Some(l) = logger
-                                    {
-                                        l.log(&ProcessEvent::ErrTxNotDisputed(tx.id));
-                                    }
-                                    continue;
-                                }
-                                
-                                // apply the resolve transaction.
-                                let account = self.accounts.entry(client_id)
-                                                  .or_insert(Account::new());
-                                account.release_held(&tx.amount);
-                                tx.disputed = false;
-                            }
-                            None =>
-                            {
-                                if let Some(l) = logger
-                                {
-                                    l.log(&ProcessEvent::ErrTxNotFound(tx_id));
-                                }
-                                continue;
-                            }
-                        }    
+                        return Err(TxError::WithdrawalNotDisputable(tx_id));
                     }
-                    "chargeback" =>
+
+                    let next = tx.state.next(tx_id, kind)?;
+                    let reverses = tx.kind == TxKind::Withdrawal
+                                && policy == WithdrawalDisputePolicy::Reverse;
+
+                    // the deposit or withdrawal may have been applied by an
+                    // engine sharing the registry with its own accounts, so
+                    // the account must be in this one's. The balances are
+                    // checked before the state changes.
+                    let mut account = accounts.get(&client_id)
+                                              .cloned()
+                                              .ok_or(TxError::AccountNotFound(client_id, tx_id))?;
+                    let moved = match (kind, reverses)
                     {
-                        match self.tx_history.get_mut(&tx_id)
-                        {
-                            Some(tx) =>
-                            {
-
-                                // this wasn't mentioned in the text
-                                // since tx_ids are globally unique
-                                // a client could reference a tx which
-                                // is not associated with their account
-                                // which shouldn't happen.
-                                if tx.client != client_id
-                                {
-                                    // if logging enabled
-                                    if let Some(l) = logger
-                                    {
-                                        l.log(&ProcessEvent::ErrUnauthorisedTx(client_id,tx_id));
-                                    }
-                                    continue;
-                                }
-
-                                if !tx.disputed
-                                {
-                                    if let Some(l) = logger
-                                    {
-                                        l.log(&ProcessEvent::ErrTxNotDisputed(tx.id));
-                                    }
-                                    continue;
-                                }
-
-                                let account = self.accounts.entry(client_id)
-                                .or_insert(Account::new());
-          
-                                account.charge(&tx.amount);
-                                account.lock();
-                                tx.disputed = false;
-                            }
-                            None => 
-                            {
-                                if let Some(l) = logger
-                                {
-                                    l.log(&ProcessEvent::ErrTxNotFound(tx_id));
-                                }
-                            }
-                        }
-                    },
-                    _ => 
+                        (TxKind::Dispute, false) => account.withhold(&tx.amount),
+                        (TxKind::Resolve, false) => account.release_held(&tx.amount),
+                        // the disputed withdrawal is reversed into held.
+                        (TxKind::Dispute, true) => account.hold(&tx.amount),
+                        // the withdrawal stands, the reversed funds leave again.
+                        (TxKind::Resolve, true) => account.charge(&tx.amount),
+                        // the withdrawal is reversed for good.
+                        (_, true) => account.release_held(&tx.amount),
+                        _ => account.charge(&tx.amount)
+                    };
+                    moved.map_err(|_| TxError::BalanceOverflow(client_id, tx_id))?;
+                    if kind == TxKind::Chargeback
                     {
-                        if let Some(l) = logger
-                        {
-                            l.log(&ProcessEvent::ErrUnrecognisedTx(entry_num, 
-                                  tx_type.to_string()));
-                        }
+                        account.lock();
                     }
+
+                    write_ahead(journal, line, &command)?;
+                    tx.state = next;
+                    Ok((tx.amount, reverses, account))
+                });
+
+                let (amount, reversed, account) = match referenced
+                {
+                    Some(referenced) => referenced?,
+                    None => return Err(TxError::TxNotFound(tx_id))
+                };
+
+                reverses = reversed;
+                self.accounts.insert(client_id, account);
+                amount
+            }
+        };
+
+        Ok(TxOutcome
+        {
+            command,
+            amount,
+            account: self.accounts[&client_id].clone(),
+            reversed: reverses
+        })
+    }
+}
+
+// journals a command which is about to be applied, if journaling.
+fn write_ahead(journal : &mut Option<Journal>, line : u64, command : &TxCommand)
+-> Result<(), TxError>
+{
+    match journal
+    {
+        Some(journal) =>
+        {
+            journal.append(line, command)
+                   .map_err(|_| TxError::JournalFailed(command.tx()))
+        }
+        None => Ok(())
+    }
+}
+
+// reads every row of the transactions file, parsing each into a
+// TxRecord and passing it to 'f' along with the row's line number and
+// the row itself (None for a row which couldn't be read at all).
+// A row that cannot be read or parsed is passed to 'f' as the event
+// describing why, and reading carries on with the next row; only a
+// failure of the underlying reader ends the loop early.
+pub(crate) fn read_records<R, F>(data : &mut csv::Reader<R>, mut f : F)
+-> Result<(), ProcessError>
+where R: std::io::Read,
+      F: FnMut(u64, Option<&csv::StringRecord>, Result<TxRecord, ProcessError>)
+{
+    let mut entry = csv::StringRecord::new();
+    loop
+    {
+        match data.read_record(&mut entry)
+        {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(err) =>
+            {
+                if let csv::ErrorKind::Io(_) = err.kind()
+                {
+                    return Err(ProcessError::ExternalErr(err.to_string()));
                 }
+
+                let event = read_error(&err, &entry);
+                let line = event_line(&err, &entry);
+                let row = match event
+                {
+                    ProcessError::ErrMalformedRow(..) => None,
+                    _ => Some(&entry)
+                };
+                f(line, row, Err(event));
+                continue;
             }
         }
-        ProcessEvent::ProcessComplete
+
+        let line = entry.position().map_or(0, |pos| pos.line());
+        f(line, Some(&entry), TxRecord::parse(&entry, line));
     }
+    Ok(())
 }
 
+// keeps the first of several events a sink failed to record.
+pub(crate) fn keep_first_failure(failure  : &mut Option<std::io::Error>,
+                                 recorded : std::io::Result<()>)
+{
+    if let (None, Err(err)) = (&failure, recorded)
+    {
+        *failure = Some(err);
+    }
+}
+
+// the line of a row the csv reader failed to read.
+fn event_line(err : &csv::Error, entry : &csv::StringRecord)
+-> u64
+{
+    err.position()
+       .or(entry.position())
+       .map_or(0, |pos| pos.line())
+}
+
+// the event describing a row the csv reader failed to read.
+fn read_error(err : &csv::Error, entry : &csv::StringRecord)
+-> ProcessError
+{
+    let line = event_line(err, entry);
+    match err.kind()
+    {
+        csv::ErrorKind::UnequalLengths { .. } =>
+        {
+            ProcessError::ErrColumnCount(line, raw_text(entry))
+        }
+        _ => ProcessError::ErrMalformedRow(line, err.to_string())
+    }
+}
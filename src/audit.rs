@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use rust_decimal::prelude::*;
+
+use crate::command::TxCommand;
+use crate::common::*;
+use crate::engine::Engine;
+use crate::ledger::LedgerAccount;
+use crate::sink::EventSink;
+
+// when the engine checks its ledger invariants:
+//
+//   - every account's available + held equals the balance it started
+//     the run with, plus its deposits, minus its withdrawals and
+//     chargebacks (plus any withdrawals reversed by a dispute).
+//   - every account's held equals the sum of its disputed transactions.
+//   - every account's available and held match its balances in the
+//     general ledger.
+//
+// EveryTx checks the client of each applied row as well as checking
+// every client at the end. It sums the client's disputed transactions
+// each time, so it's meant for debugging and audits rather than speed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AuditMode
+{
+    #[default]
+    Off,
+    EndOfRun,
+    EveryTx
+}
+
+impl AuditMode
+{
+    pub fn from_name(name : &str)
+    -> Option<Self>
+    {
+        match name
+        {
+            "off"       => Some(AuditMode::Off),
+            "end"       => Some(AuditMode::EndOfRun),
+            "every-tx"  => Some(AuditMode::EveryTx),
+            _           => None
+        }
+    }
+}
+
+// the money which has moved through a client's account this run,
+// recorded separately from the account so the two can be compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ClientFlows
+{
+    // available + held when the engine first saw the client, e.g.
+    // from a snapshot or a checkpoint. It's taken as given.
+    pub opening     : Decimal,
+    pub deposits    : Decimal,
+    pub withdrawals : Decimal,
+    pub chargebacks : Decimal,
+    // withdrawals reversed into held while disputed, see
+    // WithdrawalDisputePolicy::Reverse.
+    pub reversed    : Decimal
+}
+
+impl ClientFlows
+{
+    // what available + held should be.
+    pub fn expected_total(&self)
+    -> Decimal
+    {
+        self.opening.saturating_add(self.deposits)
+                    .saturating_sub(self.withdrawals)
+                    .saturating_add(self.reversed)
+                    .saturating_sub(self.chargebacks)
+    }
+}
+
+// an invariant which doesn't hold for a client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation
+{
+    TotalMismatch   { client : u16, expected : Decimal, actual : Decimal },
+    HeldMismatch    { client : u16, expected : Decimal, actual : Decimal },
+    // 'expected' is the ledger's balance, 'actual' the account's.
+    LedgerMismatch  { client : u16, account : LedgerAccount,
+                      expected : Decimal, actual : Decimal }
+}
+
+impl Violation
+{
+    pub fn client(&self)
+    -> u16
+    {
+        match *self
+        {
+            Violation::TotalMismatch { client, .. }     |
+            Violation::HeldMismatch { client, .. }      |
+            Violation::LedgerMismatch { client, .. }    => client
+        }
+    }
+}
+
+impl fmt::Display for Violation
+{
+    fn fmt(&self, f : &mut fmt::Formatter<'_>)
+    -> fmt::Result
+    {
+        match self
+        {
+            Violation::TotalMismatch { client, expected, actual } =>
+            {
+                write!(f, "client {client}: available + held is {actual}, but opening \
+                       + deposits - withdrawals + reversed - chargebacks is {expected}")
+            }
+            Violation::HeldMismatch { client, expected, actual } =>
+            {
+                write!(f, "client {client}: held is {actual}, but its disputed \
+                       transactions add up to {expected}")
+            }
+            Violation::LedgerMismatch { client, account, expected, actual } =>
+            {
+                write!(f, "client {client}: the account has {actual}, but the \
+                       ledger's {account} balance is {expected}")
+            }
+        }
+    }
+}
+
+impl<'a> Engine<'a>
+{
+    // checks every client's invariants, returning each violation.
+    pub fn check_invariants(&self)
+    -> Vec<Violation>
+    {
+        let held = self.tx_history.with_txs(disputed_by_client);
+
+        let mut clients : Vec<u16> = self.accounts.keys()
+                                     .chain(self.flows.keys())
+                                     .chain(held.keys())
+                                     .copied()
+                                     .collect();
+        clients.sort();
+        clients.dedup();
+
+        clients.into_iter()
+               .flat_map(|client| self.compare(client, held.get(&client).copied()))
+               .collect()
+    }
+
+    // checks one client's invariants.
+    pub fn check_client(&self, client : u16)
+    -> Vec<Violation>
+    {
+        let held = self.tx_history.with_txs(|txs|
+        {
+            txs.values()
+               .filter(|tx| tx.client == client && tx.is_disputed())
+               .map(|tx| tx.amount.round_dp(MAX_DP))
+               .sum()
+        });
+        self.compare(client, Some(held))
+    }
+
+    fn compare(&self, client : u16, disputed : Option<Decimal>)
+    -> Vec<Violation>
+    {
+        let account = self.accounts.get(&client).cloned().unwrap_or_default();
+        let mut violations = Vec::new();
+
+        // a client the engine hasn't applied anything to has no
+        // flows, its balance is still the one it started with.
+        if let Some(flows) = self.flows.get(&client)
+        {
+            // the engine keeps totals within MAX_AMOUNT, but accounts
+            // can be set by hand, so the sums here saturate.
+            let expected = flows.expected_total();
+            let actual = account.available.saturating_add(account.held);
+            if expected != actual
+            {
+                violations.push(Violation::TotalMismatch { client, expected, actual });
+            }
+
+            // the ledger only has the clients the engine has seen.
+            let (available, held) = self.ledger.client_balances(client);
+            let ledger = [(LedgerAccount::ClientAvailable(client), available, account.available),
+                          (LedgerAccount::ClientHeld(client), held, account.held)];
+            for (ledger_account, expected, actual) in ledger
+            {
+                if expected != actual
+                {
+                    violations.push(Violation::LedgerMismatch { client,
+                                    account : ledger_account, expected, actual });
+                }
+            }
+        }
+
+        let expected = disputed.unwrap_or(Decimal::ZERO);
+        if expected != account.held
+        {
+            violations.push(Violation::HeldMismatch { client, expected, actual : account.held });
+        }
+        violations
+    }
+
+    // records the client's opening balance, the first time a command
+    // for it reaches the engine and before anything is changed.
+    // the opening balance is posted to the ledger at the same time.
+    pub(crate) fn note_opening(&mut self, client : u16)
+    {
+        if self.flows.contains_key(&client)
+        {
+            return;
+        }
+
+        let account = self.accounts.get(&client).cloned().unwrap_or_default();
+        self.ledger.post_opening(client, &account);
+        self.flows.insert(client, ClientFlows
+        {
+            opening : account.available.saturating_add(account.held),
+            ..ClientFlows::default()
+        });
+    }
+
+    // records the money moved by a command which has been applied,
+    // 'reverses' is set if it concerns a withdrawal reversed by a dispute.
+    pub(crate) fn record_flow(&mut self,
+                              command   : &TxCommand,
+                              amount    : Decimal,
+                              reverses  : bool)
+    {
+        // rounded as the account rounds it.
+        let amount = amount.round_dp(MAX_DP);
+        let flows = self.flows.entry(command.client()).or_default();
+        // the totals saturate rather than overflow, a balance can't
+        // go beyond Decimal::MAX but the money moved through it can.
+        let add = |total : &mut Decimal, amount : Decimal| *total = total.saturating_add(amount);
+        match (command, reverses)
+        {
+            (TxCommand::Deposit { .. }, _)          => add(&mut flows.deposits, amount),
+            (TxCommand::Withdrawal { .. }, _)       => add(&mut flows.withdrawals, amount),
+            (TxCommand::Dispute { .. }, true)       => add(&mut flows.reversed, amount),
+            (TxCommand::Resolve { .. }, true)       => add(&mut flows.reversed, -amount),
+            (TxCommand::Chargeback { .. }, false)   => add(&mut flows.chargebacks, amount),
+            // otherwise money only moves between available
+            // and held, the total is unchanged.
+            _ => {}
+        }
+    }
+
+    // checks the invariants, reporting every violation to the sink and
+    // keeping it against 'line' (0 for the checks made at the end of a
+    // run). Every violation is kept even if the sink fails to record
+    // one, the first failure is returned.
+    pub(crate) fn audit(&mut self,
+                        line    : u64,
+                        client  : Option<u16>,
+                        sink    : &mut dyn EventSink)
+    -> std::io::Result<()>
+    {
+        let mut recorded = Ok(());
+        let violations = match client
+        {
+            Some(client) => self.check_client(client),
+            None => self.check_invariants()
+        };
+
+        for violation in violations
+        {
+            let event = ProcessError::ErrInvariantViolated(line, violation.to_string());
+            recorded = recorded.and(sink.record(line, &event));
+            self.violations.push((line, violation));
+        }
+        recorded
+    }
+}
+
+// the sum of the disputed transactions of each client.
+fn disputed_by_client(txs : &HashMap<u32, Tx>)
+-> HashMap<u16, Decimal>
+{
+    let mut held = HashMap::new();
+    for tx in txs.values().filter(|tx| tx.is_disputed())
+    {
+        *held.entry(tx.client).or_insert(Decimal::ZERO) += tx.amount.round_dp(MAX_DP);
+    }
+    held
+}
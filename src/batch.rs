@@ -0,0 +1,215 @@
+use std::io::{self, Write};
+
+use rust_decimal::prelude::*;
+use rust_decimal_macros::dec;
+
+use crate::common::*;
+use crate::dry_run::DryRun;
+use crate::engine::Engine;
+use crate::input::Input;
+use crate::sink::{json_string, EventSink};
+use crate::summary::SummaryFormat;
+
+// the thresholds a batch must meet to be committed, see
+// Engine::process_batch. None is no limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchPolicy
+{
+    // the most rows which couldn't be parsed, 0 by default.
+    pub max_row_errors          : Option<u64>,
+    // the most rows rejected for any reason, as a percentage
+    // of the rows read.
+    pub max_rejected_percent    : Option<Decimal>
+}
+
+impl Default for BatchPolicy
+{
+    fn default()
+    -> Self
+    {
+        BatchPolicy
+        {
+            max_row_errors          : Some(0),
+            max_rejected_percent    : None
+        }
+    }
+}
+
+impl BatchPolicy
+{
+    // why a staged batch can't be committed, empty if it can. A batch
+    // which failed, or in which the audit found violations, never is.
+    pub fn check(&self, run : &DryRun)
+    -> Vec<String>
+    {
+        let mut reasons : Vec<String> = Vec::new();
+        if let Err(err) = &run.result
+        {
+            reasons.push(format!("the batch failed: {err}"));
+        }
+
+        let row_errors = run.rejections.iter()
+                                       .filter(|rejection|
+                                       {
+                                           rejection.error.category() == ErrorCategory::Row
+                                       })
+                                       .count() as u64;
+        if let Some(max) = self.max_row_errors.filter(|max| row_errors > *max)
+        {
+            reasons.push(format!("{row_errors} rows couldn't be parsed, at most {max} may be"));
+        }
+
+        if let Some(max) = self.max_rejected_percent.filter(|_| run.summary.rows > 0)
+        {
+            let percent = Decimal::from(run.summary.rejected_total()) * dec!(100)
+                        / Decimal::from(run.summary.rows);
+            if percent > max
+            {
+                reasons.push(format!("{:.2}% of rows were rejected, at most {max}% may be",
+                                     percent.round_dp(2)));
+            }
+        }
+
+        if !run.violations.is_empty()
+        {
+            reasons.push(format!("the audit found {} violations", run.violations.len()));
+        }
+        reasons
+    }
+}
+
+// what became of a batch.
+#[derive(Debug, Clone)]
+pub struct BatchReport
+{
+    // the input the batch was read from, None for a reader.
+    pub input       : Option<String>,
+    pub committed   : bool,
+    // why the batch was rolled back, empty if it was committed.
+    pub reasons     : Vec<String>,
+    // the rows rejected and the balance changes made, or which would
+    // have been made had the batch been committed.
+    pub run         : DryRun
+}
+
+impl BatchReport
+{
+    pub fn write<W>(&self, format : SummaryFormat, writer : &mut W)
+    -> io::Result<()>
+    where W: Write
+    {
+        match format
+        {
+            SummaryFormat::Text => self.write_text(writer),
+            SummaryFormat::Json => self.write_json(writer)
+        }
+    }
+
+    //   batch monday.csv: rolled back
+    //     2 rows couldn't be parsed, at most 0 may be
+    //   <the run, as DryRun writes it>
+    fn write_text<W>(&self, writer : &mut W)
+    -> io::Result<()>
+    where W: Write
+    {
+        let input = self.input.as_ref().map_or(String::new(), |input| format!(" {input}"));
+        match self.committed
+        {
+            true  => writeln!(writer, "batch{input}: committed")?,
+            false => writeln!(writer, "batch{input}: rolled back")?
+        }
+        for reason in &self.reasons
+        {
+            writeln!(writer, "  {reason}")?;
+        }
+        self.run.write(SummaryFormat::Text, writer)
+    }
+
+    // {"file":"monday.csv","committed":false,"reasons":["..."],"run":{...}}
+    fn write_json<W>(&self, writer : &mut W)
+    -> io::Result<()>
+    where W: Write
+    {
+        let mut run : Vec<u8> = Vec::new();
+        self.run.write(SummaryFormat::Json, &mut run)?;
+        let run = String::from_utf8_lossy(&run);
+
+        let reasons = self.reasons.iter()
+                                  .map(|reason| json_string(reason))
+                                  .collect::<Vec<_>>()
+                                  .join(",");
+        writeln!(writer, "{{\"file\":{},\"committed\":{},\"reasons\":[{reasons}],\"run\":{}}}",
+                 self.input.as_deref().map_or(String::from("null"), json_string),
+                 self.committed,
+                 run.trim_end())?;
+        writer.flush()
+    }
+}
+
+impl<'a> Engine<'a>
+{
+    // processes an input as a single batch. The rows are applied to a
+    // copy of the engine's state, which replaces the engine's own only
+    // if the batch meets the policy; otherwise none of them are, and
+    // the engine is left as it was. The caller's sink gets every event
+    // either way, e.g. for a rejects report.
+    //
+    // staging copies the accounts and transactions, and the transaction
+    // registry is replaced on commit, so an engine sharing its registry
+    // with others shouldn't process batches. A journal records commands
+    // as they're applied, which a batch can't undo, so an engine with a
+    // journal refuses them.
+    pub fn process_batch(&mut self,
+                         input   : &Input,
+                         policy  : &BatchPolicy,
+                         sink    : &mut dyn EventSink)
+    -> BatchReport
+    {
+        let mut report = self.batch(policy, sink, |engine, sink|
+        {
+            engine.process_inputs(std::slice::from_ref(input), sink)
+        });
+        report.input = Some(input.name());
+        report
+    }
+
+    // as process_batch, for rows which aren't in a file.
+    pub fn process_batch_reader<R>(&mut self,
+                                   data    : &mut csv::Reader<R>,
+                                   policy  : &BatchPolicy,
+                                   sink    : &mut dyn EventSink)
+    -> BatchReport
+    where R: io::Read
+    {
+        self.batch(policy, sink, |engine, sink| engine.process_transactions(data, sink))
+    }
+
+    fn batch<F>(&mut self, policy : &BatchPolicy, sink : &mut dyn EventSink, run : F)
+    -> BatchReport
+    where F: FnOnce(&mut Engine, &mut dyn EventSink) -> Result<(), ProcessError>
+    {
+        let (dry_run, staged) = match self.journal
+        {
+            Some(_) => self.stage(sink, true, |_, _|
+            {
+                Err(ProcessError::ExternalErr(String::from("an engine with a journal \
+                                                            can't process batches")))
+            }),
+            None => self.stage(sink, true, run)
+        };
+
+        let reasons = policy.check(&dry_run);
+        let committed = reasons.is_empty();
+        if committed
+        {
+            self.commit(staged, &dry_run);
+        }
+        else if let Some(summary) = &mut self.summary
+        {
+            summary.rows += dry_run.summary.rows;
+            summary.rolled_back += dry_run.summary.rows;
+            summary.elapsed += dry_run.summary.elapsed;
+        }
+        BatchReport { input : None, committed, reasons, run : dry_run }
+    }
+}
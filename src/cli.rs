@@ -0,0 +1,752 @@
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::audit::AuditMode;
+use crate::batch::BatchPolicy;
+use crate::common::{LockPolicy, LogLevel, WithdrawalDisputePolicy};
+use crate::output::OutputFormat;
+use crate::statement::StatementFormat;
+use crate::summary::SummaryFormat;
+
+// the command line: a command and its options, parsed by parse_args.
+// Parsing never fails with a panic, anything it doesn't understand is
+// returned as an error in words for the user.
+
+pub const HELP : &str = "\
+tx_engine, a toy payments engine
+
+usage:
+ tx_engine <command> [arguments] [options]
+ tx_engine <transactions files...> [options]   (the same as 'process')
+
+commands:
+ process     apply transactions files and write the final accounts
+ validate    run transactions files through the rules without keeping
+             the results, and write a summary of what would happen
+ statement   write a client's statement from transactions files
+ replay      rebuild the accounts from an event stream
+ diff        compare two accounts files
+ generate    write a random transactions file
+ help        show the help for a command
+
+run 'tx_engine <command> --help' for a command's arguments and options.";
+
+pub const PROCESS_HELP : &str = "\
+usage:
+ tx_engine process <transactions files...> [options]
+
+each transactions file is a path, a glob such as 'partners/*.csv', or
+'-' for stdin. The files are processed in order against the same
+accounts, and the final accounts are written to stdout.
+
+output:
+ --format <csv|json|jsonl>   format of the accounts (default csv)
+ --output <path>             write the accounts to a file instead of stdout
+ --as-of-tx <client>:<tx>    write the client's balance right after the
+                             deposit or withdrawal tx, instead of the
+                             final accounts
+ --as-of-line <client>:<line>
+                             write the client's balance once every row up
+                             to the line had been processed, instead of
+                             the final accounts
+
+batches:
+ --atomic                    process each file as a batch, whose changes are
+                             kept only if it meets the thresholds below and
+                             otherwise rolled back
+ --max-row-errors <n|any>    the most rows of a batch which may fail to
+                             parse (default 0), implies --atomic
+ --max-rejected <percent>    the most rows of a batch which may be rejected,
+                             as a percentage of its rows (default any),
+                             implies --atomic
+ --batch-report <path>       write what became of each batch to a file
+                             instead of stderr (see --summary for the
+                             format, default text)
+";
+
+// the options shared by the commands which process transactions.
+const REPORTING_HELP : &str = "\
+reporting:
+ --log <path>                append the events to a log file
+ --log-level <debug|info|warn|error>
+                             the least severe events logged (default info)
+ --log-stderr                write the events to stderr as well
+ --rejects <path>            write every refused row, with its file, line,
+                             code and reason, to a csv file
+ --summary <text|json>       write a summary of the run to stderr
+ --exit-code-rejected <code> exit with this code when the run completed but
+                             some rows were rejected, or a batch was rolled
+                             back (default 0)
+ --exit-code-failed <code>   exit with this code when the run failed
+                             (default 1)";
+
+const RULES_HELP : &str = "\
+rules:
+ --config <path>             read the engine's rules from a toml file, see
+                             the README. The options below override it
+ --from-snapshot <dir>       start from the accounts and tx history saved
+                             in a snapshot directory by an earlier run
+ --withdrawal-disputes <withhold|reverse|reject>
+                             what disputing a withdrawal does (default
+                             withhold, see the README)
+ --lock-policy <unenforced|block-withdrawals|block-all-but-settlement|block-all>
+                             what a locked account refuses (default
+                             block-all-but-settlement)
+ --audit <off|end|every-tx>  check the ledger invariants at the end of the
+                             run, or after every transaction as well. Any
+                             violation is reported and fails the run";
+
+const PERSISTENCE_HELP : &str = "\
+state:
+ --journal <path>            journal every applied transaction, recovering
+                             from the journal first if it has entries
+ --checkpoint <path>         where the journal keeps its checkpoint
+                             (default: the journal path + .checkpoint)
+ --save-snapshot <dir>       save the accounts and tx history to a snapshot
+                             directory once the files have been processed
+ --events <path>             record every accepted and rejected operation
+                             to an event stream file
+ --trial-balance <path>      write the general ledger's trial balance to a
+                             csv file once the files have been processed";
+
+pub const VALIDATE_HELP : &str = "\
+usage:
+ tx_engine validate <transactions files...> [options]
+
+runs the files through every rule, as process would, against a copy
+of the opening balances (see --from-snapshot), without writing the
+accounts or saving any state. What would happen is written to stdout
+(see --summary for the format, default text): the run summary, every
+row which would be rejected, and how each client's balances would
+change.
+";
+
+pub const STATEMENT_HELP : &str = "\
+usage:
+ tx_engine statement <client> <transactions files...> [options]
+
+writes every deposit, withdrawal, dispute, resolve and chargeback
+applied to the client's account, with the running balances.
+
+output:
+ --lines <from>:<to>         the lines the statement covers (default all)
+ --format <csv|text>         format of the statement (default csv)
+ --output <path>             write the statement to a file instead of stdout
+";
+
+pub const REPLAY_HELP : &str = "\
+usage:
+ tx_engine replay <event stream> [options]
+
+rebuilds the accounts from an event stream written with --events, and
+writes them to stdout.
+
+options:
+ --from-snapshot <dir>       start from the snapshot the recorded run
+                             started from
+ --format <csv|json|jsonl>   format of the accounts (default csv)
+ --output <path>             write the accounts to a file instead of stdout";
+
+pub const DIFF_HELP : &str = "\
+usage:
+ tx_engine diff <before> <after> [options]
+
+compares two sets of accounts, each an accounts csv file or a snapshot
+directory, and writes a csv row for every client whose account differs:
+
+ client,available,held,total,was_locked,locked
+
+the amounts are the change from before to after.
+
+options:
+ --output <path>             write the differences to a file instead of stdout";
+
+pub const GENERATE_HELP : &str = "\
+usage:
+ tx_engine generate [options]
+
+writes a random transactions file, for load tests and demos.
+
+options:
+ --rows <n>                  the number of rows (default 1000)
+ --clients <n>               the number of clients (default 10)
+ --seed <n>                  the same seed always writes the same file
+                             (default random)
+ --output <path>             write the file to a path instead of stdout";
+
+// a point in time balance or a statement, to write
+// instead of the final accounts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query
+{
+    AfterTx(u16, u32),
+    AsOfLine(u16, u64),
+    // None covers every line.
+    Statement(u16, Option<RangeInclusive<u64>>, StatementFormat)
+}
+
+// the options of the commands which process transactions files:
+// process, validate and statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunArgs
+{
+    // the transactions files as given, before globs are expanded.
+    pub inputs        : Vec<String>,
+    pub log_file      : Option<String>,
+    pub log_level     : LogLevel,
+    pub log_stderr    : bool,
+    pub rejects       : Option<String>,
+    pub summary       : Option<SummaryFormat>,
+    // the exit codes of a run which rejected rows, and of one which failed.
+    pub exit_rejected : i32,
+    pub exit_failed   : i32,
+    pub from_snapshot : Option<String>,
+    pub audit         : AuditMode,
+    // the rules file, and the rules given as options which override it.
+    pub config        : Option<String>,
+    pub disputes      : Option<WithdrawalDisputePolicy>,
+    pub lock_policy   : Option<LockPolicy>,
+    pub journal       : Option<String>,
+    pub checkpoint    : Option<String>,
+    pub save_snapshot : Option<String>,
+    pub events        : Option<String>,
+    pub trial_balance : Option<String>,
+    pub format        : OutputFormat,
+    pub output        : Option<String>,
+    pub query         : Option<Query>,
+    // set to process each file as a batch.
+    pub batch         : Option<BatchPolicy>,
+    pub batch_report  : Option<String>
+}
+
+impl Default for RunArgs
+{
+    fn default()
+    -> Self
+    {
+        RunArgs
+        {
+            inputs        : Vec::new(),
+            log_file      : None,
+            log_level     : LogLevel::default(),
+            log_stderr    : false,
+            rejects       : None,
+            summary       : None,
+            exit_rejected : 0,
+            exit_failed   : 1,
+            from_snapshot : None,
+            audit         : AuditMode::default(),
+            config        : None,
+            disputes      : None,
+            lock_policy   : None,
+            journal       : None,
+            checkpoint    : None,
+            save_snapshot : None,
+            events        : None,
+            trial_balance : None,
+            format        : OutputFormat::default(),
+            output        : None,
+            query         : None,
+            batch         : None,
+            batch_report  : None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayArgs
+{
+    pub events        : String,
+    pub from_snapshot : Option<String>,
+    pub format        : OutputFormat,
+    pub output        : Option<String>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffArgs
+{
+    pub before  : String,
+    pub after   : String,
+    pub output  : Option<String>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenerateArgs
+{
+    pub rows    : u32,
+    pub clients : u16,
+    // None for a random seed.
+    pub seed    : Option<u64>,
+    pub output  : Option<String>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command
+{
+    Process(RunArgs),
+    Validate(RunArgs),
+    // the query is always a Query::Statement.
+    Statement(RunArgs),
+    Replay(ReplayArgs),
+    Diff(DiffArgs),
+    Generate(GenerateArgs),
+    // the help asked for, with --help or 'help'.
+    Help(String)
+}
+
+const COMMANDS : [&str; 6] = ["process", "validate", "statement", "replay", "diff", "generate"];
+
+// parses the command line, args[0] being the program. An argument
+// which isn't a command is taken as the first transactions file of
+// 'process', so 'tx_engine transactions.csv' works as it always has.
+pub fn parse_args(args : &[String])
+-> Result<Command, String>
+{
+    let args = args.get(1..).unwrap_or_default();
+    let (command, args) = match args.first().map(String::as_str)
+    {
+        None => return Err(String::from("expected a command or a transactions file")),
+        Some("--help" | "-h") => return Ok(Command::Help(String::from(HELP))),
+        Some("help") => return match args.get(1)
+        {
+            None => Ok(Command::Help(String::from(HELP))),
+            Some(command) => help(command).map(Command::Help)
+                                          .ok_or(format!("'{command}' is not a command"))
+        },
+        Some(name) => match COMMANDS.into_iter().find(|command| *command == name)
+        {
+            Some(command) => (command, &args[1..]),
+            None => ("process", args)
+        }
+    };
+
+    if args.iter().any(|arg| arg == "--help" || arg == "-h")
+    {
+        return Ok(Command::Help(help(command).unwrap_or_default()));
+    }
+
+    let mut options = Options { command, args : args.iter() };
+    match command
+    {
+        "process"   => parse_process(&mut options),
+        "validate"  => parse_validate(&mut options),
+        "statement" => parse_statement(&mut options),
+        "replay"    => parse_replay(&mut options),
+        "diff"      => parse_diff(&mut options),
+        _           => parse_generate(&mut options)
+    }
+}
+
+// the help for a command, None if there's no such command.
+pub fn help(command : &str)
+-> Option<String>
+{
+    let run_help = |help : &str, sections : &[&str]| format!("{help}\n{}", sections.join("\n\n"));
+    match command
+    {
+        "process"   => Some(run_help(PROCESS_HELP, &[REPORTING_HELP, RULES_HELP, PERSISTENCE_HELP])),
+        "validate"  => Some(run_help(VALIDATE_HELP, &[REPORTING_HELP, RULES_HELP])),
+        "statement" => Some(run_help(STATEMENT_HELP, &[REPORTING_HELP, RULES_HELP])),
+        "replay"    => Some(String::from(REPLAY_HELP)),
+        "diff"      => Some(String::from(DIFF_HELP)),
+        "generate"  => Some(String::from(GENERATE_HELP)),
+        _           => None
+    }
+}
+
+// the arguments of a command, read in order.
+struct Options<'a>
+{
+    command : &'static str,
+    args    : std::slice::Iter<'a, String>
+}
+
+impl<'a> Options<'a>
+{
+    // the next argument, and whether it's an option. '-' is stdin,
+    // not an option.
+    fn next(&mut self)
+    -> Option<(&'a String, bool)>
+    {
+        self.args.next().map(|arg| (arg, arg.starts_with("--")))
+    }
+
+    fn value(&mut self, option : &str)
+    -> Result<&'a String, String>
+    {
+        self.args.next().ok_or(format!("{option} needs a value"))
+    }
+
+    fn path(&mut self, option : &str)
+    -> Result<Option<String>, String>
+    {
+        self.value(option).map(|value| Some(value.clone()))
+    }
+
+    // an option whose value is one of a set of names.
+    fn named<T>(&mut self, option : &str, what : &str, from_name : fn(&str) -> Option<T>)
+    -> Result<T, String>
+    {
+        let name = self.value(option)?;
+        from_name(name).ok_or(format!("'{name}' is not {what}"))
+    }
+
+    fn number<T>(&mut self, option : &str)
+    -> Result<T, String>
+    where T: FromStr
+    {
+        let value = self.value(option)?;
+        value.parse::<T>().map_err(|_| format!("{option} expects a number, not '{value}'"))
+    }
+
+    fn unknown(&self, option : &str)
+    -> String
+    {
+        format!("'{option}' is not an option of {}, see 'tx_engine {} --help'",
+                self.command, self.command)
+    }
+}
+
+// parses the '<client>:<n>' value of a query option.
+fn parse_query(option : &str, value : &str)
+-> Result<(u16, u64), String>
+{
+    let invalid = || format!("{option} expects <client>:<number>, not '{value}'");
+    let (client, n) = value.split_once(':').ok_or_else(invalid)?;
+    Ok((client.parse::<u16>().map_err(|_| invalid())?,
+        n.parse::<u64>().map_err(|_| invalid())?))
+}
+
+// parses the '<from>:<to>' value of --lines, 'from' can't be after 'to'.
+fn parse_range(value : &str)
+-> Result<RangeInclusive<u64>, String>
+{
+    let invalid = || format!("--lines expects <from>:<to>, not '{value}'");
+    let (from, to) = value.split_once(':').ok_or_else(invalid)?;
+    let (from, to) = (from.parse::<u64>().map_err(|_| invalid())?,
+                      to.parse::<u64>().map_err(|_| invalid())?);
+    if from > to
+    {
+        return Err(format!("--lines {value} ends before it starts"));
+    }
+    Ok(from..=to)
+}
+
+// an exit code given on the command line.
+fn parse_exit_code(option : &str, value : &str)
+-> Result<i32, String>
+{
+    value.parse::<u8>()
+         .map(i32::from)
+         .map_err(|_| format!("{option} expects a code from 0 to 255, not '{value}'"))
+}
+
+// the reporting options, returns false if 'option' isn't one of them.
+fn parse_reporting(option : &str, options : &mut Options, args : &mut RunArgs)
+-> Result<bool, String>
+{
+    match option
+    {
+        "--log"         => args.log_file = options.path(option)?,
+        "--log-level"   => args.log_level = options.named(option, "a log level",
+                                                          LogLevel::from_name)?,
+        "--log-stderr"  => args.log_stderr = true,
+        "--rejects"     => args.rejects = options.path(option)?,
+        "--summary"     =>
+        {
+            args.summary = Some(options.named(option, "a summary format",
+                                              SummaryFormat::from_name)?);
+        }
+        "--exit-code-rejected" => args.exit_rejected = parse_exit_code(option,
+                                                                       options.value(option)?)?,
+        "--exit-code-failed" => args.exit_failed = parse_exit_code(option,
+                                                                   options.value(option)?)?,
+        _ => return Ok(false)
+    }
+    Ok(true)
+}
+
+// the options for the engine's rules and where it starts from.
+fn parse_rules(option : &str, options : &mut Options, args : &mut RunArgs)
+-> Result<bool, String>
+{
+    match option
+    {
+        "--config" => args.config = options.path(option)?,
+        "--from-snapshot" => args.from_snapshot = options.path(option)?,
+        "--withdrawal-disputes" =>
+        {
+            args.disputes = Some(options.named(option, "a withdrawal dispute policy",
+                                               WithdrawalDisputePolicy::from_name)?);
+        }
+        "--lock-policy" =>
+        {
+            args.lock_policy = Some(options.named(option, "a lock policy",
+                                                  LockPolicy::from_name)?);
+        }
+        "--audit" => args.audit = options.named(option, "an audit mode", AuditMode::from_name)?,
+        _ => return Ok(false)
+    }
+    Ok(true)
+}
+
+// the options for the state a run keeps once it's complete.
+fn parse_persistence(option : &str, options : &mut Options, args : &mut RunArgs)
+-> Result<bool, String>
+{
+    match option
+    {
+        "--journal"         => args.journal = options.path(option)?,
+        "--checkpoint"      => args.checkpoint = options.path(option)?,
+        "--save-snapshot"   => args.save_snapshot = options.path(option)?,
+        "--events"          => args.events = options.path(option)?,
+        "--trial-balance"   => args.trial_balance = options.path(option)?,
+        _ => return Ok(false)
+    }
+    Ok(true)
+}
+
+// at least one transactions file, and stdin no more than once.
+fn check_inputs(inputs : &[String])
+-> Result<(), String>
+{
+    if inputs.is_empty()
+    {
+        return Err(String::from("expected at least one transactions file"));
+    }
+    if inputs.iter().filter(|input| *input == "-").count() > 1
+    {
+        return Err(String::from("stdin ('-') can only be read once"));
+    }
+    Ok(())
+}
+
+fn parse_process(options : &mut Options)
+-> Result<Command, String>
+{
+    let mut args = RunArgs::default();
+    while let Some((arg, is_option)) = options.next()
+    {
+        if !is_option
+        {
+            args.inputs.push(arg.clone());
+            continue;
+        }
+
+        let query = match arg.as_str()
+        {
+            "--format" =>
+            {
+                args.format = options.named(arg, "an output format", OutputFormat::from_name)?;
+                None
+            }
+            "--output" =>
+            {
+                args.output = options.path(arg)?;
+                None
+            }
+            "--as-of-tx" =>
+            {
+                let (client, tx) = parse_query(arg, options.value(arg)?)?;
+                let tx = u32::try_from(tx).map_err(|_| format!("{tx} is not a valid tx id"))?;
+                Some(Query::AfterTx(client, tx))
+            }
+            "--as-of-line" =>
+            {
+                let (client, line) = parse_query(arg, options.value(arg)?)?;
+                Some(Query::AsOfLine(client, line))
+            }
+            "--atomic" =>
+            {
+                args.batch.get_or_insert_with(BatchPolicy::default);
+                None
+            }
+            "--max-row-errors" =>
+            {
+                let max = match options.value(arg)?.as_str()
+                {
+                    "any" => None,
+                    value => Some(value.parse::<u64>()
+                                       .map_err(|_| format!("{arg} expects a number or 'any', \
+                                                             not '{value}'"))?)
+                };
+                args.batch.get_or_insert_with(BatchPolicy::default).max_row_errors = max;
+                None
+            }
+            "--max-rejected" =>
+            {
+                let value = options.value(arg)?;
+                let percent = value.trim_end_matches('%')
+                                   .parse::<Decimal>()
+                                   .ok()
+                                   .filter(|percent| (Decimal::ZERO..=dec!(100)).contains(percent))
+                                   .ok_or_else(|| format!("{arg} expects a percentage from 0 \
+                                                           to 100, not '{value}'"))?;
+                args.batch.get_or_insert_with(BatchPolicy::default)
+                          .max_rejected_percent = Some(percent);
+                None
+            }
+            "--batch-report" =>
+            {
+                args.batch_report = options.path(arg)?;
+                None
+            }
+            _ if parse_reporting(arg, options, &mut args)?
+                 || parse_rules(arg, options, &mut args)?
+                 || parse_persistence(arg, options, &mut args)? => None,
+            _ => return Err(options.unknown(arg))
+        };
+
+        if query.is_some()
+        {
+            if args.query.is_some()
+            {
+                return Err(String::from("--as-of-tx and --as-of-line can't be used together"));
+            }
+            args.query = query;
+        }
+    }
+
+    if args.batch.is_some() && args.journal.is_some()
+    {
+        return Err(String::from("--journal can't be used with batches, a batch which is \
+                                 rolled back can't be taken out of the journal"));
+    }
+    if args.batch_report.is_some() && args.batch.is_none()
+    {
+        return Err(String::from("--batch-report needs --atomic"));
+    }
+
+    check_inputs(&args.inputs)?;
+    Ok(Command::Process(args))
+}
+
+fn parse_validate(options : &mut Options)
+-> Result<Command, String>
+{
+    let mut args = RunArgs::default();
+    while let Some((arg, is_option)) = options.next()
+    {
+        if !is_option
+        {
+            args.inputs.push(arg.clone());
+        }
+        else if !parse_reporting(arg, options, &mut args)? && !parse_rules(arg, options, &mut args)?
+        {
+            return Err(options.unknown(arg));
+        }
+    }
+
+    check_inputs(&args.inputs)?;
+    Ok(Command::Validate(args))
+}
+
+fn parse_statement(options : &mut Options)
+-> Result<Command, String>
+{
+    let mut args = RunArgs::default();
+    let mut lines = None;
+    let mut format = StatementFormat::default();
+    while let Some((arg, is_option)) = options.next()
+    {
+        match arg.as_str()
+        {
+            _ if !is_option => args.inputs.push(arg.clone()),
+            "--lines" => lines = Some(parse_range(options.value(arg)?)?),
+            "--format" => format = options.named(arg, "a statement format",
+                                                 StatementFormat::from_name)?,
+            "--output" => args.output = options.path(arg)?,
+            _ if parse_reporting(arg, options, &mut args)?
+                 || parse_rules(arg, options, &mut args)? => {}
+            _ => return Err(options.unknown(arg))
+        }
+    }
+
+    if args.inputs.is_empty()
+    {
+        return Err(String::from("statement needs a client id"));
+    }
+    let client = args.inputs.remove(0);
+    let client = client.parse::<u16>()
+                       .map_err(|_| format!("'{client}' is not a client id"))?;
+    check_inputs(&args.inputs)?;
+
+    args.query = Some(Query::Statement(client, lines, format));
+    Ok(Command::Statement(args))
+}
+
+fn parse_replay(options : &mut Options)
+-> Result<Command, String>
+{
+    let mut positional = Vec::new();
+    let mut from_snapshot = None;
+    let mut format = OutputFormat::default();
+    let mut output = None;
+    while let Some((arg, is_option)) = options.next()
+    {
+        match arg.as_str()
+        {
+            _ if !is_option => positional.push(arg.clone()),
+            "--from-snapshot" => from_snapshot = options.path(arg)?,
+            "--format" => format = options.named(arg, "an output format", OutputFormat::from_name)?,
+            "--output" => output = options.path(arg)?,
+            _ => return Err(options.unknown(arg))
+        }
+    }
+
+    match <[String; 1]>::try_from(positional)
+    {
+        Ok([events]) => Ok(Command::Replay(ReplayArgs { events, from_snapshot, format, output })),
+        Err(_) => Err(String::from("replay expects a single event stream file"))
+    }
+}
+
+fn parse_diff(options : &mut Options)
+-> Result<Command, String>
+{
+    let mut positional = Vec::new();
+    let mut output = None;
+    while let Some((arg, is_option)) = options.next()
+    {
+        match arg.as_str()
+        {
+            _ if !is_option => positional.push(arg.clone()),
+            "--output" => output = options.path(arg)?,
+            _ => return Err(options.unknown(arg))
+        }
+    }
+
+    match <[String; 2]>::try_from(positional)
+    {
+        Ok([before, after]) => Ok(Command::Diff(DiffArgs { before, after, output })),
+        Err(_) => Err(String::from("diff expects two sets of accounts, before and after"))
+    }
+}
+
+fn parse_generate(options : &mut Options)
+-> Result<Command, String>
+{
+    let mut args = GenerateArgs { rows : 1000, clients : 10, seed : None, output : None };
+    while let Some((arg, is_option)) = options.next()
+    {
+        match arg.as_str()
+        {
+            _ if !is_option => return Err(format!("generate doesn't take '{arg}', \
+                                                   see 'tx_engine generate --help'")),
+            "--rows"    => args.rows = options.number(arg)?,
+            "--clients" => args.clients = options.number(arg)?,
+            "--seed"    => args.seed = Some(options.number(arg)?),
+            "--output"  => args.output = options.path(arg)?,
+            _ => return Err(options.unknown(arg))
+        }
+    }
+
+    if args.clients == 0
+    {
+        return Err(String::from("--clients must be at least 1"));
+    }
+    Ok(Command::Generate(args))
+}
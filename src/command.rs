@@ -0,0 +1,187 @@
+use std::fmt;
+
+use rust_decimal::prelude::*;
+
+use crate::common::*;
+use crate::record::TxKind;
+
+// a single transaction to apply to the engine, independent
+// of where it came from (a csv row, a queue message, ...).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxCommand
+{
+    Deposit     { client : u16, tx : u32, amount : Decimal },
+    Withdrawal  { client : u16, tx : u32, amount : Decimal },
+    Dispute     { client : u16, tx : u32 },
+    Resolve     { client : u16, tx : u32 },
+    Chargeback  { client : u16, tx : u32 }
+}
+
+impl TxCommand
+{
+    // builds a command from its parts, 'amount' is ignored
+    // for the kinds which reference an existing transaction.
+    pub fn new(kind : TxKind, client : u16, tx : u32, amount : Decimal)
+    -> Self
+    {
+        match kind
+        {
+            TxKind::Deposit     => TxCommand::Deposit { client, tx, amount },
+            TxKind::Withdrawal  => TxCommand::Withdrawal { client, tx, amount },
+            TxKind::Dispute     => TxCommand::Dispute { client, tx },
+            TxKind::Resolve     => TxCommand::Resolve { client, tx },
+            TxKind::Chargeback  => TxCommand::Chargeback { client, tx }
+        }
+    }
+
+    pub fn kind(&self)
+    -> TxKind
+    {
+        match self
+        {
+            TxCommand::Deposit { .. }       => TxKind::Deposit,
+            TxCommand::Withdrawal { .. }    => TxKind::Withdrawal,
+            TxCommand::Dispute { .. }       => TxKind::Dispute,
+            TxCommand::Resolve { .. }       => TxKind::Resolve,
+            TxCommand::Chargeback { .. }    => TxKind::Chargeback
+        }
+    }
+
+    pub fn client(&self)
+    -> u16
+    {
+        match *self
+        {
+            TxCommand::Deposit { client, .. }       |
+            TxCommand::Withdrawal { client, .. }    |
+            TxCommand::Dispute { client, .. }       |
+            TxCommand::Resolve { client, .. }       |
+            TxCommand::Chargeback { client, .. }    => client
+        }
+    }
+
+    pub fn tx(&self)
+    -> u32
+    {
+        match *self
+        {
+            TxCommand::Deposit { tx, .. }       |
+            TxCommand::Withdrawal { tx, .. }    |
+            TxCommand::Dispute { tx, .. }       |
+            TxCommand::Resolve { tx, .. }       |
+            TxCommand::Chargeback { tx, .. }    => tx
+        }
+    }
+
+    // only deposits and withdrawals carry their own amount.
+    pub fn amount(&self)
+    -> Option<Decimal>
+    {
+        match *self
+        {
+            TxCommand::Deposit { amount, .. }       |
+            TxCommand::Withdrawal { amount, .. }    => Some(amount),
+            _                                       => None
+        }
+    }
+}
+
+// the command as a row of the transactions file, e.g.
+// 'withdrawal,1,2,9.0' or 'dispute,1,2,'.
+impl fmt::Display for TxCommand
+{
+    fn fmt(&self, f : &mut fmt::Formatter<'_>)
+    -> fmt::Result
+    {
+        write!(f, "{},{},{},", self.kind().name(), self.client(), self.tx())?;
+        match self.amount()
+        {
+            Some(amount) => write!(f, "{amount}"),
+            None => Ok(())
+        }
+    }
+}
+
+// the result of a command which has been applied.
+#[derive(Debug, Clone)]
+pub struct TxOutcome
+{
+    pub command  : TxCommand,
+    // the amount moved, for disputes, resolves and chargebacks
+    // this is the amount of the referenced transaction.
+    pub amount   : Decimal,
+    // the client's account after the command was applied.
+    pub account  : Account,
+    // set if the command disputed, resolved or charged back a withdrawal
+    // under WithdrawalDisputePolicy::Reverse.
+    pub reversed : bool
+}
+
+// the reasons a command can be refused. A refused
+// command leaves the engine's state unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxError
+{
+    TxNotFound(u32),
+    InsufficientFunds(u16, u32),
+    TxNotDisputed(u32),
+    UnauthorisedTx(u16, u32),
+    AmountNegative(u32),
+    TxIdExists(u32),
+    AccountLocked(u16, u32),
+    TxAlreadyDisputed(u32),
+    TxFinalised(u32, TxState),
+    // the command passed every check, but couldn't be journaled
+    // so it was refused rather than applied without a record.
+    JournalFailed(u32),
+    // the withdrawal dispute policy is WithdrawalDisputePolicy::Reject.
+    WithdrawalNotDisputable(u32),
+    // applying the command would overflow one of the client's balances.
+    BalanceOverflow(u16, u32),
+    // the referenced transaction is the client's, but the client has no
+    // account in this engine's map, e.g. the deposit was applied by
+    // another engine sharing the registry with its own accounts.
+    AccountNotFound(u16, u32)
+}
+
+// the same messages as the ProcessError each maps to.
+impl fmt::Display for TxError
+{
+    fn fmt(&self, f : &mut fmt::Formatter<'_>)
+    -> fmt::Result
+    {
+        ProcessError::from(*self).fmt(f)
+    }
+}
+
+impl std::error::Error for TxError {}
+
+impl From<TxError> for ProcessError
+{
+    fn from(err : TxError)
+    -> Self
+    {
+        match err
+        {
+            TxError::TxNotFound(tx_id)              => ProcessError::ErrTxNotFound(tx_id),
+            TxError::InsufficientFunds(cli_id, tx_id) =>
+            {
+                ProcessError::ErrInsufficientfunds(cli_id, tx_id)
+            }
+            TxError::TxNotDisputed(tx_id)           => ProcessError::ErrTxNotDisputed(tx_id),
+            TxError::UnauthorisedTx(cli_id, tx_id)  => ProcessError::ErrUnauthorisedTx(cli_id, tx_id),
+            TxError::AmountNegative(tx_id)          => ProcessError::ErrAmountNegative(tx_id),
+            TxError::TxIdExists(tx_id)              => ProcessError::ErrTxIdExists(tx_id),
+            TxError::AccountLocked(cli_id, tx_id)   => ProcessError::ErrAccountLocked(cli_id, tx_id),
+            TxError::TxAlreadyDisputed(tx_id)       => ProcessError::ErrTxAlreadyDisputed(tx_id),
+            TxError::TxFinalised(tx_id, state)      => ProcessError::ErrTxFinalised(tx_id, state),
+            TxError::JournalFailed(tx_id)           => ProcessError::ErrJournalFailed(tx_id),
+            TxError::WithdrawalNotDisputable(tx_id) =>
+            {
+                ProcessError::ErrWithdrawalNotDisputable(tx_id)
+            }
+            TxError::BalanceOverflow(cli_id, tx_id) => ProcessError::ErrBalanceOverflow(cli_id, tx_id),
+            TxError::AccountNotFound(cli_id, tx_id) => ProcessError::ErrAccountNotFound(cli_id, tx_id)
+        }
+    }
+}
@@ -1,120 +1,715 @@
-use std::fs::OpenOptions;
-use std::io::Write;
+use std::fmt;
 
 use rust_decimal::prelude::*;
 use rust_decimal_macros::dec;
 
-use chrono;
+use crate::command::TxError;
+use crate::record::TxKind;
 
-// errors which occur during processing
+// the errors reported while processing a file. Each belongs to an
+// ErrorCategory and has a stable code, see ProcessError::code.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub enum ProcessEvent
+pub enum ProcessError
 {
-    StartOfLogger,
-    ProcessComplete,
-    ExternalErr(String),
+    // rows which could not be parsed, these carry
+    // the line number and the raw text of the row
+    // (the type, for an unrecognised type).
+    ErrMalformedRow(u64, String),
+    ErrColumnCount(u64, String),
+    ErrUnrecognisedTx(u64, String),
+    ErrBadClientId(u64, String),
+    ErrBadTxId(u64, String),
+    ErrMissingAmount(u64, String),
+    ErrInvalidAmount(u64, String),
+    ErrAmountTooLarge(u64, String),
+    // transactions the engine refused, see TxError.
     ErrTxNotFound(u32),
-    ErrUnrecognisedTx(usize, String),
     ErrInsufficientfunds(u16, u32),
     ErrTxNotDisputed(u32),
     ErrUnauthorisedTx(u16,u32),
     ErrAmountNegative(u32),
-    ErrTxIdExists(u32)
+    ErrTxIdExists(u32),
+    ErrAccountLocked(u16, u32),
+    ErrTxAlreadyDisputed(u32),
+    ErrTxFinalised(u32, TxState),
+    ErrJournalFailed(u32),
+    ErrWithdrawalNotDisputable(u32),
+    ErrBalanceOverflow(u16, u32),
+    ErrAccountNotFound(u16, u32),
+    // a ledger invariant which didn't hold after the given
+    // line (0 at the end of a run), see audit.rs.
+    ErrInvariantViolated(u64, String),
+    // a failure outside of the engine, reading the file,
+    // writing the output and the like.
+    ExternalErr(String)
 }
 
-impl ProcessEvent
+// the broad kinds of ProcessError.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory
 {
-    pub fn info(&self)
-    -> (String, chrono::DateTime<chrono::Local> )
+    // a row of the file couldn't be parsed, and was skipped.
+    Row,
+    // a parsed transaction was refused by the engine, and skipped.
+    Transaction,
+    // a ledger invariant didn't hold.
+    Audit,
+    // something outside of the engine failed.
+    External
+}
+
+impl ProcessError
+{
+    pub fn category(&self)
+    -> ErrorCategory
+    {
+        match self.number() / 100
+        {
+            1 => ErrorCategory::Row,
+            2 => ErrorCategory::Transaction,
+            3 => ErrorCategory::Audit,
+            _ => ErrorCategory::External
+        }
+    }
+
+    // the error's code, for log shippers and reports to match on.
+    // Codes are stable: they are never reused or renumbered, new
+    // errors get new ones. The hundreds give the category.
+    pub fn code(&self)
+    -> &'static str
+    {
+        match self
+        {
+            ProcessError::ErrMalformedRow(..)           => "malformed_row",
+            ProcessError::ErrColumnCount(..)            => "column_count",
+            ProcessError::ErrUnrecognisedTx(..)         => "unrecognised_tx",
+            ProcessError::ErrBadClientId(..)            => "bad_client_id",
+            ProcessError::ErrBadTxId(..)                => "bad_tx_id",
+            ProcessError::ErrMissingAmount(..)          => "missing_amount",
+            ProcessError::ErrInvalidAmount(..)          => "invalid_amount",
+            ProcessError::ErrAmountTooLarge(..)         => "amount_too_large",
+            ProcessError::ErrTxNotFound(_)              => "tx_not_found",
+            ProcessError::ErrInsufficientfunds(..)      => "insufficient_funds",
+            ProcessError::ErrTxNotDisputed(_)           => "tx_not_disputed",
+            ProcessError::ErrUnauthorisedTx(..)         => "unauthorised_tx",
+            ProcessError::ErrAmountNegative(_)          => "amount_negative",
+            ProcessError::ErrTxIdExists(_)              => "tx_id_exists",
+            ProcessError::ErrAccountLocked(..)          => "account_locked",
+            ProcessError::ErrTxAlreadyDisputed(_)       => "tx_already_disputed",
+            ProcessError::ErrTxFinalised(..)            => "tx_finalised",
+            ProcessError::ErrJournalFailed(_)           => "journal_failed",
+            ProcessError::ErrWithdrawalNotDisputable(_) => "withdrawal_not_disputable",
+            ProcessError::ErrBalanceOverflow(..)        => "balance_overflow",
+            ProcessError::ErrAccountNotFound(..)        => "account_not_found",
+            ProcessError::ErrInvariantViolated(..)      => "invariant_violated",
+            ProcessError::ExternalErr(_)                => "external_error"
+        }
+    }
+
+    // the numeric form of the code.
+    pub fn number(&self)
+    -> u16
+    {
+        match self
+        {
+            ProcessError::ErrMalformedRow(..)           => 101,
+            ProcessError::ErrColumnCount(..)            => 102,
+            ProcessError::ErrUnrecognisedTx(..)         => 103,
+            ProcessError::ErrBadClientId(..)            => 104,
+            ProcessError::ErrBadTxId(..)                => 105,
+            ProcessError::ErrMissingAmount(..)          => 106,
+            ProcessError::ErrInvalidAmount(..)          => 107,
+            ProcessError::ErrAmountTooLarge(..)         => 108,
+            ProcessError::ErrTxNotFound(_)              => 201,
+            ProcessError::ErrInsufficientfunds(..)      => 202,
+            ProcessError::ErrTxNotDisputed(_)           => 203,
+            ProcessError::ErrUnauthorisedTx(..)         => 204,
+            ProcessError::ErrAmountNegative(_)          => 205,
+            ProcessError::ErrTxIdExists(_)              => 206,
+            ProcessError::ErrAccountLocked(..)          => 207,
+            ProcessError::ErrTxAlreadyDisputed(_)       => 208,
+            ProcessError::ErrTxFinalised(..)            => 209,
+            ProcessError::ErrJournalFailed(_)           => 210,
+            ProcessError::ErrWithdrawalNotDisputable(_) => 211,
+            ProcessError::ErrBalanceOverflow(..)        => 212,
+            ProcessError::ErrAccountNotFound(..)        => 213,
+            ProcessError::ErrInvariantViolated(..)      => 301,
+            ProcessError::ExternalErr(_)                => 901
+        }
+    }
+
+    // a refused row is a warning, processing carries on past it. Errors
+    // are the ones which mean the results can't be trusted.
+    pub fn level(&self)
+    -> LogLevel
+    {
+        match (self.category(), self)
+        {
+            (_, ProcessError::ErrJournalFailed(_))  => LogLevel::Error,
+            (ErrorCategory::Row, _)               |
+            (ErrorCategory::Transaction, _)         => LogLevel::Warn,
+            _                                       => LogLevel::Error
+        }
+    }
+
+    // the client the error is about, if it carries one.
+    pub fn client(&self)
+    -> Option<u16>
+    {
+        match self
+        {
+            ProcessError::ErrInsufficientfunds(cli_id, _) |
+            ProcessError::ErrUnauthorisedTx(cli_id, _)    |
+            ProcessError::ErrAccountLocked(cli_id, _)     |
+            ProcessError::ErrBalanceOverflow(cli_id, _)   |
+            ProcessError::ErrAccountNotFound(cli_id, _)   => Some(*cli_id),
+            _                                             => None
+        }
+    }
+
+    // the transaction the error is about, if it carries one.
+    pub fn tx(&self)
+    -> Option<u32>
     {
-        return match &*self
+        match self
         {
-            ProcessEvent::StartOfLogger =>
+            ProcessError::ErrTxNotFound(tx_id)                |
+            ProcessError::ErrInsufficientfunds(_, tx_id)      |
+            ProcessError::ErrTxNotDisputed(tx_id)             |
+            ProcessError::ErrUnauthorisedTx(_, tx_id)         |
+            ProcessError::ErrAmountNegative(tx_id)            |
+            ProcessError::ErrTxIdExists(tx_id)                |
+            ProcessError::ErrAccountLocked(_, tx_id)          |
+            ProcessError::ErrTxAlreadyDisputed(tx_id)         |
+            ProcessError::ErrTxFinalised(tx_id, _)            |
+            ProcessError::ErrJournalFailed(tx_id)             |
+            ProcessError::ErrWithdrawalNotDisputable(tx_id)   |
+            ProcessError::ErrBalanceOverflow(_, tx_id)        |
+            ProcessError::ErrAccountNotFound(_, tx_id)        => Some(*tx_id),
+            _                                                 => None
+        }
+    }
+
+    // the line of the transactions file the error carries, for the
+    // rows which couldn't be parsed and the audit checks.
+    pub fn line(&self)
+    -> Option<u64>
+    {
+        match self
+        {
+            ProcessError::ErrMalformedRow(line, _)    |
+            ProcessError::ErrColumnCount(line, _)     |
+            ProcessError::ErrUnrecognisedTx(line, _)  |
+            ProcessError::ErrBadClientId(line, _)     |
+            ProcessError::ErrBadTxId(line, _)         |
+            ProcessError::ErrMissingAmount(line, _)   |
+            ProcessError::ErrInvalidAmount(line, _)   |
+            ProcessError::ErrAmountTooLarge(line, _)    => Some(*line),
+            ProcessError::ErrInvariantViolated(0, _)    => None,
+            ProcessError::ErrInvariantViolated(line, _) => Some(*line),
+            _                                           => None
+        }
+    }
+}
+
+impl fmt::Display for ProcessError
+{
+    fn fmt(&self, f : &mut fmt::Formatter<'_>)
+    -> fmt::Result
+    {
+        match self
+        {
+            ProcessError::ErrMalformedRow(line, reason) =>
+            {
+                write!(f, "In csv, line {line}: the row could not be read ({reason}).")
+            }
+            ProcessError::ErrColumnCount(line, raw) =>
+            {
+                write!(f, "In csv, line {line}: '{raw}' has the wrong number of columns.")
+            }
+            ProcessError::ErrUnrecognisedTx(line, tx_type) =>
+            {
+                write!(f, "In csv, line {line}: '{tx_type}' is not a recognised \
+                           transaction type.")
+            }
+            ProcessError::ErrBadClientId(line, raw) =>
+            {
+                write!(f, "In csv, line {line}: '{raw}' does not have a valid client id.")
+            }
+            ProcessError::ErrBadTxId(line, raw) =>
+            {
+                write!(f, "In csv, line {line}: '{raw}' does not have a valid \
+                           transaction id.")
+            }
+            ProcessError::ErrMissingAmount(line, raw) =>
+            {
+                write!(f, "In csv, line {line}: '{raw}' is missing an amount.")
+            }
+            ProcessError::ErrInvalidAmount(line, raw) =>
+            {
+                write!(f, "In csv, line {line}: '{raw}' does not have a valid amount.")
+            }
+            ProcessError::ErrAmountTooLarge(line, raw) =>
+            {
+                write!(f, "In csv, line {line}: '{raw}' has an amount beyond {MAX_AMOUNT}.")
+            }
+            ProcessError::ErrTxNotFound(tx_id) =>
+            {
+                write!(f, "Transaction with id '{tx_id}' is not found.")
+            }
+            ProcessError::ErrInsufficientfunds(cli_id, tx_id) =>
+            {
+                write!(f, "Client with id '{cli_id}' has insufficient funds for \
+                           transaction with id '{tx_id}'.")
+            }
+            ProcessError::ErrTxNotDisputed(tx_id) =>
+            {
+                write!(f, "The referenced transaction with id '{tx_id}' isn't \
+                           under dispute.")
+            }
+            ProcessError::ErrUnauthorisedTx(cli_id, tx_id) =>
+            {
+                write!(f, "Client with id '{cli_id}' cannot reference transaction \
+                           with id '{tx_id}' because they do not own the transaction.")
+            }
+            ProcessError::ErrAmountNegative(tx_id) =>
+            {
+                write!(f, "Transaction with id '{tx_id}' is negative.")
+            }
+            ProcessError::ErrTxIdExists(tx_id) =>
             {
-                (String::from("Event logger created."),
-                chrono::offset::Local::now())
+                write!(f, "Transaction with id '{tx_id}' already exists.")
             }
-            ProcessEvent::ErrAmountNegative(tx_id) =>
+            ProcessError::ErrAccountLocked(cli_id, tx_id) =>
             {
-                (format!("ProcessError: Transaction with id '{tx_id}' is negative."),
-                chrono::offset::Local::now())
+                write!(f, "Client with id '{cli_id}' is locked, transaction with \
+                           id '{tx_id}' was refused.")
             }
-            ProcessEvent::ExternalErr(msg) =>
+            ProcessError::ErrTxAlreadyDisputed(tx_id) =>
             {
-                (msg.to_string(),
-                chrono::offset::Local::now())
+                write!(f, "Transaction with id '{tx_id}' is already under dispute.")
             }
-            ProcessEvent::ProcessComplete =>
+            ProcessError::ErrTxFinalised(tx_id, state) =>
             {
-                (String::from("Processed completed."),
-                chrono::offset::Local::now())
+                write!(f, "Transaction with id '{tx_id}' has been {} and can no \
+                           longer be disputed.", state.name())
             }
-            ProcessEvent::ErrTxNotFound(tx_id) =>
+            ProcessError::ErrJournalFailed(tx_id) =>
             {
-                (format!("ProcessError: Transaction with id '{tx_id}' is not found."),
-                chrono::offset::Local::now())
+                write!(f, "Transaction with id '{tx_id}' was refused because it \
+                           could not be written to the journal.")
             }
-            ProcessEvent::ErrUnrecognisedTx(line, tx_type) =>
+            ProcessError::ErrWithdrawalNotDisputable(tx_id) =>
             {
-                (format!(
-                    "ProcessError: In csv, line {line}: 
-                    '{tx_type}' is not a recognised transaction type."
-                    ), chrono::offset::Local::now())
+                write!(f, "Transaction with id '{tx_id}' is a withdrawal, which \
+                           can't be disputed.")
             }
-            ProcessEvent::ErrInsufficientfunds(cli_id, tx_id) =>
+            ProcessError::ErrBalanceOverflow(cli_id, tx_id) =>
             {
-                (format!("ProcessError: Client with id '{cli_id}' has \
-                \ninsufficient funds for transaction with id '{tx_id}'."),
-                chrono::offset::Local::now())
+                write!(f, "Transaction with id '{tx_id}' would take a balance of client \
+                           with id '{cli_id}' beyond what can be held.")
             }
-            ProcessEvent::ErrTxNotDisputed(tx_id) =>
+            ProcessError::ErrAccountNotFound(cli_id, tx_id) =>
             {
-                (format!("ProcessError: The referenced transaction with \
-                \nid '{tx_id}' isn't under dispute.'"),
-                chrono::offset::Local::now())
+                write!(f, "Client with id '{cli_id}' has no account here to apply \
+                           transaction with id '{tx_id}' to.")
             }
-            ProcessEvent::ErrUnauthorisedTx(cli_id, tx_id) =>
+            ProcessError::ErrInvariantViolated(0, violation) =>
             {
-                (format!("ProcessError: Client with id '{cli_id}' cannot \
-                reference transaction with id '{tx_id}' because \
-                they do not own the transaction.'"),
-                chrono::offset::Local::now())
+                write!(f, "At the end of the run: {violation}.")
             }
-            ProcessEvent::ErrTxIdExists(tx_id) =>
+            ProcessError::ErrInvariantViolated(line, violation) =>
             {
-                (format!("ProcessError: transaction with id '{tx_id}' already \
-                exists."),
-                chrono::offset::Local::now())
+                write!(f, "In csv, after line {line}: {violation}.")
             }
+            ProcessError::ExternalErr(msg) => write!(f, "{msg}")
+        }
+    }
+}
+
+impl std::error::Error for ProcessError {}
+
+// what happened in a run, other than errors. These are logged below
+// the errors, so they can be left out of the log (see LogLevel).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunNote
+{
+    // a row was applied to the client's account.
+    TxApplied(u16, u32),
+    // the rows of an input have been processed, 'refused' counts
+    // those which were refused or couldn't be parsed.
+    InputComplete { rows : u64, refused : u64 }
+}
+
+impl RunNote
+{
+    pub fn code(&self)
+    -> &'static str
+    {
+        match self
+        {
+            RunNote::TxApplied(..)          => "tx_applied",
+            RunNote::InputComplete { .. }   => "input_complete"
+        }
+    }
+
+    // every applied row is only worth seeing when debugging.
+    pub fn level(&self)
+    -> LogLevel
+    {
+        match self
+        {
+            RunNote::TxApplied(..)          => LogLevel::Debug,
+            RunNote::InputComplete { .. }   => LogLevel::Info
+        }
+    }
+
+    pub fn client(&self)
+    -> Option<u16>
+    {
+        match self
+        {
+            RunNote::TxApplied(cli_id, _)   => Some(*cli_id),
+            RunNote::InputComplete { .. }   => None
+        }
+    }
+
+    pub fn tx(&self)
+    -> Option<u32>
+    {
+        match self
+        {
+            RunNote::TxApplied(_, tx_id)    => Some(*tx_id),
+            RunNote::InputComplete { .. }   => None
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+impl fmt::Display for RunNote
+{
+    fn fmt(&self, f : &mut fmt::Formatter<'_>)
+    -> fmt::Result
+    {
+        match self
+        {
+            RunNote::TxApplied(cli_id, tx_id) =>
+            {
+                write!(f, "Transaction with id '{tx_id}' was applied to client with id '{cli_id}'.")
+            }
+            RunNote::InputComplete { rows, refused } =>
+            {
+                write!(f, "Processed {rows} rows, {refused} of which were refused.")
+            }
+        }
+    }
+}
+
+// how severe a logged event is, the logger skips events below its
+// minimum level. Refused rows and errors are logged at warn and error
+// (see ProcessError::level), the notes of a run below them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum LogLevel
+{
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error
+}
+
+impl LogLevel
+{
+    pub fn from_name(name : &str)
+    -> Option<Self>
+    {
+        match name
+        {
+            "debug" => Some(LogLevel::Debug),
+            "info"  => Some(LogLevel::Info),
+            "warn"  => Some(LogLevel::Warn),
+            "error" => Some(LogLevel::Error),
+            _       => None
+        }
+    }
+
+    pub fn name(&self)
+    -> &'static str
+    {
+        match self
+        {
+            LogLevel::Debug => "debug",
+            LogLevel::Info  => "info",
+            LogLevel::Warn  => "warn",
+            LogLevel::Error => "error"
+        }
+    }
+}
+
+// which operations are refused once an account has been
+// locked (frozen) by a chargeback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LockPolicy
+{
+    // locked accounts are not checked at all.
+    Unenforced,
+    // only withdrawals are refused.
+    BlockWithdrawals,
+    // deposits, withdrawals and new disputes are refused, but
+    // open disputes can still be resolved or charged back.
+    #[default]
+    BlockAllButSettlement,
+    // every operation is refused.
+    BlockAll
+}
+
+impl LockPolicy
+{
+    pub fn from_name(name : &str)
+    -> Option<Self>
+    {
+        match name
+        {
+            "unenforced"                => Some(LockPolicy::Unenforced),
+            "block-withdrawals"         => Some(LockPolicy::BlockWithdrawals),
+            "block-all-but-settlement"  => Some(LockPolicy::BlockAllButSettlement),
+            "block-all"                 => Some(LockPolicy::BlockAll),
+            _                           => None
+        }
+    }
+
+    pub fn name(&self)
+    -> &'static str
+    {
+        match self
+        {
+            LockPolicy::Unenforced              => "unenforced",
+            LockPolicy::BlockWithdrawals        => "block-withdrawals",
+            LockPolicy::BlockAllButSettlement   => "block-all-but-settlement",
+            LockPolicy::BlockAll                => "block-all"
+        }
+    }
+
+    // returns true if a locked account may still
+    // apply a transaction of the given kind.
+    pub fn permits(&self, kind : TxKind)
+    -> bool
+    {
+        match self
+        {
+            LockPolicy::Unenforced              => true,
+            LockPolicy::BlockWithdrawals        => kind != TxKind::Withdrawal,
+            LockPolicy::BlockAllButSettlement   =>
+            {
+                matches!(kind, TxKind::Resolve | TxKind::Chargeback)
+            }
+            LockPolicy::BlockAll                => false
+        }
+    }
+}
+
+// what disputing a withdrawal does. The text only describes disputes
+// of deposits, see the README for the reasoning behind each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WithdrawalDisputePolicy
+{
+    // as a deposit: the amount moves from available to held, a resolve
+    // moves it back, and a chargeback removes it from held.
+    #[default]
+    Withhold,
+    // the withdrawal is reversed into held while it's disputed, without
+    // touching available. A resolve upholds the withdrawal, removing the
+    // funds from held again; a chargeback reverses it for good, releasing
+    // the funds into available.
+    Reverse,
+    // disputes of withdrawals are refused.
+    Reject
+}
+
+impl WithdrawalDisputePolicy
+{
+    pub fn from_name(name : &str)
+    -> Option<Self>
+    {
+        match name
+        {
+            "withhold"  => Some(WithdrawalDisputePolicy::Withhold),
+            "reverse"   => Some(WithdrawalDisputePolicy::Reverse),
+            "reject"    => Some(WithdrawalDisputePolicy::Reject),
+            _           => None
+        }
+    }
+
+    pub fn name(&self)
+    -> &'static str
+    {
+        match self
+        {
+            WithdrawalDisputePolicy::Withhold   => "withhold",
+            WithdrawalDisputePolicy::Reverse    => "reverse",
+            WithdrawalDisputePolicy::Reject     => "reject"
+        }
+    }
+}
+
+// what a dispute, resolve or chargeback of another client's
+// transaction does. tx ids are global, so a row can reference a
+// transaction made by a client other than the row's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CrossClientPolicy
+{
+    // the row is refused with ErrUnauthorisedTx.
+    #[default]
+    Refuse,
+    // the row is applied to the account of the client who made the
+    // transaction, as if that client had sent it.
+    Owner
+}
+
+impl CrossClientPolicy
+{
+    pub fn from_name(name : &str)
+    -> Option<Self>
+    {
+        match name
+        {
+            "refuse"    => Some(CrossClientPolicy::Refuse),
+            "owner"     => Some(CrossClientPolicy::Owner),
+            _           => None
+        }
+    }
+
+    pub fn name(&self)
+    -> &'static str
+    {
+        match self
+        {
+            CrossClientPolicy::Refuse   => "refuse",
+            CrossClientPolicy::Owner    => "owner"
+        }
+    }
+}
+
+// the lifecycle of a deposit or withdrawal. Every transaction
+// starts as Processed, and may be disputed once. A dispute ends
+// either Resolved or ChargedBack, both of which are final.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxState
+{
+    Processed,
+    Disputed,
+    Resolved,
+    ChargedBack
+}
+
+impl TxState
+{
+    // returns the state reached by applying an operation of the given
+    // kind, or the event to report if the transition isn't legal.
+    // all state changes to a transaction are checked here.
+    pub fn next(&self, tx_id : u32, kind : TxKind)
+    -> Result<TxState, TxError>
+    {
+        match (self, kind)
+        {
+            (TxState::Processed, TxKind::Dispute)       => Ok(TxState::Disputed),
+            (TxState::Disputed, TxKind::Resolve)        => Ok(TxState::Resolved),
+            (TxState::Disputed, TxKind::Chargeback)     => Ok(TxState::ChargedBack),
+            (TxState::Disputed, TxKind::Dispute)        =>
+            {
+                Err(TxError::TxAlreadyDisputed(tx_id))
+            }
+            (TxState::Processed, TxKind::Resolve) |
+            (TxState::Processed, TxKind::Chargeback)    =>
+            {
+                Err(TxError::TxNotDisputed(tx_id))
+            }
+            (_, TxKind::Deposit) |
+            (_, TxKind::Withdrawal)                     =>
+            {
+                Err(TxError::TxIdExists(tx_id))
+            }
+            (TxState::Resolved, _) |
+            (TxState::ChargedBack, _)                   =>
+            {
+                Err(TxError::TxFinalised(tx_id, *self))
+            }
+        }
+    }
+
+    // the inverse of name.
+    pub fn from_name(name : &str)
+    -> Option<Self>
+    {
+        match name
+        {
+            "processed"     => Some(TxState::Processed),
+            "disputed"      => Some(TxState::Disputed),
+            "resolved"      => Some(TxState::Resolved),
+            "charged back"  => Some(TxState::ChargedBack),
+            _               => None
+        }
+    }
+
+    pub fn name(&self)
+    -> &'static str
+    {
+        match self
+        {
+            TxState::Processed      => "processed",
+            TxState::Disputed       => "disputed",
+            TxState::Resolved       => "resolved",
+            TxState::ChargedBack    => "charged back"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Tx
 {
     pub id       : u32,
     pub client   : u16,
+    // either TxKind::Deposit or TxKind::Withdrawal.
+    pub kind     : TxKind,
     pub amount   : Decimal,
-    pub disputed    : bool
+    pub state    : TxState
 }
 
 impl Tx
 {
-    pub fn new(tx_id: u32, cli_id:u16, am: Decimal, disp: bool) 
+    pub fn new(tx_id: u32, cli_id:u16, kind: TxKind, am: Decimal, st: TxState) 
     -> Self
     { 
         Tx
         {
             id       : tx_id,
             client   : cli_id,
+            kind,
             amount   : am,
-            disputed : disp
+            state    : st
         }
     }
+
+    pub fn is_disputed(&self)
+    -> bool
+    {
+        self.state == TxState::Disputed
+    }
 }
 
-#[derive(Debug, Clone)]
+// the most decimal places an amount is kept to, see Engine::rounding_dp.
+pub const MAX_DP : u32 = 4;
+
+// the largest amount a row may carry, and the largest balance, or
+// total of available and held, an account may reach, either way. It
+// leaves room for sums over every client, and for writing them to four
+// decimal places, which Decimal can't do from 1e27 up.
+pub const MAX_AMOUNT : Decimal = dec!(100000000000000000000);
+
+// a balance would have gone beyond MAX_AMOUNT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BalanceOverflow;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Account
 {
     pub available:  Decimal,
@@ -128,113 +723,82 @@ impl Account
     {
         Account 
         {
-            available:  dec!(0.0).round_dp(4),
-            held:       dec!(0.0).round_dp(4),
+            available:  dec!(0.0).round_dp(MAX_DP),
+            held:       dec!(0.0).round_dp(MAX_DP),
             locked:     false
         }
     }
 
+    // the operations below fail with BalanceOverflow, leaving the
+    // account as it was, if a balance or the account's total would
+    // go beyond MAX_AMOUNT.
+
     // deposit to available balance
-    pub fn deposit( &mut self, amount : &Decimal)
+    pub fn deposit( &mut self, amount : &Decimal) -> Result<(), BalanceOverflow>
     {
-        self.available += amount.round_dp(4);
+        self.move_funds(amount.round_dp(MAX_DP), Decimal::ZERO)
     }
 
     // withdraw from available balance
-    pub fn withdraw( &mut self, amount : &Decimal)
+    pub fn withdraw( &mut self, amount : &Decimal) -> Result<(), BalanceOverflow>
     {
-        self.available -= amount.round_dp(4);
+        self.move_funds(-amount.round_dp(MAX_DP), Decimal::ZERO)
     }
 
     // move funds from available balance to held balance.
-    pub fn withhold( &mut self, amount : &Decimal)
+    pub fn withhold( &mut self, amount : &Decimal) -> Result<(), BalanceOverflow>
     {
-        self.available -= amount.round_dp(4);
-        self.held += amount.round_dp(4);
+        self.move_funds(-amount.round_dp(MAX_DP), amount.round_dp(MAX_DP))
     }
 
-    // release held funds into available
-    pub fn release_held( &mut self, amount : &Decimal)
+    // hold funds which aren't taken from available, e.g. a
+    // withdrawal which is reversed while it's disputed.
+    pub fn hold( &mut self, amount : &Decimal) -> Result<(), BalanceOverflow>
     {
-        self.available += amount.round_dp(4);
-        self.held -= amount.round_dp(4);
+        self.move_funds(Decimal::ZERO, amount.round_dp(MAX_DP))
     }
 
-    // applies a chargeback on held funds.
-    pub fn charge( &mut self, amount : &Decimal)
+    // release held funds into available
+    pub fn release_held( &mut self, amount : &Decimal) -> Result<(), BalanceOverflow>
     {
-        self.held -= amount.round_dp(4);
+        self.move_funds(amount.round_dp(MAX_DP), -amount.round_dp(MAX_DP))
     }
 
-    pub fn lock(&mut self)
+    // applies a chargeback on held funds.
+    pub fn charge( &mut self, amount : &Decimal) -> Result<(), BalanceOverflow>
     {
-        self.locked = true;
+        self.move_funds(Decimal::ZERO, -amount.round_dp(MAX_DP))
     }
 
-}
-
-pub struct Logger
-{
-    pub log_file : std::fs::File,
-    pub last_event : ProcessEvent
-}
-
-impl Logger
-{
-    pub fn new(path : &String)
-    -> Option<Self>
+    fn move_funds(&mut self, available : Decimal, held : Decimal) -> Result<(), BalanceOverflow>
     {
-        match Option::from(
-            OpenOptions::new()
-            .write(true)
-            .append(true)
-            .create(true)
-            .open(path))
+        let available = self.available.checked_add(available).ok_or(BalanceOverflow)?;
+        let held = self.held.checked_add(held).ok_or(BalanceOverflow)?;
+        let total = available.checked_add(held).ok_or(BalanceOverflow)?;
+        if [available, held, total].iter().any(|balance| balance.abs() > MAX_AMOUNT)
         {
-            Some(result) => 
-            {
-                // let this unrwap panic if it fails
-                return 
-                Some(
-                    Logger
-                    {
-                        log_file    : result.unwrap(),
-                        last_event  : ProcessEvent::StartOfLogger 
-                    });
-            },
-            None => 
-            {
-                return None;
-            }
+            return Err(BalanceOverflow);
         }
+        self.available = available;
+        self.held = held;
+        Ok(())
     }
 
-    pub fn log(&mut self, event : &ProcessEvent)
+    pub fn lock(&mut self)
     {
-        // let this panic if it fails
-        let info        = event.info();
-        let message     = info.0;
-        let timestamp   = info.1;
-
-        writeln!(self.log_file, "EVENT LOG {timestamp}:\n{message}\n")
-                 .unwrap();
-        self.last_event = event.clone();
+        self.locked = true;
     }
 
-    // returns the last message with timestamp
-    // this function is used in the regression tests.
-    // but rust-analyser fails to pick it up
-    // I put this attribute here, but rest assured
-    // this function is used.
-    #[allow(dead_code)]
-    pub fn last_entry(&self)
-    -> ProcessEvent
+}
+
+impl Default for Account
+{
+    fn default() -> Self
     {
-        self.last_event.clone()
+        Self::new()
     }
 }
 
-
 // unit tests ////////////////////////////////////////////////////////////////
 //////////////////////////////////////////////////////////////////////////////
 
@@ -249,7 +813,7 @@ mod accounts_operations
         // expect back 4 d.p precision
         let mut account = Account::new();
         //assert_eq!(account.available, &dec!(0.0));
-        account.deposit(&dec!(12.3456789));
+        account.deposit(&dec!(12.3456789)).unwrap();
         assert_eq!(account.available, dec!(12.3457));
 
     }
@@ -260,8 +824,8 @@ mod accounts_operations
         // expect back 4 d.p precision
         let mut account = Account::new();
         //assert_eq!(account.available, &dec!(0.0));
-        account.deposit(&dec!(12.3456789));
-        account.withdraw(&dec!(1.23456789));
+        account.deposit(&dec!(12.3456789)).unwrap();
+        account.withdraw(&dec!(1.23456789)).unwrap();
 
         assert_eq!(account.available, dec!(11.1111));
         assert_eq!(account.held, dec!(0.0000));
@@ -273,8 +837,8 @@ mod accounts_operations
         // expect back 4 d.p precision
         let mut account = Account::new();
         //assert_eq!(account.available, &dec!(0.0));
-        account.deposit(&dec!(12.3456789));
-        account.withhold(&dec!(1.23456789));
+        account.deposit(&dec!(12.3456789)).unwrap();
+        account.withhold(&dec!(1.23456789)).unwrap();
 
         assert_eq!(account.available, dec!(11.1111));
         assert_eq!(account.held, dec!(1.2346));
@@ -293,9 +857,9 @@ mod accounts_operations
         // (withhold) amount.
         let mut account = Account::new();
         //assert_eq!(account.available, &dec!(0.0));
-        account.deposit(&dec!(12.3456789));
-        account.withhold(&dec!(1.23456789));
-        account.release_held(&dec!(0.61728394));
+        account.deposit(&dec!(12.3456789)).unwrap();
+        account.withhold(&dec!(1.23456789)).unwrap();
+        account.release_held(&dec!(0.61728394)).unwrap();
 
         assert_eq!(account.available, dec!(11.7284));
         assert_eq!(account.held, dec!(0.6173));
@@ -314,12 +878,41 @@ mod accounts_operations
         // (withhold) amount.
         let mut account = Account::new();
         //assert_eq!(account.available, &dec!(0.0));
-        account.deposit(&dec!(12.3456789));
-        account.withhold(&dec!(1.23456789));
-        account.charge(&dec!(0.61728394));
+        account.deposit(&dec!(12.3456789)).unwrap();
+        account.withhold(&dec!(1.23456789)).unwrap();
+        account.charge(&dec!(0.61728394)).unwrap();
 
         assert_eq!(account.available, dec!(11.1111));
         assert_eq!(account.held, dec!(0.6173));
 
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tx_state_transitions
+{
+    use super::*;
+
+    #[test]
+    fn test_legal_transitions()
+    {
+        assert_eq!(TxState::Processed.next(1, TxKind::Dispute), 
+                   Ok(TxState::Disputed));
+        assert_eq!(TxState::Disputed.next(1, TxKind::Resolve), 
+                   Ok(TxState::Resolved));
+        assert_eq!(TxState::Disputed.next(1, TxKind::Chargeback), 
+                   Ok(TxState::ChargedBack));
+    }
+
+    #[test]
+    fn test_final_states_reject_everything()
+    {
+        for state in [TxState::Resolved, TxState::ChargedBack]
+        {
+            for kind in [TxKind::Dispute, TxKind::Resolve, TxKind::Chargeback]
+            {
+                assert_eq!(state.next(1, kind), 
+                           Err(TxError::TxFinalised(1, state)));
+            }
+        }
+    }
+}
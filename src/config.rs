@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use crate::common::*;
+use crate::engine::Engine;
+
+// the business rules an engine applies, loaded from a toml file or
+// built in code. The defaults are the rules the engine has always had:
+//
+//   rounding_dp             = 4
+//   reject_negative_amounts = true
+//   cross_client            = "refuse"                     # or "owner"
+//   withdrawal_disputes     = "withhold"                   # or "reverse", "reject"
+//   lock_policy             = "block-all-but-settlement"   # see LockPolicy
+//
+// every key is optional. Call validate before using a config built in
+// code, from_toml and read validate the configs they return.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EngineConfig
+{
+    // the decimal places amounts are rounded to, from 0 to 4. Accounts
+    // are written with four places, so more would be lost between runs.
+    pub rounding_dp             : u32,
+    pub reject_negative_amounts : bool,
+    pub cross_client            : CrossClientPolicy,
+    pub withdrawal_disputes     : WithdrawalDisputePolicy,
+    pub lock_policy             : LockPolicy
+}
+
+impl Default for EngineConfig
+{
+    fn default()
+    -> Self
+    {
+        EngineConfig
+        {
+            rounding_dp             : MAX_DP,
+            reject_negative_amounts : true,
+            cross_client            : CrossClientPolicy::default(),
+            withdrawal_disputes     : WithdrawalDisputePolicy::default(),
+            lock_policy             : LockPolicy::default()
+        }
+    }
+}
+
+// a value in a config file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value
+{
+    Str(String),
+    Int(i64),
+    Bool(bool)
+}
+
+impl EngineConfig
+{
+    pub fn validate(&self)
+    -> io::Result<()>
+    {
+        if self.rounding_dp > MAX_DP
+        {
+            return Err(invalid(format!("rounding_dp is {}, it can be at most {MAX_DP}",
+                                       self.rounding_dp)));
+        }
+        Ok(())
+    }
+
+    // reads and validates a config file.
+    pub fn read<P>(path : P)
+    -> io::Result<Self>
+    where P: AsRef<Path>
+    {
+        EngineConfig::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    // parses and validates a config. Only the part of toml a config
+    // needs is understood: top level 'key = value' lines, with string,
+    // integer and boolean values, and comments. Anything else, and any
+    // key which isn't one of the config's, is refused with its line.
+    pub fn from_toml(text : &str)
+    -> io::Result<Self>
+    {
+        let mut config = EngineConfig::default();
+        let mut seen : HashMap<String, usize> = HashMap::new();
+
+        for (i, line) in text.lines().enumerate()
+        {
+            let n = i + 1;
+            let line = strip_comment(line).trim();
+            if line.is_empty()
+            {
+                continue;
+            }
+            if line.starts_with('[')
+            {
+                return Err(invalid(format!("line {n}: tables aren't supported, \
+                                            keys go at the top level")));
+            }
+
+            let (key, value) = line.split_once('=')
+                                   .ok_or_else(|| invalid(format!("line {n}: expected \
+                                                                   'key = value'")))?;
+            let key = key.trim();
+            let value = parse_value(value.trim())
+                        .ok_or_else(|| invalid(format!("line {n}: '{}' is not a string, \
+                                                        integer or boolean", value.trim())))?;
+            if let Some(first) = seen.insert(key.to_string(), n)
+            {
+                return Err(invalid(format!("line {n}: {key} is already set on line {first}")));
+            }
+
+            let expected = |kind : &str| invalid(format!("line {n}: {key} should be {kind}"));
+            let named = |value : &Value, kind : &str| match value
+            {
+                Value::Str(name) => Ok(name.clone()),
+                _ => Err(expected(kind))
+            };
+            let unknown = |name : &str, what : &str| invalid(format!("line {n}: '{name}' \
+                                                                      is not {what}"));
+            match key
+            {
+                "rounding_dp" => match value
+                {
+                    Value::Int(dp) if (0..=MAX_DP as i64).contains(&dp) =>
+                        config.rounding_dp = dp as u32,
+                    Value::Int(dp) => return Err(invalid(format!("line {n}: rounding_dp is {dp}, \
+                                                                  it must be from 0 to {MAX_DP}"))),
+                    _ => return Err(expected("an integer"))
+                },
+                "reject_negative_amounts" => match value
+                {
+                    Value::Bool(reject) => config.reject_negative_amounts = reject,
+                    _ => return Err(expected("true or false"))
+                },
+                "cross_client" =>
+                {
+                    let name = named(&value, "a string")?;
+                    config.cross_client = CrossClientPolicy::from_name(&name)
+                                          .ok_or_else(|| unknown(&name, "a cross client policy"))?;
+                }
+                "withdrawal_disputes" =>
+                {
+                    let name = named(&value, "a string")?;
+                    config.withdrawal_disputes = WithdrawalDisputePolicy::from_name(&name)
+                                                 .ok_or_else(|| unknown(&name, "a withdrawal \
+                                                                                dispute policy"))?;
+                }
+                "lock_policy" =>
+                {
+                    let name = named(&value, "a string")?;
+                    config.lock_policy = LockPolicy::from_name(&name)
+                                         .ok_or_else(|| unknown(&name, "a lock policy"))?;
+                }
+                _ => return Err(invalid(format!("line {n}: '{key}' is not a config key")))
+            }
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+// the config as a toml file which from_toml reads back.
+impl fmt::Display for EngineConfig
+{
+    fn fmt(&self, f : &mut fmt::Formatter<'_>)
+    -> fmt::Result
+    {
+        writeln!(f, "rounding_dp = {}", self.rounding_dp)?;
+        writeln!(f, "reject_negative_amounts = {}", self.reject_negative_amounts)?;
+        writeln!(f, "cross_client = \"{}\"", self.cross_client.name())?;
+        writeln!(f, "withdrawal_disputes = \"{}\"", self.withdrawal_disputes.name())?;
+        writeln!(f, "lock_policy = \"{}\"", self.lock_policy.name())
+    }
+}
+
+fn invalid(message : String)
+-> io::Error
+{
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// the line without a trailing '# comment', a '#' in a string is kept.
+fn strip_comment(line : &str)
+-> &str
+{
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices()
+    {
+        match c
+        {
+            _ if escaped    => escaped = false,
+            '\\'            => escaped = in_string,
+            '"'             => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn parse_value(text : &str)
+-> Option<Value>
+{
+    match text
+    {
+        "true"  => return Some(Value::Bool(true)),
+        "false" => return Some(Value::Bool(false)),
+        _ => {}
+    }
+
+    if let Some(quoted) = text.strip_prefix('"').and_then(|text| text.strip_suffix('"'))
+    {
+        let mut string = String::new();
+        let mut chars = quoted.chars();
+        while let Some(c) = chars.next()
+        {
+            match c
+            {
+                '\\' => match chars.next()?
+                {
+                    '"'     => string.push('"'),
+                    '\\'    => string.push('\\'),
+                    'n'     => string.push('\n'),
+                    't'     => string.push('\t'),
+                    _       => return None
+                },
+                '"' => return None,
+                c => string.push(c)
+            }
+        }
+        return Some(Value::Str(string));
+    }
+
+    text.replace('_', "").parse::<i64>().ok().map(Value::Int)
+}
+
+impl<'a> Engine<'a>
+{
+    // applies the rules in a config, which should have been validated.
+    pub fn configure(&mut self, config : &EngineConfig)
+    {
+        self.rounding_dp = config.rounding_dp;
+        self.reject_negative = config.reject_negative_amounts;
+        self.cross_client = config.cross_client;
+        self.dispute_policy = config.withdrawal_disputes;
+        self.lock_policy = config.lock_policy;
+    }
+
+    // the rules the engine is applying.
+    pub fn config(&self)
+    -> EngineConfig
+    {
+        EngineConfig
+        {
+            rounding_dp             : self.rounding_dp,
+            reject_negative_amounts : self.reject_negative,
+            cross_client            : self.cross_client,
+            withdrawal_disputes     : self.dispute_policy,
+            lock_policy             : self.lock_policy
+        }
+    }
+}
+
+
+// unit tests ////////////////////////////////////////////////////////////////
+//////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod config_parsing
+{
+    use super::*;
+
+    #[test]
+    fn test_parse_values()
+    {
+        assert_eq!(parse_value("true"), Some(Value::Bool(true)));
+        assert_eq!(parse_value("1_000"), Some(Value::Int(1000)));
+        assert_eq!(parse_value("-2"), Some(Value::Int(-2)));
+        assert_eq!(parse_value("\"a \\\"b\\\"\""), Some(Value::Str(String::from("a \"b\""))));
+        assert_eq!(parse_value("'single'"), None);
+        assert_eq!(parse_value("\"a\" \"b\""), None);
+        assert_eq!(parse_value("yes"), None);
+    }
+
+    #[test]
+    fn test_strip_comments()
+    {
+        assert_eq!(strip_comment("lock_policy = \"block-all\" # none"), "lock_policy = \"block-all\" ");
+        assert_eq!(strip_comment("key = \"a # b\""), "key = \"a # b\"");
+        assert_eq!(strip_comment("# only a comment"), "");
+    }
+}
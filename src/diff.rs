@@ -0,0 +1,86 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+use rust_decimal::prelude::*;
+
+use crate::common::Account;
+
+// how one client's account differs between two sets of accounts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountDelta
+{
+    pub before  : Account,
+    pub after   : Account
+}
+
+impl AccountDelta
+{
+    pub fn available(&self)
+    -> Decimal
+    {
+        self.after.available - self.before.available
+    }
+
+    pub fn held(&self)
+    -> Decimal
+    {
+        self.after.held - self.before.held
+    }
+
+    pub fn total(&self)
+    -> Decimal
+    {
+        self.available() + self.held()
+    }
+}
+
+// the clients whose accounts differ between 'before' and 'after',
+// ordered by client id. A client missing from one side is compared
+// against an empty, unlocked account.
+pub fn diff_accounts(before : &HashMap<u16, Account>,
+                     after  : &HashMap<u16, Account>)
+-> BTreeMap<u16, AccountDelta>
+{
+    before.keys()
+          .chain(after.keys())
+          .filter_map(|client|
+          {
+              let delta = AccountDelta
+              {
+                  before  : before.get(client).cloned().unwrap_or_default(),
+                  after   : after.get(client).cloned().unwrap_or_default()
+              };
+              (delta.before != delta.after).then_some((*client, delta))
+          })
+          .collect()
+}
+
+// writes the differences as csv, one row per client which changed:
+//
+//   client,available,held,total,was_locked,locked
+//
+// the amounts are the change from before to after.
+pub fn write_diff<W>(diff : &BTreeMap<u16, AccountDelta>, writer : &mut W)
+-> io::Result<()>
+where W: Write
+{
+    let mut csv_writer = csv::WriterBuilder::new()
+                         .terminator(csv::Terminator::CRLF)
+                         .from_writer(&mut *writer);
+
+    csv_writer.write_record(["client", "available", "held", "total", "was_locked", "locked"])?;
+    for (client, delta) in diff
+    {
+        csv_writer.write_record(&[
+            client.to_string(),
+            format!("{:.4}", delta.available()),
+            format!("{:.4}", delta.held()),
+            format!("{:.4}", delta.total()),
+            delta.before.locked.to_string(),
+            delta.after.locked.to_string()
+        ])?;
+    }
+    csv_writer.flush()?;
+    drop(csv_writer);
+    writer.flush()
+}
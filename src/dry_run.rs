@@ -0,0 +1,294 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+use crate::audit::{ClientFlows, Violation};
+use crate::common::*;
+use crate::diff::{diff_accounts, AccountDelta};
+use crate::engine::Engine;
+use crate::events::EngineEvent;
+use crate::history::BalanceHistory;
+use crate::input::Input;
+use crate::ledger::GeneralLedger;
+use crate::registry::TxRegistry;
+use crate::sink::{json_string, EventSink};
+use crate::summary::{RunSummary, SummaryFormat};
+
+// a row which a dry run refused, either because it couldn't be
+// parsed or because the engine's rules refused it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rejection
+{
+    // the input the row was in, None when the run was given a reader.
+    pub input   : Option<String>,
+    pub line    : u64,
+    pub error   : ProcessError
+}
+
+// what processing the rows would do to the engine's accounts.
+#[derive(Debug, Clone)]
+pub struct DryRun
+{
+    // every row refused, in the order they were read.
+    pub rejections  : Vec<Rejection>,
+    // how each client's account would change, for the clients
+    // whose accounts would change.
+    pub deltas      : BTreeMap<u16, AccountDelta>,
+    // invariant violations the audit found, if the engine audits.
+    pub violations  : Vec<(u64, Violation)>,
+    pub summary     : RunSummary,
+    // the failure which stopped the run, if one did. The rejections
+    // and deltas are those up to the failure.
+    pub result      : Result<(), ProcessError>
+}
+
+impl DryRun
+{
+    pub fn write<W>(&self, format : SummaryFormat, writer : &mut W)
+    -> io::Result<()>
+    where W: Write
+    {
+        match format
+        {
+            SummaryFormat::Text => self.write_text(writer),
+            SummaryFormat::Json => self.write_json(writer)
+        }
+    }
+
+    // the run summary, followed by
+    //
+    //   rejections:      2
+    //     a.csv line 3: insufficient_funds, <reason>
+    //     ...
+    //   balance changes: 1
+    //     client 1: available +5.0000, held +0.0000, total +5.0000
+    //     ...
+    fn write_text<W>(&self, writer : &mut W)
+    -> io::Result<()>
+    where W: Write
+    {
+        self.summary.write(SummaryFormat::Text, writer)?;
+
+        writeln!(writer, "rejections:      {}", self.rejections.len())?;
+        for rejection in &self.rejections
+        {
+            let input = rejection.input.as_ref().map_or(String::new(), |input| format!("{input} "));
+            writeln!(writer, "  {input}line {}: {}, {}",
+                     rejection.line, rejection.error.code(), rejection.error)?;
+        }
+
+        writeln!(writer, "balance changes: {}", self.deltas.len())?;
+        for (client, delta) in &self.deltas
+        {
+            let locked = match (delta.before.locked, delta.after.locked)
+            {
+                (false, true) => ", locked",
+                (true, false) => ", unlocked",
+                _ => ""
+            };
+            writeln!(writer, "  client {client}: available {:+.4}, held {:+.4}, total {:+.4}{locked}",
+                     delta.available(), delta.held(), delta.total())?;
+        }
+        writer.flush()
+    }
+
+    // {"summary":{...},
+    //  "rejections":[{"file":"a.csv","line":3,"code":"insufficient_funds","message":"..."},...],
+    //  "deltas":[{"client":1,"available":"5.0000","held":"0.0000","total":"5.0000",
+    //             "was_locked":false,"locked":false},...]}
+    fn write_json<W>(&self, writer : &mut W)
+    -> io::Result<()>
+    where W: Write
+    {
+        let mut summary : Vec<u8> = Vec::new();
+        self.summary.write(SummaryFormat::Json, &mut summary)?;
+        let summary = String::from_utf8_lossy(&summary);
+
+        let rejections = self.rejections.iter().map(|rejection|
+        {
+            format!("{{\"file\":{},\"line\":{},\"code\":\"{}\",\"message\":{}}}",
+                    rejection.input.as_deref().map_or(String::from("null"), json_string),
+                    rejection.line,
+                    rejection.error.code(),
+                    json_string(&rejection.error.to_string()))
+        }).collect::<Vec<_>>().join(",");
+
+        let deltas = self.deltas.iter().map(|(client, delta)|
+        {
+            format!("{{\"client\":{client},\"available\":\"{:.4}\",\"held\":\"{:.4}\",\
+                     \"total\":\"{:.4}\",\"was_locked\":{},\"locked\":{}}}",
+                    delta.available(), delta.held(), delta.total(),
+                    delta.before.locked, delta.after.locked)
+        }).collect::<Vec<_>>().join(",");
+
+        writeln!(writer, "{{\"summary\":{},\"rejections\":[{rejections}],\"deltas\":[{deltas}]}}",
+                 summary.trim_end())?;
+        writer.flush()
+    }
+}
+
+// passes every event on to the caller's sink, keeping the rows
+// refused along the way.
+struct Rejections<'s>
+{
+    sink        : &'s mut dyn EventSink,
+    input       : Option<String>,
+    rejections  : Vec<Rejection>
+}
+
+impl Rejections<'_>
+{
+    fn keep(&mut self, line : u64, event : &ProcessError)
+    {
+        if matches!(event.category(), ErrorCategory::Row | ErrorCategory::Transaction)
+        {
+            self.rejections.push(Rejection
+            {
+                input   : self.input.clone(),
+                line,
+                error   : event.clone()
+            });
+        }
+    }
+}
+
+impl EventSink for Rejections<'_>
+{
+    fn record(&mut self, line : u64, event : &ProcessError)
+    -> io::Result<()>
+    {
+        self.keep(line, event);
+        self.sink.record(line, event)
+    }
+
+    fn record_row(&mut self, line : u64, row : &str, event : &ProcessError)
+    -> io::Result<()>
+    {
+        self.keep(line, event);
+        self.sink.record_row(line, row, event)
+    }
+
+    fn note(&mut self, line : u64, note : &RunNote)
+    -> io::Result<()>
+    {
+        self.sink.note(line, note)
+    }
+
+    fn begin_input(&mut self, input : &str)
+    {
+        self.input = Some(input.to_string());
+        self.sink.begin_input(input)
+    }
+
+    fn flush(&mut self)
+    -> io::Result<()>
+    {
+        self.sink.flush()
+    }
+}
+
+impl<'a> Engine<'a>
+{
+    // runs the inputs through the engine's rules against a copy of its
+    // accounts and transactions, and reports what would happen. The
+    // engine is only borrowed, so its accounts can't change; the
+    // transaction registry is copied rather than cloned, since clones
+    // share their transactions. Nothing is journaled, and no events or
+    // balance history are recorded. The caller's sink still gets every
+    // event, e.g. to write a rejects report.
+    pub fn dry_run(&self, inputs : &[Input], sink : &mut dyn EventSink)
+    -> DryRun
+    {
+        self.stage(sink, false, |engine, sink| engine.process_inputs(inputs, sink)).0
+    }
+
+    // as dry_run, for rows which aren't in a file.
+    pub fn dry_run_reader<R>(&self, data : &mut csv::Reader<R>, sink : &mut dyn EventSink)
+    -> DryRun
+    where R: io::Read
+    {
+        self.stage(sink, false, |engine, sink| engine.process_transactions(data, sink)).0
+    }
+
+    // runs 'run' against a copy of the engine's state, returning what it
+    // did and the state it left, which commit makes the engine's own.
+    // With 'record' the copy records events and balance history when
+    // the engine does, so that they can be committed too.
+    pub(crate) fn stage<F>(&self, sink : &mut dyn EventSink, record : bool, run : F)
+    -> (DryRun, Staged)
+    where F: FnOnce(&mut Engine, &mut dyn EventSink) -> Result<(), ProcessError>
+    {
+        let mut accounts : HashMap<u16, Account> = self.accounts.clone();
+        let mut engine = Engine::with_registry(&mut accounts,
+                                               TxRegistry::from_map(self.tx_history.snapshot()));
+        engine.configure(&self.config());
+        engine.audit = self.audit;
+        engine.flows = self.flows.clone();
+        engine.ledger = self.ledger.clone();
+        engine.summary = Some(RunSummary::new());
+        if record
+        {
+            engine.events = self.events.as_ref().map(|_| Vec::new());
+            engine.history = self.history.clone();
+        }
+
+        let mut rejections = Rejections { sink, input : None, rejections : Vec::new() };
+        let result = run(&mut engine, &mut rejections);
+
+        let summary = engine.summary.take().unwrap_or_default();
+        let violations = std::mem::take(&mut engine.violations);
+        let staged = Staged
+        {
+            txs     : engine.tx_history.snapshot(),
+            flows   : std::mem::take(&mut engine.flows),
+            ledger  : std::mem::take(&mut engine.ledger),
+            events  : engine.events.take(),
+            history : engine.history.take(),
+            accounts
+        };
+        let dry_run = DryRun
+        {
+            rejections  : rejections.rejections,
+            deltas      : diff_accounts(self.accounts, &staged.accounts),
+            violations,
+            summary,
+            result
+        };
+        (dry_run, staged)
+    }
+
+    // makes a staged state the engine's own. Events recorded while
+    // staging follow on from the engine's.
+    pub(crate) fn commit(&mut self, staged : Staged, dry_run : &DryRun)
+    {
+        *self.accounts = staged.accounts;
+        self.tx_history.restore(staged.txs);
+        self.flows = staged.flows;
+        self.ledger = staged.ledger;
+        self.violations.extend(dry_run.violations.iter().cloned());
+        if let (Some(events), Some(staged)) = (&mut self.events, staged.events)
+        {
+            let seq = events.len() as u64;
+            events.extend(staged.into_iter().map(|event| EngineEvent { seq : event.seq + seq,
+                                                                       ..event }));
+        }
+        if staged.history.is_some()
+        {
+            self.history = staged.history;
+        }
+        if let Some(summary) = &mut self.summary
+        {
+            summary.add(&dry_run.summary);
+        }
+    }
+}
+
+// the state a staged run left, see Engine::stage.
+pub(crate) struct Staged
+{
+    accounts    : HashMap<u16, Account>,
+    txs         : HashMap<u32, Tx>,
+    flows       : HashMap<u16, ClientFlows>,
+    ledger      : GeneralLedger,
+    events      : Option<Vec<EngineEvent>>,
+    history     : Option<BalanceHistory>
+}
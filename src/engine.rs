@@ -1,36 +1,102 @@
 use std::collections::HashMap;
 
+use crate::audit::{AuditMode, ClientFlows, Violation};
 use crate::common::*;
+use crate::events::EngineEvent;
+use crate::history::BalanceHistory;
+use crate::output::*;
+use crate::journal::Journal;
+use crate::ledger::GeneralLedger;
+use crate::registry::TxRegistry;
+use crate::summary::RunSummary;
 
 pub struct Engine <'a>
 {
     pub accounts        : &'a mut  HashMap<u16, Account>,
-    pub tx_history      : HashMap<u32, Tx>,
+    pub tx_history      : TxRegistry,
+    pub lock_policy     : LockPolicy,
+    // what disputing a withdrawal does.
+    pub dispute_policy  : WithdrawalDisputePolicy,
+    // what referencing another client's transaction does.
+    pub cross_client    : CrossClientPolicy,
+    // the decimal places amounts are rounded to, at most 4.
+    pub rounding_dp     : u32,
+    // refuse deposits and withdrawals of negative amounts.
+    pub reject_negative : bool,
+    // when set, every command is journaled before it is applied.
+    pub journal         : Option<Journal>,
+    // rows up to and including this line are skipped, set by
+    // recover so that an interrupted file carries on where it was.
+    pub resume_after    : u64,
+    // when to check the ledger invariants, see audit.rs.
+    pub audit           : AuditMode,
+    // the money moved through each client's account, which
+    // the invariants compare the account balances against.
+    pub flows           : HashMap<u16, ClientFlows>,
+    // every invariant violation found, with the line it was found
+    // after (0 for the checks made at the end of a run).
+    pub violations      : Vec<(u64, Violation)>,
+    // the double-entry ledger every applied command is posted to.
+    pub ledger          : GeneralLedger,
+    // when set, every operation accepted or rejected is recorded
+    // as an event, see events.rs.
+    pub events          : Option<Vec<EngineEvent>>,
+    // when set, every balance each account has had is kept
+    // for point in time queries, see history.rs.
+    pub history         : Option<BalanceHistory>,
+    // when set, the rows processed are counted, see summary.rs.
+    pub summary         : Option<RunSummary>,
 }
 
 impl<'a> Engine <'a>
 {
     pub fn new ( accounts : &'a mut HashMap<u16, Account>) -> Self
+    {
+        Engine::with_registry(accounts, TxRegistry::new())
+    }
+
+    // an engine which shares its transaction history with every
+    // other engine given a clone of the same registry.
+    pub fn with_registry ( accounts : &'a mut HashMap<u16, Account>,
+                           registry : TxRegistry) -> Self
     {
         Engine
         {
-            accounts:       accounts,
-            tx_history:     HashMap::new(),
+            accounts,
+            tx_history:      registry,
+            lock_policy:     LockPolicy::default(),
+            dispute_policy:  WithdrawalDisputePolicy::default(),
+            cross_client:    CrossClientPolicy::default(),
+            rounding_dp:     MAX_DP,
+            reject_negative: true,
+            journal:         None,
+            resume_after:    0,
+            audit:           AuditMode::default(),
+            flows:           HashMap::new(),
+            violations:      Vec::new(),
+            ledger:          GeneralLedger::new(),
+            events:          None,
+            history:         None,
+            summary:         None,
         }
     }
 
-    pub fn output (&self)
+    // the lifecycle state of a deposit or withdrawal, for auditing.
+    pub fn tx_state (&self, tx_id : u32) -> Option<TxState>
     {
-        // four spaces tends to format better
-        let fs = "    ";
-        println!("client,{fs}available,  {fs}held, {fs}total,{fs}locked");
-        for (key, val) in self.accounts.iter()
-        {
-            let available   = val.available;
-            let held        = val.held;
-            let total       = val.available + val.held;
-            let locked      = val.locked;
-            println!("{key},{fs}{fs}{fs}{available:.4},{fs}{held:.4},{fs}{total:.4},{fs}{locked}");
-        }
+        self.tx_history.get(&tx_id).map(|tx| tx.state)
+    }
+
+    // writes the accounts to stdout as csv.
+    pub fn output (&self) -> std::io::Result<()>
+    {
+        self.write_output(OutputFormat::Csv, &mut std::io::stdout().lock())
+    }
+
+    pub fn write_output <W> (&self, format : OutputFormat, writer : &mut W) 
+    -> std::io::Result<()>
+    where W: std::io::Write
+    {
+        write_accounts(self.accounts, format, writer)
     }
 }
\ No newline at end of file
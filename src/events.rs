@@ -0,0 +1,256 @@
+use std::io::{self, BufReader, Read, Write};
+
+use rust_decimal::prelude::*;
+
+use crate::command::TxCommand;
+use crate::common::*;
+use crate::engine::Engine;
+use crate::record::TxKind;
+
+// the version of the event stream format written by this build.
+// Streams written by older versions can be read, newer ones can't.
+// Version 2 added the code of a rejected event.
+pub const EVENT_VERSION : u32 = 2;
+
+// what happened to an operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventKind
+{
+    // the command was applied and moved 'amount'. 'reversed' is set for
+    // a withdrawal disputed (resolved, charged back) under
+    // WithdrawalDisputePolicy::Reverse, so the stream doesn't depend on
+    // the policy the run used.
+    Applied     { command : TxCommand, amount : Decimal, reversed : bool },
+    // the command was refused, or the row couldn't be parsed into one.
+    // 'code' is the error's stable code (see ProcessError::code), empty
+    // in streams older than version 2, and 'reason' its message.
+    Rejected    { command : Option<TxCommand>, code : String, reason : String }
+}
+
+impl EventKind
+{
+    pub fn rejected(command : Option<TxCommand>, error : &ProcessError)
+    -> Self
+    {
+        EventKind::Rejected
+        {
+            command,
+            code    : error.code().to_string(),
+            reason  : error.to_string()
+        }
+    }
+}
+
+// an operation the engine accepted or rejected, numbered in the order
+// they happened. 'line' is the line of the transactions file the
+// operation came from, 0 for commands given to Engine::apply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineEvent
+{
+    pub version : u32,
+    pub seq     : u64,
+    pub line    : u64,
+    pub kind    : EventKind
+}
+
+impl<'a> Engine<'a>
+{
+    // records an event, if the engine is recording them.
+    pub(crate) fn record_event(&mut self, line : u64, kind : EventKind)
+    {
+        if let Some(events) = &mut self.events
+        {
+            events.push(EngineEvent
+            {
+                version : EVENT_VERSION,
+                seq     : events.len() as u64 + 1,
+                line,
+                kind
+            });
+        }
+    }
+
+    // rebuilds the engine's state from an event stream alone, applying
+    // every applied event again and skipping the rejected ones. Start
+    // from the same accounts and transactions the recorded run started
+    // from (usually none) to end with the same state.
+    //
+    // every applied event passed the engine's checks when it was
+    // recorded, so one which can't be applied again means the stream
+    // doesn't belong to this state, and is reported as invalid data.
+    // Deposits and withdrawals are applied with the amount recorded,
+    // as rounded by the recorded run, whatever this engine's rules.
+    pub fn replay(&mut self, events : &[EngineEvent])
+    -> io::Result<()>
+    {
+        let config = self.config();
+        self.lock_policy = LockPolicy::Unenforced;
+        self.cross_client = CrossClientPolicy::Refuse;
+        self.rounding_dp = MAX_DP;
+        self.reject_negative = false;
+
+        let mut result = Ok(());
+        for event in events
+        {
+            if let EventKind::Applied { command, amount, reversed } = event.kind
+            {
+                let command = match command.amount()
+                {
+                    Some(_) => TxCommand::new(command.kind(), command.client(),
+                                              command.tx(), amount),
+                    None => command
+                };
+                self.dispute_policy = match reversed
+                {
+                    true  => WithdrawalDisputePolicy::Reverse,
+                    false => WithdrawalDisputePolicy::Withhold
+                };
+
+                if let Err(err) = self.apply(command)
+                {
+                    result = Err(io::Error::new(io::ErrorKind::InvalidData,
+                                 format!("event {} could not be replayed: {err}", event.seq)));
+                    break;
+                }
+            }
+        }
+
+        self.configure(&config);
+        result
+    }
+}
+
+// writes the events as csv, one row per event:
+//
+//   version,seq,line,outcome,type,client,tx,amount,reversed,code,reason
+//
+// type, client and tx are empty for rows which couldn't be parsed,
+// and amount for the commands which don't carry one.
+pub fn write_events<W>(events : &[EngineEvent], writer : &mut W)
+-> io::Result<()>
+where W: Write
+{
+    let mut csv_writer = csv::WriterBuilder::new()
+                         .terminator(csv::Terminator::CRLF)
+                         .from_writer(&mut *writer);
+
+    csv_writer.write_record(["version", "seq", "line", "outcome", "type", "client",
+                             "tx", "amount", "reversed", "code", "reason"])?;
+    for event in events
+    {
+        let (outcome, command, amount, reversed, code, reason) = match &event.kind
+        {
+            EventKind::Applied { command, amount, reversed } =>
+            {
+                ("applied", Some(command), Some(*amount), reversed.to_string(), "", "")
+            }
+            EventKind::Rejected { command, code, reason } =>
+            {
+                ("rejected", command.as_ref(), command.and_then(|c| c.amount()),
+                 String::new(), code.as_str(), reason.as_str())
+            }
+        };
+
+        csv_writer.write_record(&[
+            event.version.to_string(),
+            event.seq.to_string(),
+            event.line.to_string(),
+            outcome.to_string(),
+            command.map_or(String::new(), |c| c.kind().name().to_string()),
+            command.map_or(String::new(), |c| c.client().to_string()),
+            command.map_or(String::new(), |c| c.tx().to_string()),
+            amount.map_or(String::new(), |a| a.to_string()),
+            reversed,
+            code.to_string(),
+            reason.to_string()
+        ])?;
+    }
+    csv_writer.flush()?;
+    drop(csv_writer);
+    writer.flush()
+}
+
+// reads an event stream written by write_events, or by an older
+// version, whose rows have no code.
+pub fn read_events<R>(reader : R)
+-> io::Result<Vec<EngineEvent>>
+where R: Read
+{
+    let mut data = csv::Reader::from_reader(BufReader::new(reader));
+
+    let mut events = Vec::new();
+    for result in data.records()
+    {
+        let entry = result.map_err(io::Error::other)?;
+        let line = entry.position().map_or(0, |pos| pos.line());
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData,
+                          format!("event stream line {line} is invalid: '{}'",
+                                  entry.iter().collect::<Vec<_>>().join(",")));
+
+        let version = entry.get(0).and_then(|version| version.parse::<u32>().ok())
+                                  .ok_or_else(invalid)?;
+        if version > EVENT_VERSION
+        {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                       format!("event stream line {line} has version {version}, \
+                                this build reads up to version {EVENT_VERSION}")));
+        }
+        let columns = match version
+        {
+            1 => 10,
+            _ => 11
+        };
+        if entry.len() != columns
+        {
+            return Err(invalid());
+        }
+
+        let command = match &entry[4]
+        {
+            "" => None,
+            name =>
+            {
+                let kind = TxKind::from_name(name).ok_or_else(invalid)?;
+                let amount = match &entry[7]
+                {
+                    "" => Decimal::ZERO,
+                    amount => Decimal::from_str(amount).map_err(|_| invalid())?
+                };
+                Some(TxCommand::new(kind,
+                                    entry[5].parse::<u16>().map_err(|_| invalid())?,
+                                    entry[6].parse::<u32>().map_err(|_| invalid())?,
+                                    amount))
+            }
+        };
+
+        let kind = match (&entry[3], command)
+        {
+            ("applied", Some(command)) => EventKind::Applied
+            {
+                command,
+                amount   : Decimal::from_str(&entry[7]).map_err(|_| invalid())?,
+                reversed : entry[8].parse::<bool>().map_err(|_| invalid())?
+            },
+            ("rejected", command) => EventKind::Rejected
+            {
+                command,
+                code    : match version
+                {
+                    1 => String::new(),
+                    _ => entry[9].to_string()
+                },
+                reason  : entry[columns - 1].to_string()
+            },
+            _ => return Err(invalid())
+        };
+
+        events.push(EngineEvent
+        {
+            version,
+            seq     : entry[1].parse::<u64>().map_err(|_| invalid())?,
+            line    : entry[2].parse::<u64>().map_err(|_| invalid())?,
+            kind
+        });
+    }
+    Ok(events)
+}
@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+// writes a transactions file of 'rows' rows spread over clients 1 to
+// 'clients', for load tests and demos. Most rows are deposits and
+// withdrawals; disputes, resolves and chargebacks reference one of the
+// client's earlier deposits, so some of them are refused the way real
+// ones would be (a resolve of a deposit which isn't disputed, say).
+// The same seed always writes the same file.
+pub fn generate_transactions<W>(rows    : u32,
+                                clients : u16,
+                                seed    : u64,
+                                writer  : &mut W)
+-> io::Result<()>
+where W: Write
+{
+    let clients = clients.max(1);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut deposits : HashMap<u16, Vec<u32>> = HashMap::new();
+
+    writeln!(writer, "type,client,tx,amount")?;
+    for tx in 1..=rows
+    {
+        let client : u16 = rng.gen_range(1..=clients);
+        let roll : u32 = rng.gen_range(0..100);
+        let previous = deposits.get(&client)
+                       .and_then(|txs| txs.get(rng.gen_range(0..txs.len().max(1))))
+                       .copied();
+
+        match (roll, previous)
+        {
+            (0..=9, Some(prev))     => writeln!(writer, "dispute,{client},{prev},")?,
+            (10..=13, Some(prev))   => writeln!(writer, "resolve,{client},{prev},")?,
+            (14, Some(prev))        => writeln!(writer, "chargeback,{client},{prev},")?,
+            (15..=39, _) =>
+            {
+                let amount = rng.gen_range(1..500) as f64 / 100.0;
+                writeln!(writer, "withdrawal,{client},{tx},{amount}")?;
+            }
+            _ =>
+            {
+                let amount = rng.gen_range(1..1000) as f64 / 100.0;
+                writeln!(writer, "deposit,{client},{tx},{amount}")?;
+                deposits.entry(client).or_default().push(tx);
+            }
+        }
+    }
+    writer.flush()
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+use rust_decimal::prelude::*;
+
+use crate::command::{TxCommand, TxOutcome};
+use crate::common::Account;
+use crate::engine::Engine;
+
+// a client's account right after a command was applied to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalancePoint
+{
+    // the input the command came from, counting each run of
+    // process_transactions (process_inputs makes one per input) from 0.
+    // Commands given to Engine::apply (and replayed events) come before
+    // the next input's rows, they're recorded at its line 0.
+    pub input   : usize,
+    // the line of the input the command came from. Each client's points
+    // are kept in (input, line) order, see BalanceHistory::push.
+    pub line    : u64,
+    pub command : TxCommand,
+    pub amount  : Decimal,
+    pub account : Account
+}
+
+// every balance each client's account has had, so that balances can be
+// looked up as of an earlier point in the run. It keeps a copy of the
+// account per applied command, so it's off unless Engine::history is set.
+#[derive(Debug, Clone, Default)]
+pub struct BalanceHistory
+{
+    // each client's account before the first command applied to it.
+    opening : HashMap<u16, Account>,
+    points  : HashMap<u16, Vec<BalancePoint>>,
+    // the inputs begun, line numbers start again with each one.
+    inputs  : usize
+}
+
+impl BalanceHistory
+{
+    pub fn new()
+    -> Self
+    {
+        BalanceHistory::default()
+    }
+
+    // the account the client had before anything was applied to it,
+    // None if nothing has been.
+    pub fn opening(&self, client : u16)
+    -> Option<&Account>
+    {
+        self.opening.get(&client)
+    }
+
+    // every command applied to the client, in the order they were applied.
+    pub fn points(&self, client : u16)
+    -> &[BalancePoint]
+    {
+        self.points.get(&client).map_or(&[], |points| points.as_slice())
+    }
+
+    // the client's account right after the deposit or withdrawal 'tx'
+    // was applied, None if the client has no such transaction.
+    pub fn after_tx(&self, client : u16, tx : u32)
+    -> Option<&Account>
+    {
+        self.points(client)
+            .iter()
+            .find(|point| point.command.tx() == tx && point.command.amount().is_some())
+            .map(|point| &point.account)
+    }
+
+    // the client's account once every row up to and including 'line'
+    // of 'input' had been processed (see BalancePoint::input). Commands
+    // given to Engine::apply (line 0) count as before the next row.
+    pub fn as_of_line(&self, client : u16, input : usize, line : u64)
+    -> Option<&Account>
+    {
+        let points = self.points(client);
+        match points.partition_point(|point| (point.input, point.line) <= (input, line))
+        {
+            0 => self.opening(client),
+            n => Some(&points[n - 1].account)
+        }
+    }
+
+    // the rows which follow are from the next input.
+    pub(crate) fn begin_input(&mut self)
+    {
+        self.inputs += 1;
+    }
+
+    pub(crate) fn open(&mut self, client : u16, account : &Account)
+    {
+        self.opening.entry(client).or_insert_with(|| account.clone());
+    }
+
+    // the lookups search each client's points by (input, line), so a point
+    // is never recorded before the one preceding it. A journal replayed by
+    // Engine::recover can hold the rows of several inputs, whose lines
+    // start again, those points are recorded at the last point's position.
+    pub(crate) fn push(&mut self, line : u64, outcome : &TxOutcome)
+    {
+        let input = match line
+        {
+            0 => self.inputs,
+            _ => self.inputs.saturating_sub(1)
+        };
+        let points = self.points.entry(outcome.command.client()).or_default();
+        let (input, line) = match points.last()
+        {
+            Some(last) if (last.input, last.line) > (input, line) => (last.input, last.line),
+            _ => (input, line)
+        };
+
+        points.push(BalancePoint
+        {
+            input,
+            line,
+            command : outcome.command,
+            amount  : outcome.amount,
+            account : outcome.account.clone()
+        });
+    }
+}
+
+impl<'a> Engine<'a>
+{
+    // the client's balance right after the deposit or withdrawal 'tx'.
+    // None if the engine isn't keeping a history, or the client has no
+    // such transaction.
+    pub fn balance_after_tx(&self, client : u16, tx : u32)
+    -> Option<Account>
+    {
+        self.history.as_ref()?.after_tx(client, tx).cloned()
+    }
+
+    // the client's balance once every row up to and including 'line' of
+    // 'input' had been processed, the first input being 0. None if the
+    // engine isn't keeping a history, or nothing was ever applied to the
+    // client.
+    pub fn balance_as_of_line(&self, client : u16, input : usize, line : u64)
+    -> Option<Account>
+    {
+        self.history.as_ref()?.as_of_line(client, input, line).cloned()
+    }
+}
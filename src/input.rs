@@ -0,0 +1,221 @@
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::common::ProcessError;
+use crate::engine::Engine;
+use crate::sink::EventSink;
+
+// where a transactions file is read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input
+{
+    // standard input, named '-' on the command line.
+    Stdin,
+    File(PathBuf)
+}
+
+impl Input
+{
+    // the input a command line argument names, '-' for stdin.
+    pub fn from_arg(arg : &str)
+    -> Self
+    {
+        match arg
+        {
+            "-" => Input::Stdin,
+            _   => Input::File(PathBuf::from(arg))
+        }
+    }
+
+    // how the input is named in events and reports.
+    pub fn name(&self)
+    -> String
+    {
+        match self
+        {
+            Input::Stdin => String::from("-"),
+            Input::File(path) => path.to_string_lossy().to_string()
+        }
+    }
+
+    // a csv reader over the input. The reader is flexible so that rows
+    // with the wrong number of columns are reported by the engine.
+    pub fn open(&self)
+    -> io::Result<csv::Reader<Box<dyn Read>>>
+    {
+        let reader : Box<dyn Read> = match self
+        {
+            Input::Stdin => Box::new(io::stdin().lock()),
+            Input::File(path) => Box::new(std::fs::File::open(path)?)
+        };
+        Ok(csv::ReaderBuilder::new()
+           .flexible(true)
+           .from_reader(reader))
+    }
+}
+
+// the inputs a list of command line arguments name, in order. An
+// argument with a '*' or '?' in its file name is a glob, replaced by
+// the files it matches sorted by name; a glob which matches nothing is
+// an error. Wildcards are only understood in the file name, not in the
+// directories leading to it.
+pub fn expand_inputs(args : &[String])
+-> io::Result<Vec<Input>>
+{
+    let mut inputs = Vec::new();
+    for arg in args
+    {
+        if !is_glob(arg)
+        {
+            inputs.push(Input::from_arg(arg));
+            continue;
+        }
+
+        let pattern = Path::new(arg);
+        let dir = match pattern.parent()
+        {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new(".")
+        };
+        let name_pattern = pattern.file_name()
+                                  .map(|name| name.to_string_lossy().to_string())
+                                  .unwrap_or_default();
+        if is_glob(&dir.to_string_lossy())
+        {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      format!("{arg}: wildcards are only supported \
+                                               in the file name")));
+        }
+
+        let mut matched = Vec::new();
+        for entry in std::fs::read_dir(dir).map_err(|err| io::Error::new(err.kind(),
+                                                                       format!("{arg}: {err}")))?
+        {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if entry.file_type()?.is_file() && glob_matches(&name_pattern, &name)
+            {
+                matched.push(pattern.with_file_name(name));
+            }
+        }
+        if matched.is_empty()
+        {
+            return Err(io::Error::new(io::ErrorKind::NotFound,
+                                      format!("{arg}: no files match")));
+        }
+        matched.sort();
+        inputs.extend(matched.into_iter().map(Input::File));
+    }
+    Ok(inputs)
+}
+
+fn is_glob(arg : &str)
+-> bool
+{
+    arg.contains(['*', '?'])
+}
+
+// whether 'name' matches 'pattern', where '*' matches any run of
+// characters and '?' any single one.
+fn glob_matches(pattern : &str, name : &str)
+-> bool
+{
+    let pattern : Vec<char> = pattern.chars().collect();
+    let name : Vec<char> = name.chars().collect();
+
+    // where to carry on from if the text after the last '*' doesn't match.
+    let mut backtrack : Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len()
+    {
+        match pattern.get(p)
+        {
+            Some('*') =>
+            {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] =>
+            {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack
+            {
+                Some((star, from)) =>
+                {
+                    backtrack = Some((star, from + 1));
+                    p = star + 1;
+                    n = from + 1;
+                }
+                None => return false
+            }
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+impl<'a> Engine<'a>
+{
+    // processes each input in turn against the same accounts, so a
+    // later file can dispute a transaction from an earlier one. The sink
+    // is told which input the events which follow are from. Line
+    // numbers start again with each input. An input which can't be
+    // opened, or a failure processing one, stops the run before the
+    // next input; the inputs before it stay applied.
+    pub fn process_inputs(&mut self,
+                          inputs  : &[Input],
+                          sink    : &mut dyn EventSink)
+    -> Result<(), ProcessError>
+    {
+        for input in inputs
+        {
+            let name = input.name();
+            sink.begin_input(&name);
+
+            let mut data = input.open()
+                                .map_err(|err| ProcessError::ExternalErr(format!("{name}: {err}")))?;
+            self.process_transactions(&mut data, sink)?;
+        }
+        Ok(())
+    }
+}
+
+
+// unit tests ////////////////////////////////////////////////////////////////
+//////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod glob_matching
+{
+    use super::*;
+
+    #[test]
+    fn test_match_wildcards()
+    {
+        assert!(glob_matches("*.csv", "monday.csv"));
+        assert!(glob_matches("*.csv", ".csv"));
+        assert!(glob_matches("day-?.csv", "day-1.csv"));
+        assert!(glob_matches("*-*.csv", "partner-a-2022.csv"));
+        assert!(glob_matches("a*b*c", "abcbc"));
+        assert!(glob_matches("*", "anything"));
+        assert!(glob_matches("exact.csv", "exact.csv"));
+    }
+
+    #[test]
+    fn test_reject_non_matches()
+    {
+        assert!(!glob_matches("*.csv", "monday.csv.bak"));
+        assert!(!glob_matches("day-?.csv", "day-10.csv"));
+        assert!(!glob_matches("a*b*c", "abcb"));
+        assert!(!glob_matches("exact.csv", "other.csv"));
+    }
+
+    #[test]
+    fn test_stdin_is_a_dash()
+    {
+        assert_eq!(Input::from_arg("-"), Input::Stdin);
+        assert_eq!(Input::from_arg("a.csv"), Input::File(PathBuf::from("a.csv")));
+        assert_eq!(Input::Stdin.name(), "-");
+    }
+}
@@ -0,0 +1,441 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use rust_decimal::prelude::*;
+
+use crate::command::TxCommand;
+use crate::common::*;
+use crate::engine::Engine;
+use crate::history::BalanceHistory;
+use crate::ledger::GeneralLedger;
+use crate::record::TxKind;
+
+// a write-ahead journal of every command the engine applies.
+//
+// An entry is written (and by default synced to disk) after a command
+// has passed every check but before any account or transaction is
+// changed, so a crash at any point leaves the journal holding at least
+// everything that was applied. A checkpoint writes the full state to a
+// separate file and empties the journal; recovery loads the checkpoint
+// and replays the journal on top of it.
+//
+// journal entries are one line each:   line,type,client,tx,amount
+// where 'line' is the line of the transactions file the command came
+// from (0 for commands given to Engine::apply directly).
+//
+// every journal a checkpoint empties is replaced by the next generation,
+// whose first line is 'journal,<generation>' (a journal without one is
+// generation 0). The checkpoint records the generation it includes, so
+// if a crash comes after the checkpoint is written but before the
+// journal is replaced, the entries it already includes aren't replayed.
+pub struct Journal
+{
+    file            : File,
+    journal_path    : PathBuf,
+    checkpoint_path : PathBuf,
+    // sync every entry to disk before it is applied. Turning this off
+    // is faster, but entries may be lost if the machine itself fails.
+    pub sync        : bool,
+    last_line       : u64,
+    generation      : u64
+}
+
+impl Journal
+{
+    // opens (or creates) a journal, appending to any entries
+    // already in it. Run Engine::recover before applying more. An
+    // entry torn by a crash is cut off, so the next entry starts on a
+    // line of its own. A journal whose entries the checkpoint already
+    // includes is replaced by the next generation, as the checkpoint
+    // would have done had it completed.
+    pub fn open<P, Q>(journal_path : P, checkpoint_path : Q)
+    -> io::Result<Self>
+    where P: AsRef<Path>, Q: AsRef<Path>
+    {
+        let file = OpenOptions::new()
+                   .append(true)
+                   .create(true)
+                   .open(journal_path.as_ref())?;
+
+        let text = fs::read(journal_path.as_ref())?;
+        if text.last().is_some_and(|last| *last != b'\n')
+        {
+            let complete = text.iter().rposition(|c| *c == b'\n').map_or(0, |end| end + 1);
+            file.set_len(complete as u64)?;
+        }
+
+        let mut journal = Journal
+        {
+            file,
+            journal_path    : journal_path.as_ref().to_path_buf(),
+            checkpoint_path : checkpoint_path.as_ref().to_path_buf(),
+            sync            : true,
+            last_line       : 0,
+            generation      : journal_generation(&String::from_utf8_lossy(&text))?
+        };
+
+        if let Some(included) = checkpoint_generation(&journal.checkpoint_path)?
+        {
+            if journal.generation <= included
+            {
+                journal.replace(included + 1)?;
+            }
+        }
+        Ok(journal)
+    }
+
+    // the line of the last command journaled from a file.
+    pub fn last_line(&self)
+    -> u64
+    {
+        self.last_line
+    }
+
+    // writes an entry for a command about to be applied. The entry is
+    // written with a single write so a crash can only tear the last line.
+    pub fn append(&mut self, line : u64, command : &TxCommand)
+    -> io::Result<()>
+    {
+        let amount = command.amount().map_or(String::new(), |a| a.to_string());
+        let entry = format!("{line},{},{},{},{amount}\n",
+                            command.kind().name(),
+                            command.client(),
+                            command.tx());
+
+        self.file.write_all(entry.as_bytes())?;
+        if self.sync
+        {
+            self.file.sync_data()?;
+        }
+        self.last_line = self.last_line.max(line);
+        Ok(())
+    }
+
+    // writes the full state to the checkpoint file and empties the
+    // journal. The checkpoint is written to a temporary file first and
+    // renamed over the old one, so there is always one complete copy.
+    // The journal is replaced the same way.
+    pub fn checkpoint(&mut self,
+                      accounts  : &HashMap<u16, Account>,
+                      txs       : &HashMap<u32, Tx>)
+    -> io::Result<()>
+    {
+        let tmp_path = self.checkpoint_path.with_extension("tmp");
+        {
+            let mut tmp = io::BufWriter::new(File::create(&tmp_path)?);
+            writeln!(tmp, "journal,{}", self.generation)?;
+
+            let mut clients : Vec<&u16> = accounts.keys().collect();
+            clients.sort();
+            for client in clients
+            {
+                let account = &accounts[client];
+                writeln!(tmp, "account,{client},{},{},{}",
+                         account.available, account.held, account.locked)?;
+            }
+
+            let mut ids : Vec<&u32> = txs.keys().collect();
+            ids.sort();
+            for id in ids
+            {
+                let tx = &txs[id];
+                writeln!(tmp, "tx,{id},{},{},{},{}",
+                         tx.client, tx.kind.name(), tx.amount, tx.state.name())?;
+            }
+
+            let file = tmp.into_inner().map_err(|err| err.into_error())?;
+            file.sync_all()?;
+        }
+        fs::rename(&tmp_path, &self.checkpoint_path)?;
+
+        // everything journaled is now in the checkpoint.
+        self.replace(self.generation + 1)
+    }
+
+    // replaces the journal with an empty one of the given generation.
+    fn replace(&mut self, generation : u64)
+    -> io::Result<()>
+    {
+        let mut tmp_path = self.journal_path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        {
+            let mut tmp = File::create(&tmp_path)?;
+            writeln!(tmp, "journal,{generation}")?;
+            tmp.sync_all()?;
+        }
+        fs::rename(&tmp_path, &self.journal_path)?;
+
+        self.file = OpenOptions::new().append(true).open(&self.journal_path)?;
+        self.generation = generation;
+        self.last_line = 0;
+        Ok(())
+    }
+}
+
+// the generation of a journal, from its first line.
+fn journal_generation(text : &str)
+-> io::Result<u64>
+{
+    match text.lines().next().and_then(|first| first.strip_prefix("journal,"))
+    {
+        Some(generation) => generation.parse::<u64>().map_err(|_|
+        {
+            invalid_data(format!("journal line 1 is invalid: 'journal,{generation}'"))
+        }),
+        None => Ok(0)
+    }
+}
+
+// the generation of the journal the checkpoint includes, None if there
+// is no checkpoint or it was written before checkpoints recorded one.
+fn checkpoint_generation(path : &Path)
+-> io::Result<Option<u64>>
+{
+    let file = match File::open(path)
+    {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err)
+    };
+
+    match BufReader::new(file).lines().next().transpose()?
+    {
+        Some(first) if first.starts_with("journal,") => journal_generation(&first).map(Some),
+        _ => Ok(None)
+    }
+}
+
+// the state saved by the last checkpoint. A missing
+// checkpoint file is read as an empty state.
+pub fn read_checkpoint<P>(path : P)
+-> io::Result<(HashMap<u16, Account>, HashMap<u32, Tx>)>
+where P: AsRef<Path>
+{
+    let mut accounts = HashMap::new();
+    let mut txs = HashMap::new();
+
+    let file = match File::open(path)
+    {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound =>
+        {
+            return Ok((accounts, txs));
+        }
+        Err(err) => return Err(err)
+    };
+
+    for (num, text) in BufReader::new(file).lines().enumerate()
+    {
+        let text = text?;
+        let fields : Vec<&str> = text.split(',').collect();
+        let parsed = match fields.as_slice()
+        {
+            ["account", client, available, held, locked] =>
+            {
+                parse_account(client, available, held, locked)
+                .map(|(client, account)| { accounts.insert(client, account); })
+            }
+            ["tx", id, client, kind, amount, state] =>
+            {
+                parse_tx(id, client, kind, amount, state)
+                .map(|tx| { txs.insert(tx.id, tx); })
+            }
+            // the journal generation, see checkpoint_generation.
+            ["journal", generation] if num == 0 => generation.parse::<u64>().ok().map(|_| ()),
+            _ => None
+        };
+
+        if parsed.is_none()
+        {
+            return Err(invalid_data(format!("checkpoint line {} is invalid: '{text}'",
+                                            num + 1)));
+        }
+    }
+    Ok((accounts, txs))
+}
+
+// every entry in the journal, after its generation line if it has
+// one. Entries are written whole with their
+// '\n', so anything after the last '\n' is an entry a crash tore, and
+// is dropped since it can't have been applied (a torn entry may still
+// parse, e.g. with its amount cut short). An invalid complete entry
+// means the journal is corrupt.
+pub fn read_journal<P>(path : P)
+-> io::Result<Vec<(u64, TxCommand)>>
+where P: AsRef<Path>
+{
+    let text = match fs::read_to_string(path)
+    {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err)
+    };
+
+    let complete = text.rfind('\n').map_or("", |end| &text[..end]);
+    let mut entries = Vec::new();
+    for (num, line) in complete.split('\n').enumerate()
+    {
+        if line.is_empty() || (num == 0 && line.starts_with("journal,"))
+        {
+            continue;
+        }
+
+        match parse_entry(line)
+        {
+            Some(entry) => entries.push(entry),
+            None =>
+            {
+                return Err(invalid_data(format!("journal line {} is invalid: '{line}'",
+                                                num + 1)));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+fn parse_entry(text : &str)
+-> Option<(u64, TxCommand)>
+{
+    let fields : Vec<&str> = text.split(',').collect();
+    if fields.len() != 5
+    {
+        return None;
+    }
+
+    let line = fields[0].parse::<u64>().ok()?;
+    let kind = TxKind::from_name(fields[1])?;
+    let client = fields[2].parse::<u16>().ok()?;
+    let tx = fields[3].parse::<u32>().ok()?;
+    let amount = if kind.has_amount()
+    {
+        Decimal::from_str(fields[4]).ok()?
+    }
+    else
+    {
+        Decimal::ZERO
+    };
+
+    Some((line, TxCommand::new(kind, client, tx, amount)))
+}
+
+fn parse_account(client : &str, available : &str, held : &str, locked : &str)
+-> Option<(u16, Account)>
+{
+    let mut account = Account::new();
+    account.available = Decimal::from_str(available).ok()?;
+    account.held = Decimal::from_str(held).ok()?;
+    account.locked = locked.parse::<bool>().ok()?;
+    Some((client.parse::<u16>().ok()?, account))
+}
+
+fn parse_tx(id : &str, client : &str, kind : &str, amount : &str, state : &str)
+-> Option<Tx>
+{
+    Some(Tx::new(id.parse::<u32>().ok()?,
+                 client.parse::<u16>().ok()?,
+                 stored_kind(kind)?,
+                 Decimal::from_str(amount).ok()?,
+                 TxState::from_name(state)?))
+}
+
+// only deposits and withdrawals are stored as transactions.
+pub(crate) fn stored_kind(name : &str)
+-> Option<TxKind>
+{
+    TxKind::from_name(name).filter(|kind| kind.has_amount())
+}
+
+fn invalid_data(message : String)
+-> io::Error
+{
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl<'a> Engine<'a>
+{
+    // rebuilds accounts and tx_history from the journal's checkpoint
+    // and the entries journaled since, replacing whatever they held.
+    // Until the first checkpoint is written the entries are replayed
+    // on top of the engine's state instead, so a run which started from
+    // opening balances recovers on top of the same opening balances.
+    //
+    // Afterwards process_transactions skips every row up to the last
+    // journaled line, so rerunning the file that was being processed
+    // when the crash happened carries on from where it stopped.
+    // returns that line (0 if the journal was empty).
+    pub fn recover(&mut self)
+    -> io::Result<u64>
+    {
+        let mut journal = match self.journal.take()
+        {
+            Some(journal) => journal,
+            None => return Err(io::Error::other("the engine has no journal"))
+        };
+
+        let result = self.replay_journal(&journal);
+        if let Ok(last_line) = result
+        {
+            journal.last_line = last_line;
+            self.resume_after = last_line;
+        }
+        self.journal = Some(journal);
+        result
+    }
+
+    fn replay_journal(&mut self, journal : &Journal)
+    -> io::Result<u64>
+    {
+        let entries = read_journal(&journal.journal_path)?;
+
+        if journal.checkpoint_path.exists()
+        {
+            let (accounts, txs) = read_checkpoint(&journal.checkpoint_path)?;
+            self.accounts.clear();
+            self.accounts.extend(accounts);
+            self.tx_history.restore(txs);
+            self.flows.clear();
+            self.ledger = GeneralLedger::new();
+            if let Some(history) = &mut self.history
+            {
+                *history = BalanceHistory::new();
+            }
+        }
+
+        // every entry passed the lock policy when it was journaled.
+        let lock_policy = self.lock_policy;
+        self.lock_policy = LockPolicy::Unenforced;
+
+        let mut last_line = 0;
+        let mut result = Ok(());
+        for (line, command) in entries
+        {
+            // at the journaled line, so that whatever is recorded while
+            // replaying (events, history) points back at the file.
+            if let Err(err) = self.apply_at(command, line)
+            {
+                result = Err(invalid_data(format!("journaled {} of tx {} could not \
+                             be replayed: {err}", command.kind().name(), command.tx())));
+                break;
+            }
+            last_line = last_line.max(line);
+        }
+
+        self.lock_policy = lock_policy;
+        result.map(|_| last_line)
+    }
+
+    // saves the current state as the journal's checkpoint and empties
+    // the journal. Call this once a file has been fully processed.
+    pub fn checkpoint(&mut self)
+    -> io::Result<()>
+    {
+        self.resume_after = 0;
+        match &mut self.journal
+        {
+            Some(journal) => journal.checkpoint(self.accounts, &self.tx_history.snapshot()),
+            None => Err(io::Error::other("the engine has no journal"))
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+
+use rust_decimal::prelude::*;
+
+use crate::command::TxCommand;
+use crate::common::{Account, MAX_DP};
+
+// the accounts of the general ledger. Balances are kept debit
+// positive, so assets (platform cash) have positive balances and
+// liabilities (what is owed to clients and card issuers) negative ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LedgerAccount
+{
+    // the money the platform holds.
+    PlatformCash,
+    // owed to a client, and free for them to withdraw.
+    ClientAvailable(u16),
+    // owed to a client, but held while a transaction is disputed.
+    ClientHeld(u16),
+    // charged back funds, owed back to the card issuers.
+    ChargebacksPayable,
+    // withdrawals reversed by a dispute (see WithdrawalDisputePolicy::
+    // Reverse), to be recovered from wherever they were paid.
+    DisputedWithdrawals,
+    // the counterpart of balances the engine started with (from a
+    // snapshot or checkpoint), whose history isn't in this ledger.
+    OpeningBalances
+}
+
+impl fmt::Display for LedgerAccount
+{
+    fn fmt(&self, f : &mut fmt::Formatter<'_>)
+    -> fmt::Result
+    {
+        match self
+        {
+            LedgerAccount::PlatformCash             => write!(f, "platform cash"),
+            LedgerAccount::ClientAvailable(client)  => write!(f, "client {client} available"),
+            LedgerAccount::ClientHeld(client)       => write!(f, "client {client} held"),
+            LedgerAccount::ChargebacksPayable       => write!(f, "chargebacks payable"),
+            LedgerAccount::DisputedWithdrawals      => write!(f, "disputed withdrawals"),
+            LedgerAccount::OpeningBalances          => write!(f, "opening balances")
+        }
+    }
+}
+
+// a balanced posting, 'amount' is debited from one account
+// and credited to the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LedgerEntry
+{
+    // the transaction which caused the posting, 0 for opening balances.
+    pub tx      : u32,
+    pub debit   : LedgerAccount,
+    pub credit  : LedgerAccount,
+    pub amount  : Decimal
+}
+
+// a double-entry ledger every engine operation posts to:
+//
+//   deposit     Dr platform cash        Cr client available
+//   withdrawal  Dr client available     Cr platform cash
+//   dispute     Dr client available     Cr client held
+//   resolve     Dr client held          Cr client available
+//   chargeback  Dr client held          Cr chargebacks payable
+//
+// and, for withdrawals disputed under WithdrawalDisputePolicy::Reverse:
+//
+//   dispute     Dr disputed withdrawals Cr client held
+//   resolve     Dr client held          Cr disputed withdrawals
+//   chargeback  Dr client held          Cr client available
+//
+// Only the balances are kept by default, set 'entries' to keep every
+// posting as well (it grows with the number of transactions).
+#[derive(Debug, Clone, Default)]
+pub struct GeneralLedger
+{
+    balances    : HashMap<LedgerAccount, Decimal>,
+    pub entries : Option<Vec<LedgerEntry>>
+}
+
+impl GeneralLedger
+{
+    pub fn new()
+    -> Self
+    {
+        GeneralLedger::default()
+    }
+
+    // the debit positive balance of an account.
+    pub fn balance(&self, account : LedgerAccount)
+    -> Decimal
+    {
+        self.balances.get(&account).copied().unwrap_or(Decimal::ZERO)
+    }
+
+    // what the ledger says the client is owed: (available, held).
+    pub fn client_balances(&self, client : u16)
+    -> (Decimal, Decimal)
+    {
+        (-self.balance(LedgerAccount::ClientAvailable(client)),
+         -self.balance(LedgerAccount::ClientHeld(client)))
+    }
+
+    // the sum of every balance, which is zero if every
+    // posting was balanced.
+    pub fn imbalance(&self)
+    -> Decimal
+    {
+        self.balances.values().fold(Decimal::ZERO, |sum, balance| sum.saturating_add(*balance))
+    }
+
+    pub fn post(&mut self, entry : LedgerEntry)
+    {
+        // rounded as the accounts round it.
+        let amount = entry.amount.round_dp(MAX_DP);
+        // the balances saturate rather than overflow, the external
+        // account's can go beyond what a client's can.
+        let debit = self.balances.entry(entry.debit).or_insert(Decimal::ZERO);
+        *debit = debit.saturating_add(amount);
+        let credit = self.balances.entry(entry.credit).or_insert(Decimal::ZERO);
+        *credit = credit.saturating_sub(amount);
+
+        if let Some(entries) = &mut self.entries
+        {
+            entries.push(LedgerEntry { amount, ..entry });
+        }
+    }
+
+    // posts a command which has been applied, 'amount' being the amount
+    // it moved. 'reverses' is set if it concerns a withdrawal reversed
+    // by a dispute.
+    pub fn post_command(&mut self, command : &TxCommand, amount : Decimal, reverses : bool)
+    {
+        let client = command.client();
+        let available = LedgerAccount::ClientAvailable(client);
+        let held = LedgerAccount::ClientHeld(client);
+        let reversed = LedgerAccount::DisputedWithdrawals;
+
+        let (debit, credit) = match (command, reverses)
+        {
+            (TxCommand::Deposit { .. }, _)          => (LedgerAccount::PlatformCash, available),
+            (TxCommand::Withdrawal { .. }, _)       => (available, LedgerAccount::PlatformCash),
+            (TxCommand::Dispute { .. }, false)      => (available, held),
+            (TxCommand::Resolve { .. }, false)      => (held, available),
+            (TxCommand::Chargeback { .. }, false)   => (held, LedgerAccount::ChargebacksPayable),
+            (TxCommand::Dispute { .. }, true)       => (reversed, held),
+            (TxCommand::Resolve { .. }, true)       => (held, reversed),
+            (TxCommand::Chargeback { .. }, true)    => (held, available)
+        };
+        self.post(LedgerEntry { tx : command.tx(), debit, credit, amount });
+    }
+
+    // posts the balances of an account the engine started with.
+    pub fn post_opening(&mut self, client : u16, account : &Account)
+    {
+        let postings = [(LedgerAccount::ClientAvailable(client), account.available),
+                        (LedgerAccount::ClientHeld(client), account.held)];
+
+        for (credit, amount) in postings
+        {
+            if !amount.is_zero()
+            {
+                self.post(LedgerEntry { tx : 0, debit : LedgerAccount::OpeningBalances,
+                                        credit, amount });
+            }
+        }
+    }
+
+    // writes the trial balance as csv, one row per account with its
+    // balance in the debit or credit column, then a row of totals:
+    //
+    //   account,debit,credit
+    pub fn write_trial_balance<W>(&self, writer : &mut W)
+    -> io::Result<()>
+    where W: Write
+    {
+        let mut accounts : Vec<&LedgerAccount> = self.balances.keys().collect();
+        accounts.sort();
+
+        let mut csv_writer = csv::WriterBuilder::new()
+                             .terminator(csv::Terminator::CRLF)
+                             .from_writer(&mut *writer);
+
+        csv_writer.write_record(["account", "debit", "credit"])?;
+
+        let mut debits = Decimal::ZERO;
+        let mut credits = Decimal::ZERO;
+        for account in accounts
+        {
+            let balance = self.balances[account];
+            let (debit, credit) = if balance >= Decimal::ZERO
+            {
+                (balance, Decimal::ZERO)
+            }
+            else
+            {
+                (Decimal::ZERO, -balance)
+            };
+            debits = debits.checked_add(debit).ok_or_else(too_large)?;
+            credits = credits.checked_add(credit).ok_or_else(too_large)?;
+
+            csv_writer.write_record(&[
+                account.to_string(),
+                written(debit)?,
+                written(credit)?
+            ])?;
+        }
+        csv_writer.write_record(&[
+            String::from("total"),
+            written(debits)?,
+            written(credits)?
+        ])?;
+        csv_writer.flush()?;
+        drop(csv_writer);
+        writer.flush()
+    }
+}
+
+// the balances of accounts which aren't a client's, and so their totals,
+// aren't bounded by MAX_AMOUNT. Decimal can't write one of 1e27 or more
+// to four places, so a trial balance which would need to is refused.
+fn written(amount : Decimal)
+-> io::Result<String>
+{
+    match amount.abs() < Decimal::from_i128_with_scale(10_i128.pow(27), 0)
+    {
+        true  => Ok(format!("{amount:.4}")),
+        false => Err(too_large())
+    }
+}
+
+fn too_large()
+-> io::Error
+{
+    io::Error::new(io::ErrorKind::InvalidData, "the trial balance is too large to be written")
+}
@@ -1,3 +1,25 @@
 mod app_process;
 pub mod engine; // public for tests
-pub mod common; // public for tests
\ No newline at end of file
+pub mod common; // public for tests
+pub mod record;
+pub mod output;
+pub mod command;
+pub mod registry;
+pub mod sharded;
+pub mod journal;
+pub mod snapshot;
+pub mod audit;
+pub mod ledger;
+pub mod events;
+pub mod history;
+pub mod statement;
+pub mod sink;
+pub mod rejects;
+pub mod summary;
+pub mod input;
+pub mod diff;
+pub mod dry_run;
+pub mod batch;
+pub mod generate;
+pub mod cli;
+pub mod config;
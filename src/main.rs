@@ -1,63 +1,451 @@
-mod engine;
-mod app_process;
-mod common;
-
-use common::Logger;
-use common::Account;
-use common::ProcessEvent;
+use tx_engine::engine::Engine;
+use tx_engine::audit::Violation;
+use tx_engine::batch::BatchPolicy;
+use tx_engine::cli::{parse_args, Command, DiffArgs, GenerateArgs, Query, ReplayArgs, RunArgs};
+use tx_engine::rejects::RejectsReport;
+use tx_engine::sink::{EventSink, FanOut, Logger, StderrSink};
+use tx_engine::common::Account;
+use tx_engine::common::ProcessError;
+use tx_engine::config::EngineConfig;
+use tx_engine::diff::{diff_accounts, write_diff};
+use tx_engine::journal::Journal;
+use tx_engine::events::{read_events, write_events};
+use tx_engine::generate::generate_transactions;
+use tx_engine::history::BalanceHistory;
+use tx_engine::input::{expand_inputs, Input};
+use tx_engine::output::write_accounts;
+use tx_engine::registry::TxRegistry;
+use tx_engine::snapshot::{read_accounts, Snapshot};
+use tx_engine::summary::{RunSummary, SummaryFormat};
 
 use std::process;
 use std::env;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::collections::HashMap;
+use std::path::Path;
 
-fn the_app()
--> ProcessEvent
+// an error about a file, or anything else outside the engine.
+fn external_err(context : &str, err : impl Display)
+-> ProcessError
 {
-    // get required command line argument
-    let args : Vec<String> = env::args().collect();
-    if args.len() < 2
+    ProcessError::ExternalErr(format!("{context}: {err}"))
+}
+
+// where the results are written, a file or stdout.
+fn open_output(path : &Option<String>)
+-> std::io::Result<Box<dyn Write>>
+{
+    match path
+    {
+        Some(path) => Ok(Box::new(BufWriter::new(File::create(path)?))),
+        None => Ok(Box::new(std::io::stdout().lock()))
+    }
+}
+
+// where the events of a run are reported, none of them by default.
+fn open_sink(args : &RunArgs)
+-> Result<FanOut<'static>, ProcessError>
+{
+    let mut sink = FanOut::default();
+    if let Some(path) = &args.log_file
+    {
+        let mut logger = Logger::new(Path::new(path)).map_err(|err| external_err(path, err))?;
+        logger.min_level = args.log_level;
+        sink.sinks.push(Box::new(logger));
+    }
+    if args.log_stderr
+    {
+        sink.sinks.push(Box::new(StderrSink { min_level : args.log_level, input : None }));
+    }
+    if let Some(path) = &args.rejects
+    {
+        let report = File::create(path).and_then(|file| RejectsReport::new(BufWriter::new(file)))
+                                       .map_err(|err| external_err(path, err))?;
+        sink.sinks.push(Box::new(report));
+    }
+    Ok(sink)
+}
+
+// the files to process, in order. Every file has to be there
+// before any of them is processed.
+fn open_inputs(args : &RunArgs)
+-> Result<Vec<Input>, ProcessError>
+{
+    let inputs = expand_inputs(&args.inputs)
+                 .map_err(|err| ProcessError::ExternalErr(err.to_string()))?;
+    for input in &inputs
+    {
+        if let Input::File(path) = input
+        {
+            File::open(path).map_err(|err| external_err(&input.name(), err))?;
+        }
+    }
+
+    // line numbers start again with each file, so whatever
+    // depends on a single sequence of lines needs a single file.
+    if inputs.len() > 1
+    {
+        let by_line = match &args.query
+        {
+            Some(Query::AsOfLine(..)) => Some("--as-of-line"),
+            Some(Query::Statement(_, Some(_), _)) => Some("--lines"),
+            _ if args.journal.is_some() => Some("--journal"),
+            _ => None
+        };
+        if let Some(option) = by_line
+        {
+            return Err(ProcessError::ExternalErr(format!("{option} can only be used with \
+                                                          a single transactions file.")));
+        }
+    }
+    Ok(inputs)
+}
+
+// opening balances, and the transactions they can still be
+// disputed by, from the snapshot saved at the end of an earlier run.
+fn read_snapshot(dir : &Option<String>)
+-> Result<Snapshot, ProcessError>
+{
+    match dir
+    {
+        Some(dir) => Snapshot::read_dir(dir).map_err(|err| external_err(dir, err)),
+        None => Ok(Snapshot::default())
+    }
+}
+
+// the rules from the config file, if there is one, with
+// those given as options in place of the file's.
+fn read_config(args : &RunArgs)
+-> Result<EngineConfig, ProcessError>
+{
+    let mut config = match &args.config
+    {
+        Some(path) => EngineConfig::read(path).map_err(|err| external_err(path, err))?,
+        None => EngineConfig::default()
+    };
+    if let Some(disputes) = args.disputes
+    {
+        config.withdrawal_disputes = disputes;
+    }
+    if let Some(lock_policy) = args.lock_policy
+    {
+        config.lock_policy = lock_policy;
+    }
+    config.validate().map_err(|err| ProcessError::ExternalErr(err.to_string()))?;
+    Ok(config)
+}
+
+// every violation the audit found fails the run.
+fn check_violations(violations : &[(u64, Violation)])
+-> Result<(), ProcessError>
+{
+    if violations.is_empty()
+    {
+        return Ok(());
+    }
+    for (line, violation) in violations
+    {
+        match line
+        {
+            0 => eprintln!("at the end of the run, {violation}"),
+            _ => eprintln!("after line {line}, {violation}")
+        }
+    }
+    Err(ProcessError::ExternalErr(format!("the ledger audit found {} violations.",
+                                          violations.len())))
+}
+
+// processes each file as a batch, writing what became of it. As
+// without batches, a file which fails stops the run.
+fn process_batches(engine : &mut Engine,
+                   inputs : &[Input],
+                   policy : &BatchPolicy,
+                   args   : &RunArgs,
+                   sink   : &mut dyn EventSink)
+-> Result<(), ProcessError>
+{
+    let mut report : Box<dyn Write> = match &args.batch_report
+    {
+        Some(path) => Box::new(BufWriter::new(File::create(path)
+                                              .map_err(|err| external_err(path, err))?)),
+        None => Box::new(std::io::stderr().lock())
+    };
+
+    for input in inputs
+    {
+        let batch = engine.process_batch(input, policy, sink);
+        batch.write(args.summary.unwrap_or_default(), &mut report)
+             .map_err(|err| ProcessError::ExternalErr(err.to_string()))?;
+        batch.run.result?;
+    }
+    Ok(())
+}
+
+// process and statement: applies the files, returning what the run did.
+fn process_files(args : &RunArgs)
+-> Result<RunSummary, ProcessError>
+{
+    let config = read_config(args)?;
+    let mut sink = open_sink(args)?;
+    let inputs = open_inputs(args)?;
+    let snapshot = read_snapshot(&args.from_snapshot)?;
+
+    let mut accounts : HashMap<u16, Account> = snapshot.accounts;
+    let mut engine = Engine::with_registry(&mut accounts,
+                                           TxRegistry::from_map(snapshot.tx_history));
+    engine.configure(&config);
+    engine.audit = args.audit;
+    if args.events.is_some()
+    {
+        engine.events = Some(Vec::new());
+    }
+    if args.query.is_some()
     {
-        println!("usage:\n cargo run -- [transactions file] [(OPTIONAL) log file]");
-        process::exit(1);
+        engine.history = Some(BalanceHistory::new());
     }
+    // the summary is always kept, the exit code depends on it.
+    engine.summary = Some(RunSummary::new());
 
-    // setup optional logger
-    let mut logger : Option<Logger> = None;
-    if args.len() == 3
+    // recover whatever an interrupted run left in the journal, the
+    // rows it already applied are skipped when the file is rerun.
+    if let Some(path) = &args.journal
     {
-        logger = Logger::new(&args[2]);
+        let checkpoint = args.checkpoint.clone()
+                         .unwrap_or(format!("{path}.checkpoint"));
+        let recovered = Journal::open(path, &checkpoint).and_then(|journal|
+        {
+            engine.journal = Some(journal);
+            engine.recover()
+        });
+
+        match recovered
+        {
+            Ok(0) => {}
+            Ok(line) => eprintln!("recovered from journal, resuming after line {line}."),
+            Err(err) => return Err(ProcessError::ExternalErr(err.to_string()))
+        }
     }
 
-    // setup csv data
-    let mut data = match csv::Reader::from_path(&args[1])
+    let result = match &args.batch
     {
-        Ok(d)=> d,
-        Err(err)=>
+        Some(policy) => process_batches(&mut engine, &inputs, policy, args, &mut sink),
+        None => engine.process_inputs(&inputs, &mut sink)
+    };
+    sink.flush().map_err(|err| ProcessError::ExternalErr(format!("an event could not be \
+                                                                  recorded: {err}")))?;
+
+    let summary = engine.summary.take().unwrap_or_default();
+    if let Some(format) = args.summary
+    {
+        summary.write(format, &mut std::io::stderr().lock())
+               .map_err(|err| ProcessError::ExternalErr(err.to_string()))?;
+    }
+
+    // a run which failed part way keeps its journal, so that the next
+    // run recovers from it, and saves no snapshot of the partial state.
+    if engine.journal.is_some() && result.is_ok()
+    {
+        engine.checkpoint().map_err(|err| ProcessError::ExternalErr(err.to_string()))?;
+    }
+
+    if let Some(dir) = args.save_snapshot.as_ref().filter(|_| result.is_ok())
+    {
+        let snapshot = Snapshot
         {
-            return 
-            ProcessEvent::ExternalErr(err.to_string())
+            accounts    : engine.accounts.clone(),
+            tx_history  : engine.tx_history.snapshot()
+        };
+        snapshot.write_dir(dir).map_err(|err| external_err(dir, err))?;
+    }
+
+    if let (Some(path), Some(events)) = (&args.events, &engine.events)
+    {
+        File::create(path).and_then(|file| write_events(events, &mut BufWriter::new(file)))
+                          .map_err(|err| external_err(path, err))?;
+    }
+
+    if let Some(path) = &args.trial_balance
+    {
+        File::create(path).and_then(|file|
+                          {
+                              engine.ledger.write_trial_balance(&mut BufWriter::new(file))
+                          })
+                          .map_err(|err| external_err(path, err))?;
+    }
+
+    // the final accounts, or whatever was queried instead.
+    let written = open_output(&args.output).and_then(|mut writer|
+    {
+        let (client, found) = match &args.query
+        {
+            None => return write_accounts(engine.accounts, args.format, &mut writer),
+            Some(Query::Statement(client, lines, format)) =>
+            {
+                // a range of lines is only allowed with a single file.
+                match engine.statement(*client, lines.clone().map(|lines| (0, lines)))
+                {
+                    Some(statement) => return statement.write(*format, &mut writer),
+                    None => (*client, None)
+                }
+            }
+            Some(Query::AfterTx(client, tx)) => (*client, engine.balance_after_tx(*client, *tx)),
+            Some(Query::AsOfLine(client, line)) =>
+            {
+                (*client, engine.balance_as_of_line(*client, 0, *line))
+            }
+        };
+
+        match found
+        {
+            Some(account) =>
+            {
+                write_accounts(&HashMap::from([(client, account)]), args.format, &mut writer)
+            }
+            None => Err(std::io::Error::other(format!("no balance found for client \
+                                                       {client} at that point.")))
         }
+    });
+    written.map_err(|err| ProcessError::ExternalErr(err.to_string()))?;
+
+    check_violations(&engine.violations)?;
+    result.map(|_| summary)
+}
+
+// validate: applies the files to a copy of the opening state which is
+// thrown away, and writes the summary of what would have happened.
+fn validate_files(args : &RunArgs)
+-> Result<RunSummary, ProcessError>
+{
+    let config = read_config(args)?;
+    let mut sink = open_sink(args)?;
+    let inputs = open_inputs(args)?;
+    let snapshot = read_snapshot(&args.from_snapshot)?;
+
+    let mut accounts : HashMap<u16, Account> = snapshot.accounts;
+    let mut engine = Engine::with_registry(&mut accounts,
+                                           TxRegistry::from_map(snapshot.tx_history));
+    engine.configure(&config);
+    engine.audit = args.audit;
+
+    let dry_run = engine.dry_run(&inputs, &mut sink);
+    sink.flush().map_err(|err| ProcessError::ExternalErr(format!("an event could not be \
+                                                                  recorded: {err}")))?;
+    dry_run.write(args.summary.unwrap_or(SummaryFormat::Text), &mut std::io::stdout().lock())
+           .map_err(|err| ProcessError::ExternalErr(err.to_string()))?;
+
+    check_violations(&dry_run.violations)?;
+    dry_run.result.map(|_| dry_run.summary)
+}
+
+// replay: rebuilds the accounts from an event stream.
+fn replay(args : &ReplayArgs)
+-> Result<(), ProcessError>
+{
+    let events = File::open(&args.events).and_then(read_events)
+                                         .map_err(|err| external_err(&args.events, err))?;
+    let snapshot = read_snapshot(&args.from_snapshot)?;
+
+    let mut accounts : HashMap<u16, Account> = snapshot.accounts;
+    let mut engine = Engine::with_registry(&mut accounts,
+                                           TxRegistry::from_map(snapshot.tx_history));
+    engine.replay(&events).map_err(|err| external_err(&args.events, err))?;
+
+    open_output(&args.output).and_then(|mut writer|
+                             {
+                                 write_accounts(engine.accounts, args.format, &mut writer)
+                             })
+                             .map_err(|err| ProcessError::ExternalErr(err.to_string()))
+}
+
+// the accounts in an accounts csv file, or a snapshot directory.
+fn read_accounts_at(path : &str)
+-> Result<HashMap<u16, Account>, ProcessError>
+{
+    let accounts = match Path::new(path).is_dir()
+    {
+        true  => Snapshot::read_dir(path).map(|snapshot| snapshot.accounts),
+        false => File::open(path).and_then(read_accounts)
     };
+    accounts.map_err(|err| external_err(path, err))
+}
+
+// diff: compares two sets of accounts.
+fn diff(args : &DiffArgs)
+-> Result<(), ProcessError>
+{
+    let before = read_accounts_at(&args.before)?;
+    let after = read_accounts_at(&args.after)?;
 
-    let mut accounts : HashMap<u16, Account> = HashMap::new();
-    let mut engine = engine::Engine::new(&mut accounts);
-    engine.process_transactions(&mut data,&mut logger);
-    engine.output();
+    open_output(&args.output).and_then(|mut writer|
+                             {
+                                 write_diff(&diff_accounts(&before, &after), &mut writer)
+                             })
+                             .map_err(|err| ProcessError::ExternalErr(err.to_string()))
+}
 
-    ProcessEvent::ProcessComplete
+// generate: writes a random transactions file.
+fn generate(args : &GenerateArgs)
+-> Result<(), ProcessError>
+{
+    let seed = args.seed.unwrap_or_else(rand::random);
+    open_output(&args.output).and_then(|mut writer|
+                             {
+                                 generate_transactions(args.rows, args.clients, seed, &mut writer)
+                             })
+                             .map_err(|err| ProcessError::ExternalErr(err.to_string()))
 }
 
 fn main()
 {
+    let args : Vec<String> = env::args().collect();
+    let command = match parse_args(&args)
+    {
+        Ok(command) => command,
+        Err(err) =>
+        {
+            eprintln!("error: {err}\n\nrun 'tx_engine --help' for usage.");
+            process::exit(2);
+        }
+    };
+
+    // the exit codes of a run which rejected rows, and of one which failed.
+    let (exit_rejected, exit_failed) = match &command
+    {
+        Command::Process(args) | Command::Validate(args) | Command::Statement(args) =>
+        {
+            (args.exit_rejected, args.exit_failed)
+        }
+        _ => (0, 1)
+    };
 
-    match the_app()
+    let result = match &command
     {
-        ProcessEvent::ProcessComplete =>{}
-        ProcessEvent::ExternalErr(err)=>
+        Command::Help(help) =>
         {
-            println!("App failed: {err}");
-            process::exit(1);
+            // nothing to be done if stdout has gone away, e.g. piped to head.
+            let _ = writeln!(std::io::stdout(), "{help}");
+            return;
+        }
+        Command::Process(args) | Command::Statement(args) => process_files(args).map(Some),
+        Command::Validate(args) => validate_files(args).map(Some),
+        Command::Replay(args) => replay(args).map(|_| None),
+        Command::Diff(args) => diff(args).map(|_| None),
+        Command::Generate(args) => generate(args).map(|_| None)
+    };
+
+    match result
+    {
+        Ok(Some(summary)) if summary.rejected_total() > 0 || summary.rolled_back > 0 =>
+        {
+            process::exit(exit_rejected)
+        }
+        Ok(_) => {}
+        Err(err) =>
+        {
+            eprintln!("App failed: {err}");
+            process::exit(exit_failed);
         }
-        _ =>{}
     }
-}
\ No newline at end of file
+}
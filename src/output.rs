@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::common::Account;
+
+// the formats the final account balances can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat
+{
+    // RFC 4180 csv, with a header row and CRLF line endings.
+    #[default]
+    Csv,
+    // a single JSON array of account objects.
+    Json,
+    // one JSON account object per line.
+    JsonLines
+}
+
+impl OutputFormat
+{
+    // returns None if the name isn't a recognised format.
+    pub fn from_name(name : &str)
+    -> Option<Self>
+    {
+        match name
+        {
+            "csv"               => Some(OutputFormat::Csv),
+            "json"              => Some(OutputFormat::Json),
+            "jsonl" | "ndjson"  => Some(OutputFormat::JsonLines),
+            _                   => None
+        }
+    }
+}
+
+// writes the accounts in the given format, ordered by client id
+// so that the same balances always produce the same output.
+pub fn write_accounts<W>(accounts  : &HashMap<u16, Account>,
+                         format    : OutputFormat,
+                         writer    : &mut W)
+-> std::io::Result<()>
+where W: Write
+{
+    let mut clients : Vec<&u16> = accounts.keys().collect();
+    clients.sort();
+
+    match format
+    {
+        OutputFormat::Csv =>
+        {
+            let mut csv_writer = csv::WriterBuilder::new()
+                                 .terminator(csv::Terminator::CRLF)
+                                 .from_writer(&mut *writer);
+
+            csv_writer.write_record(["client", "available", "held", "total", "locked"])?;
+            for client in clients
+            {
+                let account = &accounts[client];
+                csv_writer.write_record(&[
+                    client.to_string(),
+                    format!("{:.4}", account.available),
+                    format!("{:.4}", account.held),
+                    format!("{:.4}", account.available + account.held),
+                    account.locked.to_string()
+                ])?;
+            }
+            csv_writer.flush()?;
+        }
+        OutputFormat::Json =>
+        {
+            write!(writer, "[")?;
+            for (i, client) in clients.into_iter().enumerate()
+            {
+                if i > 0
+                {
+                    write!(writer, ",")?;
+                }
+                write!(writer, "\n  {}", json_object(*client, &accounts[client]))?;
+            }
+            writeln!(writer, "\n]")?;
+        }
+        OutputFormat::JsonLines =>
+        {
+            for client in clients
+            {
+                writeln!(writer, "{}", json_object(*client, &accounts[client]))?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+// amounts are written as JSON numbers to 4 d.p, the
+// same precision the balances are kept at.
+fn json_object(client : u16, account : &Account)
+-> String
+{
+    format!("{{\"client\":{client},\"available\":{:.4},\"held\":{:.4},\
+            \"total\":{:.4},\"locked\":{}}}",
+            account.available,
+            account.held,
+            account.available + account.held,
+            account.locked)
+}
@@ -0,0 +1,201 @@
+use rust_decimal::prelude::*;
+
+use crate::command::TxCommand;
+use crate::common::{ProcessError, MAX_AMOUNT};
+
+// the kinds of transaction which may appear in the 'type' column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TxKind
+{
+    Deposit,
+    Withdrawal,
+    Dispute,
+    Resolve,
+    Chargeback
+}
+
+impl TxKind
+{
+    // returns None if the name is not a recognised transaction type.
+    pub fn from_name(name : &str)
+    -> Option<Self>
+    {
+        match name
+        {
+            "deposit"       => Some(TxKind::Deposit),
+            "withdrawal"    => Some(TxKind::Withdrawal),
+            "dispute"       => Some(TxKind::Dispute),
+            "resolve"       => Some(TxKind::Resolve),
+            "chargeback"    => Some(TxKind::Chargeback),
+            _               => None
+        }
+    }
+
+    pub fn name(&self)
+    -> &'static str
+    {
+        match self
+        {
+            TxKind::Deposit     => "deposit",
+            TxKind::Withdrawal  => "withdrawal",
+            TxKind::Dispute     => "dispute",
+            TxKind::Resolve     => "resolve",
+            TxKind::Chargeback  => "chargeback"
+        }
+    }
+
+    // only deposits and withdrawals carry an amount, the
+    // others reference the amount of an existing transaction.
+    pub fn has_amount(&self)
+    -> bool
+    {
+        matches!(self, TxKind::Deposit | TxKind::Withdrawal)
+    }
+}
+
+// a single row of the transactions file, parsed into its types.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxRecord
+{
+    pub kind     : TxKind,
+    pub client   : u16,
+    pub tx       : u32,
+    pub amount   : Option<Decimal>
+}
+
+impl TxRecord
+{
+    // parses a row of the transactions file. A row that cannot be
+    // parsed is returned as the event which describes why, so that
+    // the caller can report it and carry on with the next row.
+    //
+    // 'line' is the line number of the row in the file.
+    pub fn parse(entry : &csv::StringRecord, line : u64)
+    -> Result<Self, ProcessError>
+    {
+        // the amount column may be left off entirely for
+        // disputes, resolves and chargebacks.
+        if entry.len() < 3 || entry.len() > 4
+        {
+            return Err(ProcessError::ErrColumnCount(line, raw_text(entry)));
+        }
+
+        let tx_type = entry[0].trim();
+        let kind = match TxKind::from_name(tx_type)
+        {
+            Some(kind) => kind,
+            None =>
+            {
+                return Err(ProcessError::ErrUnrecognisedTx(line,
+                           tx_type.to_string()));
+            }
+        };
+
+        let client = match entry[1].trim().parse::<u16>()
+        {
+            Ok(client) => client,
+            Err(_) => return Err(ProcessError::ErrBadClientId(line, raw_text(entry)))
+        };
+
+        let tx = match entry[2].trim().parse::<u32>()
+        {
+            Ok(tx) => tx,
+            Err(_) => return Err(ProcessError::ErrBadTxId(line, raw_text(entry)))
+        };
+
+        let mut amount = None;
+        if kind.has_amount()
+        {
+            let text = entry.get(3).unwrap_or("").trim();
+            if text.is_empty()
+            {
+                return Err(ProcessError::ErrMissingAmount(line, raw_text(entry)));
+            }
+
+            amount = match Decimal::from_str(text)
+            {
+                Ok(amount) if amount.abs() <= MAX_AMOUNT => Some(amount),
+                Ok(_) => return Err(ProcessError::ErrAmountTooLarge(line, raw_text(entry))),
+                Err(_) => return Err(ProcessError::ErrInvalidAmount(line, raw_text(entry)))
+            };
+        }
+
+        Ok(TxRecord { kind, client, tx, amount })
+    }
+
+    // the command to apply to the engine for this row.
+    pub fn to_command(&self)
+    -> TxCommand
+    {
+        TxCommand::new(self.kind, self.client, self.tx,
+                       self.amount.unwrap_or_default())
+    }
+}
+
+// rebuilds the text of a row as it appeared in the file, so that
+// it can be quoted back in the events describing a rejected row.
+pub fn raw_text(entry : &csv::StringRecord)
+-> String
+{
+    entry.iter().collect::<Vec<&str>>().join(",")
+}
+
+
+// unit tests ////////////////////////////////////////////////////////////////
+//////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod record_parsing
+{
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn row(fields : &[&str])
+    -> csv::StringRecord
+    {
+        csv::StringRecord::from(fields.to_vec())
+    }
+
+    #[test]
+    fn test_parse_deposit()
+    {
+        let record = TxRecord::parse(&row(&["deposit", " 1", " 2", " 3.5"]), 2)
+                     .unwrap();
+
+        assert_eq!(record.kind, TxKind::Deposit);
+        assert_eq!(record.client, 1);
+        assert_eq!(record.tx, 2);
+        assert_eq!(record.amount, Some(dec!(3.5)));
+    }
+
+    #[test]
+    fn test_parse_dispute_without_amount_column()
+    {
+        // the trailing amount column is optional for disputes.
+        let record = TxRecord::parse(&row(&["dispute", "1", "2"]), 2)
+                     .unwrap();
+
+        assert_eq!(record.kind, TxKind::Dispute);
+        assert_eq!(record.amount, None);
+    }
+
+    #[test]
+    fn test_parse_rejects_with_line_and_raw_text()
+    {
+        let result = TxRecord::parse(&row(&["deposit", "1", "2", ""]), 7);
+        assert_eq!(result, Err(ProcessError::ErrMissingAmount(7,
+                               "deposit,1,2,".to_string())));
+
+        let result = TxRecord::parse(&row(&["deposit", "1", "2", "1.2.3"]), 8);
+        assert_eq!(result, Err(ProcessError::ErrInvalidAmount(8,
+                               "deposit,1,2,1.2.3".to_string())));
+
+        let result = TxRecord::parse(&row(&["deposit", "1", "2", "1000000000000000000001"]), 10);
+        assert_eq!(result, Err(ProcessError::ErrAmountTooLarge(10,
+                               "deposit,1,2,1000000000000000000001".to_string())));
+
+        let result = TxRecord::parse(&row(&["deposit", "1"]), 9);
+        assert_eq!(result, Err(ProcessError::ErrColumnCount(9,
+                               "deposit,1".to_string())));
+    }
+}
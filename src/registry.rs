@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use crate::command::TxError;
+use crate::common::Tx;
+
+// a thread safe store of the deposits and withdrawals processed so far.
+// Cloning a registry shares it, so several engines (on as many threads)
+// can use one registry at once: a tx id can only be used once across
+// all of them, and a dispute in one file can reference a deposit or
+// withdrawal processed from another.
+#[derive(Debug, Clone, Default)]
+pub struct TxRegistry
+{
+    txs : Arc<RwLock<HashMap<u32, Tx>>>
+}
+
+impl TxRegistry
+{
+    pub fn new()
+    -> Self
+    {
+        TxRegistry::default()
+    }
+
+    // builds a registry holding the given transactions.
+    pub fn from_map(txs : HashMap<u32, Tx>)
+    -> Self
+    {
+        TxRegistry
+        {
+            txs : Arc::new(RwLock::new(txs))
+        }
+    }
+
+    // returns a copy of the transaction, since the
+    // registry may be modified by another engine.
+    pub fn get(&self, tx_id : &u32)
+    -> Option<Tx>
+    {
+        self.read().get(tx_id).copied()
+    }
+
+    pub fn contains_key(&self, tx_id : &u32)
+    -> bool
+    {
+        self.read().contains_key(tx_id)
+    }
+
+    pub fn len(&self)
+    -> usize
+    {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self)
+    -> bool
+    {
+        self.read().is_empty()
+    }
+
+    // inserts a new transaction. The check for an existing id and
+    // the insert happen under the same lock, so two engines racing
+    // to use the same id cannot both succeed.
+    pub fn insert_new(&self, tx : Tx)
+    -> Result<(), TxError>
+    {
+        self.insert_new_with(tx, || Ok(()))
+    }
+
+    // as insert_new, but runs 'before' once the id is known to be free
+    // and before the transaction is inserted, still holding the lock.
+    // if 'before' fails nothing is inserted.
+    pub fn insert_new_with<F>(&self, tx : Tx, before : F)
+    -> Result<(), TxError>
+    where F: FnOnce() -> Result<(), TxError>
+    {
+        let mut txs = self.write();
+        if txs.contains_key(&tx.id)
+        {
+            return Err(TxError::TxIdExists(tx.id));
+        }
+        before()?;
+        txs.insert(tx.id, tx);
+        Ok(())
+    }
+
+    // runs 'f' on the transaction while holding the lock, so that
+    // checking and changing its state happens in one step.
+    // returns None if there is no transaction with the id.
+    pub fn update<F, T>(&self, tx_id : u32, f : F)
+    -> Option<T>
+    where F: FnOnce(&mut Tx) -> T
+    {
+        self.write().get_mut(&tx_id).map(f)
+    }
+
+    // replaces every transaction in the registry, e.g. when
+    // restoring state saved by a checkpoint.
+    pub fn restore(&self, txs : HashMap<u32, Tx>)
+    {
+        *self.write() = txs;
+    }
+
+    // a copy of every transaction in the registry.
+    pub fn snapshot(&self)
+    -> HashMap<u32, Tx>
+    {
+        self.read().clone()
+    }
+
+    // runs 'f' on every transaction while holding the read lock,
+    // for summaries which shouldn't copy the whole registry.
+    pub fn with_txs<F, T>(&self, f : F)
+    -> T
+    where F: FnOnce(&HashMap<u32, Tx>) -> T
+    {
+        f(&self.read())
+    }
+
+    // a panic on another thread while it held the lock doesn't leave
+    // the map half updated (every change is a single insert or field
+    // write), so a poisoned lock is recovered rather than panicking.
+    fn read(&self)
+    -> std::sync::RwLockReadGuard<'_, HashMap<u32, Tx>>
+    {
+        self.txs.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self)
+    -> std::sync::RwLockWriteGuard<'_, HashMap<u32, Tx>>
+    {
+        self.txs.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
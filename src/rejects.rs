@@ -0,0 +1,86 @@
+use std::io::{self, Write};
+
+use crate::common::{ErrorCategory, ProcessError};
+use crate::sink::EventSink;
+
+// a machine readable report of every row which was refused or couldn't
+// be parsed, written as csv with a row per rejection:
+//
+//   file,line,code,reason,row
+//
+// 'file' is the input the row came from, empty until the report is
+// told (see EventSink::begin_input). 'row' is the row as it appeared
+// in the file, empty for a row which couldn't be read at all.
+// ShardedEngine's workers only see the parsed command, so the rows it
+// refuses are rebuilt from the command.
+//
+// events which aren't about a row, such as audit violations, are left
+// out. Rows are buffered, call flush once the file has been processed
+// to find out whether the report was written.
+pub struct RejectsReport<W>
+where W: Write
+{
+    csv_writer : csv::Writer<W>,
+    input      : String
+}
+
+impl<W> RejectsReport<W>
+where W: Write
+{
+    // starts the report with its header row.
+    pub fn new(writer : W)
+    -> io::Result<Self>
+    {
+        let mut csv_writer = csv::WriterBuilder::new()
+                             .terminator(csv::Terminator::CRLF)
+                             .from_writer(writer);
+        csv_writer.write_record(["file", "line", "code", "reason", "row"])?;
+        Ok(RejectsReport { csv_writer, input : String::new() })
+    }
+
+    // flushes the report and hands back the writer.
+    pub fn into_inner(self)
+    -> io::Result<W>
+    {
+        self.csv_writer.into_inner()
+            .map_err(|err| io::Error::new(err.error().kind(), err.error().to_string()))
+    }
+}
+
+impl<W> EventSink for RejectsReport<W>
+where W: Write
+{
+    fn record(&mut self, line : u64, event : &ProcessError)
+    -> io::Result<()>
+    {
+        self.record_row(line, "", event)
+    }
+
+    fn record_row(&mut self, line : u64, row : &str, event : &ProcessError)
+    -> io::Result<()>
+    {
+        if !matches!(event.category(), ErrorCategory::Row | ErrorCategory::Transaction)
+        {
+            return Ok(());
+        }
+
+        let line = event.line().unwrap_or(line);
+        self.csv_writer.write_record([self.input.as_str(),
+                                      line.to_string().as_str(),
+                                      event.code(),
+                                      event.to_string().as_str(),
+                                      row])?;
+        Ok(())
+    }
+
+    fn begin_input(&mut self, input : &str)
+    {
+        self.input = input.to_string();
+    }
+
+    fn flush(&mut self)
+    -> io::Result<()>
+    {
+        self.csv_writer.flush()
+    }
+}
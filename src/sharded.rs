@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::thread;
+
+use crate::app_process::{keep_first_failure, read_records};
+use crate::command::TxCommand;
+use crate::common::*;
+use crate::config::EngineConfig;
+use crate::engine::Engine;
+use crate::record::raw_text;
+use crate::registry::TxRegistry;
+use crate::sink::EventSink;
+
+// how many commands are sent to a worker at a time,
+// batching keeps the cost of the channel per row low.
+const BATCH_SIZE : usize = 1024;
+
+// processes a transactions file over several worker threads.
+//
+// Work is partitioned by client id: each worker owns the accounts of
+// the clients assigned to it and applies their transactions with its
+// own Engine. Since all of a client's rows go to the same worker, in
+// the order they appear in the file, every account ends up exactly as
+// it would after a single threaded run.
+//
+// Transactions are kept in a TxRegistry shared by the workers, so tx
+// ids stay unique across clients. If two different clients use the
+// same tx id, which of them is refused depends on which worker gets
+// there first, rather than on the order of the rows in the file.
+//
+// A worker only holds the accounts of its own clients, so it can't
+// apply a row to another client's account: references to another
+// client's transaction are always refused (CrossClientPolicy::Refuse).
+pub struct ShardedEngine <'a>
+{
+    pub accounts        : &'a mut HashMap<u16, Account>,
+    pub tx_history      : TxRegistry,
+    pub lock_policy     : LockPolicy,
+    pub dispute_policy  : WithdrawalDisputePolicy,
+    pub rounding_dp     : u32,
+    pub reject_negative : bool,
+    pub shards          : usize,
+}
+
+impl<'a> ShardedEngine <'a>
+{
+    pub fn new ( accounts : &'a mut HashMap<u16, Account>, shards : usize) -> Self
+    {
+        ShardedEngine
+        {
+            accounts,
+            tx_history:     TxRegistry::new(),
+            lock_policy:     LockPolicy::default(),
+            dispute_policy:  WithdrawalDisputePolicy::default(),
+            rounding_dp:     MAX_DP,
+            reject_negative: true,
+            shards:          shards.max(1),
+        }
+    }
+
+    // applies the rules in a config, refusing one which
+    // asks for CrossClientPolicy::Owner.
+    pub fn configure(&mut self, config : &EngineConfig)
+    -> std::io::Result<()>
+    {
+        if config.cross_client != CrossClientPolicy::Refuse
+        {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput,
+                                           "the sharded engine can only refuse \
+                                            cross client references"));
+        }
+        self.lock_policy = config.lock_policy;
+        self.dispute_policy = config.withdrawal_disputes;
+        self.rounding_dp = config.rounding_dp;
+        self.reject_negative = config.reject_negative_amounts;
+        Ok(())
+    }
+
+    fn shard_of(&self, client : u16) -> usize
+    {
+        client as usize % self.shards
+    }
+
+    pub fn process_transactions<R>(&mut self,
+                                data    : &mut csv::Reader<R>,
+                                sink    : &mut dyn EventSink)
+    -> Result<(), ProcessError>
+    where R: std::io::Read
+    {
+        // hand each worker the accounts of its own clients.
+        let mut parts : Vec<HashMap<u16, Account>> = vec![HashMap::new(); self.shards];
+        for (client, account) in self.accounts.drain()
+        {
+            parts[client as usize % self.shards].insert(client, account);
+        }
+
+        // every refused row is tagged with the line it came from, so
+        // they can be reported in file order once workers finish.
+        let mut events : Vec<(u64, String, ProcessError)> = Vec::new();
+        let mut result = Ok(());
+        let mut rows = 0;
+
+        thread::scope(|scope|
+        {
+            let mut senders = Vec::with_capacity(self.shards);
+            let mut workers = Vec::with_capacity(self.shards);
+
+            for part in parts.iter_mut()
+            {
+                let (sender, receiver) = mpsc::channel::<Vec<(u64, TxCommand)>>();
+                let registry = self.tx_history.clone();
+                let config = EngineConfig
+                {
+                    rounding_dp             : self.rounding_dp,
+                    reject_negative_amounts : self.reject_negative,
+                    cross_client            : CrossClientPolicy::Refuse,
+                    withdrawal_disputes     : self.dispute_policy,
+                    lock_policy             : self.lock_policy
+                };
+
+                senders.push(sender);
+                workers.push(scope.spawn(move ||
+                {
+                    let mut engine = Engine::with_registry(part, registry);
+                    engine.configure(&config);
+
+                    let mut rejected = Vec::new();
+                    for batch in receiver
+                    {
+                        for (line, command) in batch
+                        {
+                            // workers only have the command, so the row
+                            // is rebuilt from it.
+                            if let Err(err) = engine.apply(command)
+                            {
+                                rejected.push((line, command.to_string(), ProcessError::from(err)));
+                            }
+                        }
+                    }
+                    rejected
+                }));
+            }
+
+            // rows are read and parsed on this thread, and only the
+            // commands are handed over. Parsing is cheap next to the
+            // cost of allocating a record per row to send to a worker.
+            let mut batches : Vec<Vec<(u64, TxCommand)>> = vec![Vec::new(); self.shards];
+            result = read_records(data, |line, row, record|
+            {
+                rows += 1;
+                match record
+                {
+                    Ok(record) =>
+                    {
+                        let shard = self.shard_of(record.client);
+                        batches[shard].push((line, record.to_command()));
+                        if batches[shard].len() == BATCH_SIZE
+                        {
+                            // a worker only stops receiving once it has
+                            // panicked, which is reported when it's joined.
+                            let _ = senders[shard].send(std::mem::take(&mut batches[shard]));
+                        }
+                    }
+                    Err(event) => events.push((line, row.map_or(String::new(), raw_text), event))
+                }
+            });
+
+            for (sender, batch) in senders.into_iter().zip(batches)
+            {
+                let _ = sender.send(batch);
+            }
+
+            for worker in workers
+            {
+                match worker.join()
+                {
+                    Ok(rejected) => events.extend(rejected),
+                    Err(_) =>
+                    {
+                        result = Err(ProcessError::ExternalErr(
+                                     String::from("a worker thread panicked")));
+                    }
+                }
+            }
+        });
+
+        // merge the workers' accounts back into the shared map.
+        for part in parts
+        {
+            self.accounts.extend(part);
+        }
+
+        events.sort_by_key(|(line, ..)| *line);
+        let mut sink_failure = None;
+        for (line, row, event) in &events
+        {
+            keep_first_failure(&mut sink_failure, sink.record_row(*line, row, event));
+        }
+
+        // the workers only hand back the rows they refused, so the
+        // applied rows aren't noted one by one.
+        if result.is_ok()
+        {
+            let complete = RunNote::InputComplete { rows, refused : events.len() as u64 };
+            keep_first_failure(&mut sink_failure, sink.note(0, &complete));
+        }
+
+        if let (Ok(()), Some(err)) = (&result, sink_failure)
+        {
+            result = Err(ProcessError::ExternalErr(format!("an event could not be \
+                                                            recorded: {err}")));
+        }
+
+        result
+    }
+}
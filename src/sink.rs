@@ -0,0 +1,415 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::common::{LogLevel, ProcessError, RunNote};
+
+// where the engine reports the events of a run: rows it refused,
+// audit violations and the like. 'line' is the line of the
+// transactions file the event came from, 0 if it didn't come from a
+// row (or carries its own line, see ProcessError::line).
+//
+// a sink which fails to record an event returns the error, the run
+// carries on and reports the first failure once it's complete.
+pub trait EventSink
+{
+    fn record(&mut self, line : u64, event : &ProcessError)
+    -> io::Result<()>;
+
+    // as record, for a row of the transactions file which was refused,
+    // along with the row's text. Sinks which don't need the row can
+    // leave this to record.
+    fn record_row(&mut self, line : u64, row : &str, event : &ProcessError)
+    -> io::Result<()>
+    {
+        let _ = row;
+        self.record(line, event)
+    }
+
+    // records what happened in a run other than an error, e.g. a row
+    // being applied. Sinks which only keep errors can leave this out.
+    fn note(&mut self, line : u64, note : &RunNote)
+    -> io::Result<()>
+    {
+        let _ = (line, note);
+        Ok(())
+    }
+
+    // called before the rows of each input are processed, with the
+    // input's name ('-' for stdin). Events recorded afterwards are from
+    // that input. Sinks which don't say where an event came from can
+    // leave this out.
+    fn begin_input(&mut self, input : &str)
+    {
+        let _ = input;
+    }
+
+    // writes out any events the sink has buffered, call once the run
+    // is complete to find out whether they were recorded.
+    fn flush(&mut self)
+    -> io::Result<()>
+    {
+        Ok(())
+    }
+}
+
+impl<S> EventSink for &mut S
+where S: EventSink + ?Sized
+{
+    fn record(&mut self, line : u64, event : &ProcessError)
+    -> io::Result<()>
+    {
+        (**self).record(line, event)
+    }
+
+    fn record_row(&mut self, line : u64, row : &str, event : &ProcessError)
+    -> io::Result<()>
+    {
+        (**self).record_row(line, row, event)
+    }
+
+    fn note(&mut self, line : u64, note : &RunNote)
+    -> io::Result<()>
+    {
+        (**self).note(line, note)
+    }
+
+    fn begin_input(&mut self, input : &str)
+    {
+        (**self).begin_input(input)
+    }
+
+    fn flush(&mut self)
+    -> io::Result<()>
+    {
+        (**self).flush()
+    }
+}
+
+impl<S> EventSink for Box<S>
+where S: EventSink + ?Sized
+{
+    fn record(&mut self, line : u64, event : &ProcessError)
+    -> io::Result<()>
+    {
+        (**self).record(line, event)
+    }
+
+    fn record_row(&mut self, line : u64, row : &str, event : &ProcessError)
+    -> io::Result<()>
+    {
+        (**self).record_row(line, row, event)
+    }
+
+    fn note(&mut self, line : u64, note : &RunNote)
+    -> io::Result<()>
+    {
+        (**self).note(line, note)
+    }
+
+    fn begin_input(&mut self, input : &str)
+    {
+        (**self).begin_input(input)
+    }
+
+    fn flush(&mut self)
+    -> io::Result<()>
+    {
+        (**self).flush()
+    }
+}
+
+// discards every event.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullSink;
+
+impl EventSink for NullSink
+{
+    fn record(&mut self, _ : u64, _ : &ProcessError)
+    -> io::Result<()>
+    {
+        Ok(())
+    }
+}
+
+// writes events to a file, one json object per line:
+//
+//   {"timestamp":"...","level":"warn","code":"tx_not_found","number":201,
+//    "client":null,"tx":7,"line":12,"file":"monday.csv","message":"..."}
+//
+// client, tx and line are null when the event doesn't have one, file
+// is null until the sink is told which input the events are from.
+pub struct Logger
+{
+    pub log_file    : std::fs::File,
+    pub min_level   : LogLevel,
+    pub input       : Option<String>
+}
+
+impl Logger
+{
+    // opens the log file for appending, creating it if need be.
+    pub fn new(path : &Path)
+    -> io::Result<Self>
+    {
+        let file = OpenOptions::new().append(true)
+                                     .create(true)
+                                     .open(path)?;
+        Ok(Logger
+        {
+            log_file    : file,
+            min_level   : LogLevel::default(),
+            input       : None
+        })
+    }
+}
+
+impl EventSink for Logger
+{
+    // events below the minimum level are skipped.
+    fn record(&mut self, line : u64, event : &ProcessError)
+    -> io::Result<()>
+    {
+        if event.level() < self.min_level
+        {
+            return Ok(());
+        }
+        writeln!(self.log_file, "{}", json_entry(&chrono::Local::now(),
+                                                 self.input.as_deref(), line, event))
+    }
+
+    fn note(&mut self, line : u64, note : &RunNote)
+    -> io::Result<()>
+    {
+        if note.level() < self.min_level
+        {
+            return Ok(());
+        }
+        writeln!(self.log_file, "{}", json_note(&chrono::Local::now(),
+                                                self.input.as_deref(), line, note))
+    }
+
+    fn begin_input(&mut self, input : &str)
+    {
+        self.input = Some(input.to_string());
+    }
+}
+
+// writes events to stderr, in the same format as Logger.
+#[derive(Debug, Clone, Default)]
+pub struct StderrSink
+{
+    pub min_level   : LogLevel,
+    pub input       : Option<String>
+}
+
+impl EventSink for StderrSink
+{
+    fn record(&mut self, line : u64, event : &ProcessError)
+    -> io::Result<()>
+    {
+        if event.level() < self.min_level
+        {
+            return Ok(());
+        }
+        writeln!(io::stderr().lock(), "{}", json_entry(&chrono::Local::now(),
+                                                       self.input.as_deref(), line, event))
+    }
+
+    fn note(&mut self, line : u64, note : &RunNote)
+    -> io::Result<()>
+    {
+        if note.level() < self.min_level
+        {
+            return Ok(());
+        }
+        writeln!(io::stderr().lock(), "{}", json_note(&chrono::Local::now(),
+                                                      self.input.as_deref(), line, note))
+    }
+
+    fn begin_input(&mut self, input : &str)
+    {
+        self.input = Some(input.to_string());
+    }
+}
+
+// keeps every event in memory, in the order they were recorded.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySink
+{
+    pub events : Vec<(u64, ProcessError)>
+}
+
+impl MemorySink
+{
+    pub fn new()
+    -> Self
+    {
+        MemorySink::default()
+    }
+
+    // the last event recorded, None if there hasn't been one.
+    pub fn last_entry(&self)
+    -> Option<ProcessError>
+    {
+        self.events.last().map(|(_, event)| event.clone())
+    }
+}
+
+impl EventSink for MemorySink
+{
+    fn record(&mut self, line : u64, event : &ProcessError)
+    -> io::Result<()>
+    {
+        self.events.push((line, event.clone()));
+        Ok(())
+    }
+}
+
+// hands every event to each of several sinks. A sink which fails
+// doesn't stop the others getting the event, the first failure is
+// returned.
+#[derive(Default)]
+pub struct FanOut<'a>
+{
+    pub sinks : Vec<Box<dyn EventSink + 'a>>
+}
+
+impl<'a> FanOut<'a>
+{
+    pub fn new(sinks : Vec<Box<dyn EventSink + 'a>>)
+    -> Self
+    {
+        FanOut { sinks }
+    }
+}
+
+impl<'a> EventSink for FanOut<'a>
+{
+    fn record(&mut self, line : u64, event : &ProcessError)
+    -> io::Result<()>
+    {
+        let mut recorded = Ok(());
+        for sink in &mut self.sinks
+        {
+            recorded = recorded.and(sink.record(line, event));
+        }
+        recorded
+    }
+
+    fn record_row(&mut self, line : u64, row : &str, event : &ProcessError)
+    -> io::Result<()>
+    {
+        let mut recorded = Ok(());
+        for sink in &mut self.sinks
+        {
+            recorded = recorded.and(sink.record_row(line, row, event));
+        }
+        recorded
+    }
+
+    fn note(&mut self, line : u64, note : &RunNote)
+    -> io::Result<()>
+    {
+        let mut recorded = Ok(());
+        for sink in &mut self.sinks
+        {
+            recorded = recorded.and(sink.note(line, note));
+        }
+        recorded
+    }
+
+    fn begin_input(&mut self, input : &str)
+    {
+        for sink in &mut self.sinks
+        {
+            sink.begin_input(input);
+        }
+    }
+
+    fn flush(&mut self)
+    -> io::Result<()>
+    {
+        let mut flushed = Ok(());
+        for sink in &mut self.sinks
+        {
+            flushed = flushed.and(sink.flush());
+        }
+        flushed
+    }
+}
+
+// the event as a single line json object, see Logger. Events are
+// stamped with the time they're recorded by the sink, 'input' is the
+// file they came from if the sink knows it.
+pub fn json_entry<Tz>(timestamp : &chrono::DateTime<Tz>,
+                      input     : Option<&str>,
+                      line      : u64,
+                      event     : &ProcessError)
+-> String
+where Tz: chrono::TimeZone,
+      Tz::Offset: std::fmt::Display
+{
+    let line = event.line().or(Some(line).filter(|line| *line != 0));
+    let null_or = |value : Option<String>| value.unwrap_or_else(|| String::from("null"));
+
+    format!("{{\"timestamp\":{},\"level\":\"{}\",\"code\":\"{}\",\"number\":{},\
+             \"client\":{},\"tx\":{},\"line\":{},\"file\":{},\"message\":{}}}",
+            json_string(&timestamp.to_rfc3339()),
+            event.level().name(),
+            event.code(),
+            event.number(),
+            null_or(event.client().map(|client| client.to_string())),
+            null_or(event.tx().map(|tx| tx.to_string())),
+            null_or(line.map(|line| line.to_string())),
+            null_or(input.map(json_string)),
+            json_string(&event.to_string()))
+}
+
+// a note as a single line json object, in the same format as an event.
+// Notes have no number, so it's null.
+pub fn json_note<Tz>(timestamp : &chrono::DateTime<Tz>,
+                     input     : Option<&str>,
+                     line      : u64,
+                     note      : &RunNote)
+-> String
+where Tz: chrono::TimeZone,
+      Tz::Offset: std::fmt::Display
+{
+    let line = Some(line).filter(|line| *line != 0);
+    let null_or = |value : Option<String>| value.unwrap_or_else(|| String::from("null"));
+
+    format!("{{\"timestamp\":{},\"level\":\"{}\",\"code\":\"{}\",\"number\":null,\
+             \"client\":{},\"tx\":{},\"line\":{},\"file\":{},\"message\":{}}}",
+            json_string(&timestamp.to_rfc3339()),
+            note.level().name(),
+            note.code(),
+            null_or(note.client().map(|client| client.to_string())),
+            null_or(note.tx().map(|tx| tx.to_string())),
+            null_or(line.map(|line| line.to_string())),
+            null_or(input.map(json_string)),
+            json_string(&note.to_string()))
+}
+
+// a json string literal holding 'text'.
+pub(crate) fn json_string(text : &str)
+-> String
+{
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars()
+    {
+        match c
+        {
+            '"'                 => quoted.push_str("\\\""),
+            '\\'                => quoted.push_str("\\\\"),
+            '\n'                => quoted.push_str("\\n"),
+            '\r'                => quoted.push_str("\\r"),
+            '\t'                => quoted.push_str("\\t"),
+            c if c < ' '        => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c                   => quoted.push(c)
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use rust_decimal::prelude::*;
+
+use crate::common::*;
+use crate::journal::stored_kind;
+use crate::output::{write_accounts, OutputFormat};
+
+// the names of the files making up a snapshot directory.
+pub const ACCOUNTS_FILE : &str = "accounts.csv";
+pub const HISTORY_FILE  : &str = "tx_history.csv";
+
+// the state at the end of a run, enough to start the next run from:
+// the accounts, and every deposit and withdrawal so far so that later
+// files can dispute them and can't reuse their tx ids.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot
+{
+    pub accounts    : HashMap<u16, Account>,
+    pub tx_history  : HashMap<u32, Tx>
+}
+
+impl Snapshot
+{
+    // reads a snapshot directory. The tx history file is optional,
+    // so an accounts file on its own can give the opening balances.
+    pub fn read_dir<P>(dir : P)
+    -> io::Result<Self>
+    where P: AsRef<Path>
+    {
+        let dir = dir.as_ref();
+        let accounts = read_accounts(File::open(dir.join(ACCOUNTS_FILE))?)?;
+
+        let tx_history = match File::open(dir.join(HISTORY_FILE))
+        {
+            Ok(file) => read_tx_history(file)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => return Err(err)
+        };
+
+        Ok(Snapshot { accounts, tx_history })
+    }
+
+    // writes the snapshot to a directory, creating it if needed.
+    pub fn write_dir<P>(&self, dir : P)
+    -> io::Result<()>
+    where P: AsRef<Path>
+    {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        let mut accounts = BufWriter::new(File::create(dir.join(ACCOUNTS_FILE))?);
+        write_accounts(&self.accounts, OutputFormat::Csv, &mut accounts)?;
+
+        let mut history = BufWriter::new(File::create(dir.join(HISTORY_FILE))?);
+        write_tx_history(&self.tx_history, &mut history)
+    }
+}
+
+// reads accounts in the csv shape written by the output module:
+//
+//   client,available,held,total,locked
+//
+// whitespace around fields is ignored, so the padded files written by
+// earlier versions are read too. A total which isn't available + held
+// means the file has been edited or corrupted, and is refused, as is a
+// balance beyond MAX_AMOUNT, which the engine never writes.
+pub fn read_accounts<R>(reader : R)
+-> io::Result<HashMap<u16, Account>>
+where R: Read
+{
+    let mut data = csv::ReaderBuilder::new()
+                   .trim(csv::Trim::All)
+                   .from_reader(BufReader::new(reader));
+
+    let mut accounts = HashMap::new();
+    for result in data.records()
+    {
+        let entry = result.map_err(io::Error::other)?;
+        let line = entry.position().map_or(0, |pos| pos.line());
+        let invalid = || invalid_data(format!("accounts line {line} is invalid: '{}'",
+                                              entry.iter().collect::<Vec<_>>().join(",")));
+
+        if entry.len() != 5
+        {
+            return Err(invalid());
+        }
+
+        let client = entry[0].parse::<u16>().map_err(|_| invalid())?;
+        let mut account = Account::new();
+        account.available = Decimal::from_str(&entry[1]).map_err(|_| invalid())?;
+        account.held = Decimal::from_str(&entry[2]).map_err(|_| invalid())?;
+        account.locked = entry[4].parse::<bool>().map_err(|_| invalid())?;
+
+        let total = Decimal::from_str(&entry[3]).map_err(|_| invalid())?;
+        if total != account.available.checked_add(account.held).ok_or_else(invalid)?
+        || [account.available, account.held, total].iter().any(|b| b.abs() > MAX_AMOUNT)
+        {
+            return Err(invalid());
+        }
+
+        if accounts.insert(client, account).is_some()
+        {
+            return Err(invalid_data(format!("accounts line {line}: client {client} \
+                                             appears more than once")));
+        }
+    }
+    Ok(accounts)
+}
+
+// reads the tx history written by write_tx_history.
+pub fn read_tx_history<R>(reader : R)
+-> io::Result<HashMap<u32, Tx>>
+where R: Read
+{
+    let mut data = csv::ReaderBuilder::new()
+                   .trim(csv::Trim::All)
+                   .from_reader(BufReader::new(reader));
+
+    let mut txs = HashMap::new();
+    for result in data.records()
+    {
+        let entry = result.map_err(io::Error::other)?;
+        let line = entry.position().map_or(0, |pos| pos.line());
+        let invalid = || invalid_data(format!("tx history line {line} is invalid: '{}'",
+                                              entry.iter().collect::<Vec<_>>().join(",")));
+
+        if entry.len() != 5
+        {
+            return Err(invalid());
+        }
+
+        let tx = Tx::new(entry[0].parse::<u32>().map_err(|_| invalid())?,
+                         entry[1].parse::<u16>().map_err(|_| invalid())?,
+                         stored_kind(&entry[2]).ok_or_else(invalid)?,
+                         Decimal::from_str(&entry[3]).map_err(|_| invalid())?,
+                         TxState::from_name(&entry[4]).ok_or_else(invalid)?);
+
+        if txs.insert(tx.id, tx).is_some()
+        {
+            return Err(invalid_data(format!("tx history line {line}: tx {} \
+                                             appears more than once", tx.id)));
+        }
+    }
+    Ok(txs)
+}
+
+// writes the tx history as csv, ordered by tx id:
+//
+//   tx,client,type,amount,state
+pub fn write_tx_history<W>(txs : &HashMap<u32, Tx>, writer : &mut W)
+-> io::Result<()>
+where W: Write
+{
+    let mut ids : Vec<&u32> = txs.keys().collect();
+    ids.sort();
+
+    let mut csv_writer = csv::WriterBuilder::new()
+                         .terminator(csv::Terminator::CRLF)
+                         .from_writer(&mut *writer);
+
+    csv_writer.write_record(["tx", "client", "type", "amount", "state"])?;
+    for id in ids
+    {
+        let tx = &txs[id];
+        csv_writer.write_record(&[
+            id.to_string(),
+            tx.client.to_string(),
+            tx.kind.name().to_string(),
+            tx.amount.to_string(),
+            tx.state.name().to_string()
+        ])?;
+    }
+    csv_writer.flush()?;
+    drop(csv_writer);
+    writer.flush()
+}
+
+fn invalid_data(message : String)
+-> io::Error
+{
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
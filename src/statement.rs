@@ -0,0 +1,201 @@
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+
+use crate::common::Account;
+use crate::engine::Engine;
+use crate::history::{BalanceHistory, BalancePoint};
+
+// the formats a statement can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StatementFormat
+{
+    // RFC 4180 csv, with a header row and CRLF line endings.
+    #[default]
+    Csv,
+    // aligned columns, for sending to the client.
+    Text
+}
+
+impl StatementFormat
+{
+    pub fn from_name(name : &str)
+    -> Option<Self>
+    {
+        match name
+        {
+            "csv"   => Some(StatementFormat::Csv),
+            "text"  => Some(StatementFormat::Text),
+            _       => None
+        }
+    }
+}
+
+// every deposit, withdrawal, dispute, resolve and chargeback applied to
+// a client's account, over a range of lines or the whole history, with
+// the running balances.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement
+{
+    pub client  : u16,
+    // the input (see BalancePoint::input) and the lines of it
+    // the statement covers, None for the whole history.
+    pub lines   : Option<(usize, RangeInclusive<u64>)>,
+    // the account before the first line of the range.
+    pub opening : Account,
+    pub rows    : Vec<BalancePoint>
+}
+
+impl Statement
+{
+    // the client's statement from the balance history, None
+    // if nothing was ever applied to the client.
+    pub fn new(history  : &BalanceHistory,
+               client   : u16,
+               lines    : Option<(usize, RangeInclusive<u64>)>)
+    -> Option<Self>
+    {
+        // the account before the first line of the range.
+        let points = history.points(client);
+        let before = match &lines
+        {
+            Some((input, range)) =>
+            {
+                points.partition_point(|point| (point.input, point.line) < (*input, *range.start()))
+            }
+            None => 0
+        };
+        let opening = match before
+        {
+            0 => history.opening(client)?.clone(),
+            n => points[n - 1].account.clone()
+        };
+
+        let rows = points.iter()
+                         .filter(|point| match &lines
+                         {
+                             Some((input, range)) =>
+                             {
+                                 point.input == *input && range.contains(&point.line)
+                             }
+                             None => true
+                         })
+                         .cloned()
+                         .collect();
+
+        Some(Statement { client, lines, opening, rows })
+    }
+
+    // the account after the last line of the range.
+    pub fn closing(&self)
+    -> &Account
+    {
+        self.rows.last().map_or(&self.opening, |row| &row.account)
+    }
+
+    pub fn write<W>(&self, format : StatementFormat, writer : &mut W)
+    -> io::Result<()>
+    where W: Write
+    {
+        match format
+        {
+            StatementFormat::Csv => self.write_csv(writer),
+            StatementFormat::Text => self.write_text(writer)
+        }
+    }
+
+    // one row per command, between an opening and a closing balance row:
+    //
+    //   line,type,tx,amount,available,held,total,locked
+    fn write_csv<W>(&self, writer : &mut W)
+    -> io::Result<()>
+    where W: Write
+    {
+        let mut csv_writer = csv::WriterBuilder::new()
+                             .terminator(csv::Terminator::CRLF)
+                             .from_writer(&mut *writer);
+
+        csv_writer.write_record(["line", "type", "tx", "amount",
+                                 "available", "held", "total", "locked"])?;
+
+        let balances = |account : &Account| [
+            format!("{:.4}", account.available),
+            format!("{:.4}", account.held),
+            format!("{:.4}", account.available + account.held),
+            account.locked.to_string()
+        ];
+
+        let mut record = vec![String::new(), String::from("opening balance"),
+                              String::new(), String::new()];
+        record.extend(balances(&self.opening));
+        csv_writer.write_record(&record)?;
+
+        for row in &self.rows
+        {
+            let mut record = vec![row.line.to_string(),
+                                  row.command.kind().name().to_string(),
+                                  row.command.tx().to_string(),
+                                  format!("{:.4}", row.amount)];
+            record.extend(balances(&row.account));
+            csv_writer.write_record(&record)?;
+        }
+
+        let mut record = vec![String::new(), String::from("closing balance"),
+                              String::new(), String::new()];
+        record.extend(balances(self.closing()));
+        csv_writer.write_record(&record)?;
+
+        csv_writer.flush()?;
+        drop(csv_writer);
+        writer.flush()
+    }
+
+    fn write_text<W>(&self, writer : &mut W)
+    -> io::Result<()>
+    where W: Write
+    {
+        match &self.lines
+        {
+            Some((_, range)) => writeln!(writer, "Statement for client {}, lines {} to {}\n",
+                                         self.client, range.start(), range.end())?,
+            None => writeln!(writer, "Statement for client {}, all lines\n", self.client)?
+        }
+        writeln!(writer, "{:>6}  {:<16}{:>10}{:>14}{:>14}{:>14}{:>14}",
+                 "line", "type", "tx", "amount", "available", "held", "total")?;
+
+        let balances = |account : &Account| format!("{:>14}{:>14}{:>14}",
+                                                    format!("{:.4}", account.available),
+                                                    format!("{:.4}", account.held),
+                                                    format!("{:.4}", account.available
+                                                                     + account.held));
+
+        writeln!(writer, "{:>6}  {:<16}{:>10}{:>14}{}",
+                 "", "opening balance", "", "", balances(&self.opening))?;
+        for row in &self.rows
+        {
+            writeln!(writer, "{:>6}  {:<16}{:>10}{:>14}{}",
+                     row.line, row.command.kind().name(), row.command.tx(),
+                     format!("{:.4}", row.amount), balances(&row.account))?;
+        }
+        writeln!(writer, "{:>6}  {:<16}{:>10}{:>14}{}",
+                 "", "closing balance", "", "", balances(self.closing()))?;
+
+        if self.closing().locked
+        {
+            writeln!(writer, "\nThe account is locked.")?;
+        }
+        writer.flush()
+    }
+}
+
+impl<'a> Engine<'a>
+{
+    // the client's statement over a range of lines of an input, or the
+    // whole history. None if the engine isn't keeping a history, or
+    // nothing was ever applied to the client.
+    pub fn statement(&self, client : u16, lines : Option<(usize, RangeInclusive<u64>)>)
+    -> Option<Statement>
+    {
+        Statement::new(self.history.as_ref()?, client, lines)
+    }
+}
+
@@ -0,0 +1,236 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::command::TxOutcome;
+use crate::common::ProcessError;
+use crate::config::EngineConfig;
+use crate::engine::Engine;
+
+// the formats a run summary can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SummaryFormat
+{
+    // a few lines for a person to read.
+    #[default]
+    Text,
+    // a single json object.
+    Json
+}
+
+impl SummaryFormat
+{
+    pub fn from_name(name : &str)
+    -> Option<Self>
+    {
+        match name
+        {
+            "text"  => Some(SummaryFormat::Text),
+            "json"  => Some(SummaryFormat::Json),
+            _       => None
+        }
+    }
+}
+
+// what happened over a run, counted as the rows are processed. Set
+// Engine::summary to count; it adds up over several files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunSummary
+{
+    // rows read, not counting those skipped after recovering
+    // from a journal.
+    pub rows        : u64,
+    // rows applied, by transaction type.
+    pub accepted    : BTreeMap<&'static str, u64>,
+    // rows refused or which couldn't be parsed, by error code.
+    pub rejected    : BTreeMap<&'static str, u64>,
+    // rows in batches which were rolled back, see batch.rs. These
+    // aren't counted as accepted or rejected.
+    pub rolled_back : u64,
+    // the clients the rows which could be parsed were for.
+    pub clients     : HashSet<u16>,
+    // accounts locked at the end of the run, including any
+    // which were locked before it.
+    pub locked      : usize,
+    // invariant violations found by the audit.
+    pub violations  : usize,
+    // time spent processing rows.
+    pub elapsed     : Duration,
+    // the rules the rows were processed under, for the record.
+    pub config      : Option<EngineConfig>
+}
+
+impl RunSummary
+{
+    pub fn new()
+    -> Self
+    {
+        RunSummary::default()
+    }
+
+    pub fn accepted_total(&self)
+    -> u64
+    {
+        self.accepted.values().sum()
+    }
+
+    pub fn rejected_total(&self)
+    -> u64
+    {
+        self.rejected.values().sum()
+    }
+
+    // adds the counts of a run made separately, such as a batch
+    // staged against a copy of the engine's accounts.
+    pub fn add(&mut self, other : &RunSummary)
+    {
+        self.rows += other.rows;
+        for (code, n) in &other.accepted
+        {
+            *self.accepted.entry(code).or_default() += n;
+        }
+        for (code, n) in &other.rejected
+        {
+            *self.rejected.entry(code).or_default() += n;
+        }
+        self.rolled_back += other.rolled_back;
+        self.clients.extend(&other.clients);
+        self.locked = other.locked;
+        self.violations += other.violations;
+        self.elapsed += other.elapsed;
+        self.config = other.config.or(self.config);
+    }
+
+    // rows processed per second, 0 if no time has passed.
+    pub fn rows_per_second(&self)
+    -> f64
+    {
+        match self.elapsed.as_secs_f64()
+        {
+            secs if secs > 0.0 => self.rows as f64 / secs,
+            _ => 0.0
+        }
+    }
+
+    pub fn write<W>(&self, format : SummaryFormat, writer : &mut W)
+    -> io::Result<()>
+    where W: Write
+    {
+        match format
+        {
+            SummaryFormat::Text => self.write_text(writer),
+            SummaryFormat::Json => self.write_json(writer)
+        }
+    }
+
+    fn write_text<W>(&self, writer : &mut W)
+    -> io::Result<()>
+    where W: Write
+    {
+        let counts = |counts : &BTreeMap<&str, u64>| counts.iter()
+                                                          .map(|(name, n)| format!("{name} {n}"))
+                                                          .collect::<Vec<_>>()
+                                                          .join(", ");
+
+        writeln!(writer, "rows read:       {}", self.rows)?;
+        writeln!(writer, "accepted:        {} ({})", self.accepted_total(), counts(&self.accepted))?;
+        writeln!(writer, "rejected:        {} ({})", self.rejected_total(), counts(&self.rejected))?;
+        writeln!(writer, "rolled back:     {}", self.rolled_back)?;
+        writeln!(writer, "clients touched: {}", self.clients.len())?;
+        writeln!(writer, "accounts locked: {}", self.locked)?;
+        writeln!(writer, "violations:      {}", self.violations)?;
+        writeln!(writer, "elapsed:         {:.3}s ({:.0} rows/s)",
+                 self.elapsed.as_secs_f64(), self.rows_per_second())?;
+        if let Some(config) = &self.config
+        {
+            writeln!(writer, "config:          rounding_dp {}, reject_negative_amounts {}, \
+                              cross_client {}, withdrawal_disputes {}, lock_policy {}",
+                     config.rounding_dp,
+                     config.reject_negative_amounts,
+                     config.cross_client.name(),
+                     config.withdrawal_disputes.name(),
+                     config.lock_policy.name())?;
+        }
+        writer.flush()
+    }
+
+    // {"rows":13,"accepted":{"deposit":5,...},"accepted_total":9,
+    //  "rejected":{"tx_not_found":2,...},"rejected_total":4,"rolled_back":0,"clients":3,
+    //  "locked":1,"violations":0,"elapsed_secs":0.002,"rows_per_second":6500.0,
+    //  "config":{"rounding_dp":4,...}}
+    fn write_json<W>(&self, writer : &mut W)
+    -> io::Result<()>
+    where W: Write
+    {
+        let counts = |counts : &BTreeMap<&str, u64>| counts.iter()
+                                                          .map(|(name, n)| format!("\"{name}\":{n}"))
+                                                          .collect::<Vec<_>>()
+                                                          .join(",");
+
+        let config = match &self.config
+        {
+            Some(config) => format!("{{\"rounding_dp\":{},\"reject_negative_amounts\":{},\
+                                     \"cross_client\":\"{}\",\"withdrawal_disputes\":\"{}\",\
+                                     \"lock_policy\":\"{}\"}}",
+                                    config.rounding_dp,
+                                    config.reject_negative_amounts,
+                                    config.cross_client.name(),
+                                    config.withdrawal_disputes.name(),
+                                    config.lock_policy.name()),
+            None => String::from("null")
+        };
+
+        writeln!(writer, "{{\"rows\":{},\"accepted\":{{{}}},\"accepted_total\":{},\
+                          \"rejected\":{{{}}},\"rejected_total\":{},\"rolled_back\":{},\"clients\":{},\
+                          \"locked\":{},\"violations\":{},\"elapsed_secs\":{:.6},\
+                          \"rows_per_second\":{:.1},\"config\":{}}}",
+                 self.rows,
+                 counts(&self.accepted), self.accepted_total(),
+                 counts(&self.rejected), self.rejected_total(),
+                 self.rolled_back,
+                 self.clients.len(),
+                 self.locked,
+                 self.violations,
+                 self.elapsed.as_secs_f64(),
+                 self.rows_per_second(),
+                 config)?;
+        writer.flush()
+    }
+}
+
+impl<'a> Engine<'a>
+{
+    // counts a row processed, if the engine is keeping a summary.
+    // 'client' is None for a row which couldn't be parsed.
+    pub(crate) fn count_row(&mut self,
+                            client  : Option<u16>,
+                            result  : &Result<TxOutcome, ProcessError>)
+    {
+        if let Some(summary) = &mut self.summary
+        {
+            summary.rows += 1;
+            summary.clients.extend(client);
+            match result
+            {
+                Ok(outcome) =>
+                {
+                    *summary.accepted.entry(outcome.command.kind().name()).or_default() += 1;
+                }
+                Err(err) => *summary.rejected.entry(err.code()).or_default() += 1
+            }
+        }
+    }
+
+    // the counts taken once the rows have been processed.
+    pub(crate) fn close_summary(&mut self, elapsed : Duration)
+    {
+        let config = self.config();
+        if let Some(summary) = &mut self.summary
+        {
+            summary.locked = self.accounts.values().filter(|account| account.locked).count();
+            summary.violations = self.violations.len();
+            summary.elapsed += elapsed;
+            summary.config = Some(config);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+// tests applying single transactions without going through csv
+use tx_engine::command::*;
+use tx_engine::common::*;
+use tx_engine::sink::NullSink;
+use std::collections::HashMap;
+use rust_decimal_macros::dec;
+
+#[test]
+fn apply_deposit_and_withdrawal()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+
+    engine.apply(TxCommand::Deposit { client: 1, tx: 1, amount: dec!(5.0) })
+          .unwrap();
+    let outcome = engine.apply(TxCommand::Withdrawal { client: 1, tx: 2, amount: dec!(1.5) })
+                        .unwrap();
+
+    // the outcome carries the account as it is after the command.
+    assert_eq!(outcome.amount, dec!(1.5));
+    assert_eq!(outcome.account.available, dec!(3.5));
+    assert_eq!(outcome.account.held, dec!(0.0));
+}
+
+#[test]
+fn apply_dispute_uses_referenced_amount()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+
+    engine.apply(TxCommand::Deposit { client: 1, tx: 1, amount: dec!(5.0) })
+          .unwrap();
+    let outcome = engine.apply(TxCommand::Dispute { client: 1, tx: 1 })
+                        .unwrap();
+
+    assert_eq!(outcome.amount, dec!(5.0));
+    assert_eq!(outcome.account.available, dec!(0.0));
+    assert_eq!(outcome.account.held, dec!(5.0));
+    assert_eq!(engine.tx_state(1), Some(TxState::Disputed));
+}
+
+#[test]
+fn apply_refused_leaves_state_unchanged()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+
+    engine.apply(TxCommand::Deposit { client: 1, tx: 1, amount: dec!(5.0) })
+          .unwrap();
+
+    let result = engine.apply(TxCommand::Withdrawal { client: 1, tx: 2, amount: dec!(6.0) });
+    assert_eq!(result.unwrap_err(), TxError::InsufficientFunds(1, 2));
+
+    let result = engine.apply(TxCommand::Resolve { client: 1, tx: 1 });
+    assert_eq!(result.unwrap_err(), TxError::TxNotDisputed(1));
+
+    let result = engine.apply(TxCommand::Chargeback { client: 2, tx: 1 });
+    assert_eq!(result.unwrap_err(), TxError::UnauthorisedTx(2, 1));
+
+    let account : &Account = 
+    engine.accounts.get(&1).unwrap();
+
+    assert_eq!(account.available, dec!(5.0));
+    assert_eq!(account.held, dec!(0.0));
+    assert!(!engine.tx_history.contains_key(&2));
+}
+
+#[test]
+fn apply_matches_csv_processing()
+{
+    let csv =
+    "type,       client,     tx,     amount
+    deposit,         1,      1,     5.0
+    deposit,         2,      2,     3.0
+    withdrawal,      1,      3,     2.0
+    dispute,         2,      2,
+    chargeback,      2,      2,";
+
+    let mut from_csv : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    tx_engine::engine::Engine::new(&mut from_csv)
+        .process_transactions(&mut data,&mut NullSink).unwrap();
+
+    let mut from_commands : HashMap<u16, Account> = HashMap::new();
+    let mut engine = tx_engine::engine::Engine::new(&mut from_commands);
+    let commands = [
+        TxCommand::Deposit { client: 1, tx: 1, amount: dec!(5.0) },
+        TxCommand::Deposit { client: 2, tx: 2, amount: dec!(3.0) },
+        TxCommand::Withdrawal { client: 1, tx: 3, amount: dec!(2.0) },
+        TxCommand::Dispute { client: 2, tx: 2 },
+        TxCommand::Chargeback { client: 2, tx: 2 }
+    ];
+    for command in commands
+    {
+        engine.apply(command).unwrap();
+    }
+
+    for client in [1, 2]
+    {
+        let a = &from_csv[&client];
+        let b = &from_commands[&client];
+        assert_eq!((a.available, a.held, a.locked), (b.available, b.held, b.locked));
+    }
+}
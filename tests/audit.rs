@@ -0,0 +1,130 @@
+// tests the ledger invariant checks, by corrupting the state
+// the way a bug in the engine could and checking it's reported.
+use tx_engine::audit::*;
+use tx_engine::common::*;
+use tx_engine::sink::MemorySink;
+use tx_engine::engine::Engine;
+use tx_engine::ledger::LedgerAccount;
+use std::collections::HashMap;
+use rust_decimal_macros::dec;
+
+mod common;
+use common::process;
+
+const CSV : &str =
+"type,       client,     tx,     amount
+deposit,         1,      1,     5.0
+deposit,         2,      2,     3.0
+withdrawal,      1,      3,     1.5
+dispute,         2,      2,
+deposit,         3,      4,     2.0
+dispute,         3,      4,
+chargeback,      3,      4,
+deposit,         1,      5,     0.25
+dispute,         1,      5,
+resolve,         1,      5,";
+
+#[test]
+fn clean_run_has_no_violations()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    engine.audit = AuditMode::EveryTx;
+    process(&mut engine, CSV);
+
+    assert_eq!(engine.violations, vec![]);
+    assert_eq!(engine.check_invariants(), vec![]);
+}
+
+#[test]
+fn detect_corrupt_available()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    process(&mut engine, CSV);
+
+    engine.accounts.get_mut(&1).unwrap().available += dec!(0.0001);
+
+    assert_eq!(engine.check_invariants(), vec![
+        Violation::TotalMismatch { client : 1, expected : dec!(3.75), actual : dec!(3.7501) },
+        Violation::LedgerMismatch { client : 1, account : LedgerAccount::ClientAvailable(1),
+                                    expected : dec!(3.75), actual : dec!(3.7501) }
+    ]);
+}
+
+#[test]
+fn detect_corrupt_held()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    process(&mut engine, CSV);
+
+    // the held funds were released but the tx is still disputed
+    let account = engine.accounts.get_mut(&2).unwrap();
+    account.held = dec!(0.0);
+    account.available = dec!(3.0);
+
+    assert_eq!(engine.check_client(2), vec![
+        Violation::LedgerMismatch { client : 2, account : LedgerAccount::ClientAvailable(2),
+                                    expected : dec!(0.0), actual : dec!(3.0) },
+        Violation::LedgerMismatch { client : 2, account : LedgerAccount::ClientHeld(2),
+                                    expected : dec!(3.0), actual : dec!(0.0) },
+        Violation::HeldMismatch { client : 2, expected : dec!(3.0), actual : dec!(0.0) }
+    ]);
+}
+
+#[test]
+fn detect_corrupt_tx_state()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    process(&mut engine, CSV);
+
+    // tx 5 was resolved, marking it disputed again without
+    // withholding anything leaves held short of the disputes.
+    engine.tx_history.update(5, |tx| tx.state = TxState::Disputed);
+
+    let violations = engine.check_invariants();
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].client(), 1);
+    assert!(matches!(violations[0], Violation::HeldMismatch { .. }));
+}
+
+#[test]
+fn every_tx_reports_the_line()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    engine.audit = AuditMode::EveryTx;
+    process(&mut engine, CSV);
+
+    engine.accounts.get_mut(&2).unwrap().held = dec!(2.0);
+
+    let mut sink = MemorySink::new();
+    let mut data = csv::Reader::from_reader(
+"type,       client,     tx,     amount
+deposit,         1,      6,     1.0
+deposit,         2,      7,     1.0".as_bytes());
+    engine.process_transactions(&mut data,&mut sink).unwrap();
+
+    // found after the row touching client 2, and again at the end.
+    let lines : Vec<u64> = engine.violations.iter().map(|(line, _)| *line).collect();
+    assert_eq!(lines, vec![3, 3, 3, 0, 0, 0]);
+    assert!(engine.violations.iter().all(|(_, v)| v.client() == 2));
+    assert!(matches!(sink.last_entry(), Some(ProcessError::ErrInvariantViolated(0, _))));
+}
+
+#[test]
+fn opening_balances_are_taken_as_given()
+{
+    let mut opening = Account::new();
+    opening.deposit(&dec!(10.0)).unwrap();
+    let mut accounts : HashMap<u16, Account> = HashMap::from([(1, opening)]);
+    let mut engine = Engine::new(&mut accounts);
+    engine.audit = AuditMode::EndOfRun;
+    process(&mut engine, CSV);
+
+    assert_eq!(engine.violations, vec![]);
+    assert_eq!(engine.flows[&1].opening, dec!(10.0));
+    assert_eq!(engine.accounts[&1].available, dec!(13.75));
+}
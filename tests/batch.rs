@@ -0,0 +1,169 @@
+// tests processing files as batches which are committed or rolled back whole
+use tx_engine::batch::*;
+use tx_engine::common::*;
+use tx_engine::engine::Engine;
+use tx_engine::input::Input;
+use tx_engine::sink::*;
+use tx_engine::summary::{RunSummary, SummaryFormat};
+use std::collections::HashMap;
+use rust_decimal_macros::dec;
+
+const MONDAY : &str =
+"type,       client,     tx,     amount
+deposit,         1,      1,     5.0
+deposit,         2,      2,     3.0";
+
+// one row in three is refused.
+const TUESDAY : &str =
+"type,       client,     tx,     amount
+deposit,         1,      3,     1.0
+withdrawal,      2,      4,     9.0
+dispute,         1,      1,";
+
+// as tuesday, with a row which can't be parsed.
+const WEDNESDAY : &str =
+"type,       client,     tx,     amount
+deposit,         1,      3,     1.0
+withdrawal,      2,      4,     9.0
+deposit,         x,      5,     1.0";
+
+fn reader(csv_data : &str)
+-> csv::Reader<&[u8]>
+{
+    csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(csv_data.as_bytes())
+}
+
+#[test]
+fn commit_a_batch_which_meets_the_policy()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    engine.summary = Some(RunSummary::new());
+    let policy = BatchPolicy::default();
+
+    let report = engine.process_batch_reader(&mut reader(MONDAY), &policy, &mut NullSink);
+    assert!(report.committed);
+    let report = engine.process_batch_reader(&mut reader(TUESDAY), &policy, &mut NullSink);
+    assert!(report.committed);
+    assert!(report.reasons.is_empty());
+    assert_eq!(report.run.rejections.len(), 1);
+
+    assert_eq!(engine.tx_state(3), Some(TxState::Processed));
+    assert_eq!(engine.tx_state(1), Some(TxState::Disputed));
+    let summary = engine.summary.take().unwrap();
+    assert_eq!((summary.rows, summary.accepted_total(), summary.rejected_total()), (5, 4, 1));
+    assert_eq!(summary.rolled_back, 0);
+
+    assert_eq!(accounts[&1], Account { available : dec!(1.0), held : dec!(5.0), locked : false });
+    assert_eq!(accounts[&2], Account { available : dec!(3.0), held : dec!(0.0), locked : false });
+}
+
+#[test]
+fn roll_back_a_batch_with_row_errors()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    engine.summary = Some(RunSummary::new());
+    engine.process_batch_reader(&mut reader(MONDAY), &BatchPolicy::default(), &mut NullSink);
+    let before = engine.accounts.clone();
+
+    // the caller's sink still gets the rejected rows.
+    let mut sink = MemorySink::new();
+    let report = engine.process_batch_reader(&mut reader(WEDNESDAY), &BatchPolicy::default(),
+                                             &mut sink);
+    assert!(!report.committed);
+    assert_eq!(report.reasons, ["1 rows couldn't be parsed, at most 0 may be"]);
+    assert_eq!(sink.events.len(), 2);
+    assert_eq!(report.run.deltas.len(), 1);
+
+    // none of the batch was applied, not even its deposit.
+    assert_eq!(*engine.accounts, before);
+    assert_eq!(engine.tx_state(3), None);
+    let summary = engine.summary.take().unwrap();
+    assert_eq!((summary.rows, summary.accepted_total(), summary.rolled_back), (5, 2, 3));
+
+    // unless row errors are allowed.
+    let policy = BatchPolicy { max_row_errors : None, ..BatchPolicy::default() };
+    let report = engine.process_batch_reader(&mut reader(WEDNESDAY), &policy, &mut NullSink);
+    assert!(report.committed);
+    assert_eq!(engine.tx_state(3), Some(TxState::Processed));
+}
+
+#[test]
+fn roll_back_a_batch_with_too_many_rejections()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    engine.process_batch_reader(&mut reader(MONDAY), &BatchPolicy::default(), &mut NullSink);
+
+    let policy = BatchPolicy { max_rejected_percent : Some(dec!(30)), ..BatchPolicy::default() };
+    let report = engine.process_batch_reader(&mut reader(TUESDAY), &policy, &mut NullSink);
+    assert!(!report.committed);
+    assert_eq!(report.reasons, ["33.33% of rows were rejected, at most 30% may be"]);
+    assert_eq!(engine.tx_state(1), Some(TxState::Processed));
+
+    let policy = BatchPolicy { max_rejected_percent : Some(dec!(33.5)), ..BatchPolicy::default() };
+    let report = engine.process_batch_reader(&mut reader(TUESDAY), &policy, &mut NullSink);
+    assert!(report.committed);
+    assert_eq!(engine.tx_state(1), Some(TxState::Disputed));
+}
+
+#[test]
+fn keep_the_events_of_committed_batches()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    engine.events = Some(Vec::new());
+    let policy = BatchPolicy::default();
+
+    engine.process_batch_reader(&mut reader(MONDAY), &policy, &mut NullSink);
+    engine.process_batch_reader(&mut reader(WEDNESDAY), &policy, &mut NullSink);
+    engine.process_batch_reader(&mut reader(TUESDAY), &policy, &mut NullSink);
+
+    let events = engine.events.as_ref().unwrap();
+    let seqs : Vec<(u64, u64)> = events.iter().map(|event| (event.seq, event.line)).collect();
+    assert_eq!(seqs, [(1, 2), (2, 3), (3, 2), (4, 3), (5, 4)]);
+
+    // the stream rebuilds the committed batches.
+    let mut replayed : HashMap<u16, Account> = HashMap::new();
+    Engine::new(&mut replayed).replay(events).unwrap();
+    drop(engine);
+    assert_eq!(replayed, accounts);
+}
+
+#[test]
+fn roll_back_a_batch_which_fails()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    let missing = Input::File(std::env::temp_dir().join("tx_engine_batch_missing.csv"));
+    let report = engine.process_batch(&missing, &BatchPolicy::default(), &mut NullSink);
+
+    assert!(!report.committed);
+    assert!(matches!(report.run.result, Err(ProcessError::ExternalErr(_))));
+    assert!(report.reasons[0].starts_with("the batch failed: "));
+    assert_eq!(report.input, Some(missing.name()));
+    assert!(accounts.is_empty());
+}
+
+#[test]
+fn write_the_report()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    let report = engine.process_batch_reader(&mut reader(WEDNESDAY), &BatchPolicy::default(),
+                                             &mut NullSink);
+
+    let mut text : Vec<u8> = Vec::new();
+    report.write(SummaryFormat::Text, &mut text).unwrap();
+    assert!(String::from_utf8(text).unwrap()
+            .starts_with("batch: rolled back\n  1 rows couldn't be parsed, at most 0 may be\n\
+                          rows read:       3\n"));
+
+    let mut json : Vec<u8> = Vec::new();
+    report.write(SummaryFormat::Json, &mut json).unwrap();
+    assert!(String::from_utf8(json).unwrap()
+            .starts_with("{\"file\":null,\"committed\":false,\
+                          \"reasons\":[\"1 rows couldn't be parsed, at most 0 may be\"],\
+                          \"run\":{\"summary\":{\"rows\":3,"));
+}
@@ -0,0 +1,204 @@
+// tests parsing the command line
+use tx_engine::audit::AuditMode;
+use tx_engine::batch::BatchPolicy;
+use tx_engine::cli::*;
+use tx_engine::common::*;
+use tx_engine::diff::*;
+use tx_engine::generate::generate_transactions;
+use tx_engine::output::OutputFormat;
+use tx_engine::statement::StatementFormat;
+use tx_engine::sink::MemorySink;
+use tx_engine::engine::Engine;
+use std::collections::HashMap;
+use rust_decimal_macros::dec;
+
+fn parse(line : &str)
+-> Result<Command, String>
+{
+    let args : Vec<String> = line.split_whitespace().map(String::from).collect();
+    parse_args(&args)
+}
+
+#[test]
+fn a_file_on_its_own_is_processed()
+{
+    let expected = RunArgs { inputs : vec![String::from("transactions.csv")], ..RunArgs::default() };
+    assert_eq!(parse("tx_engine transactions.csv"), Ok(Command::Process(expected.clone())));
+    assert_eq!(parse("tx_engine process transactions.csv"), Ok(Command::Process(expected)));
+}
+
+#[test]
+fn parse_named_options()
+{
+    let Ok(Command::Process(args)) = parse("tx_engine process a.csv - --format json \
+                                            --output out.json --log run.log --audit end \
+                                            --withdrawal-disputes reverse --lock-policy block-all \
+                                            --as-of-tx 3:7 --exit-code-rejected 4")
+    else
+    {
+        panic!("expected a process command");
+    };
+
+    assert_eq!(args.inputs, ["a.csv", "-"]);
+    assert_eq!(args.format, OutputFormat::Json);
+    assert_eq!(args.output.as_deref(), Some("out.json"));
+    assert_eq!(args.log_file.as_deref(), Some("run.log"));
+    assert_eq!(args.audit, AuditMode::EndOfRun);
+    assert_eq!(args.disputes, Some(WithdrawalDisputePolicy::Reverse));
+    assert_eq!(args.lock_policy, Some(LockPolicy::BlockAll));
+    assert_eq!(args.query, Some(Query::AfterTx(3, 7)));
+    assert_eq!(args.exit_rejected, 4);
+    assert_eq!(args.exit_failed, 1);
+}
+
+#[test]
+fn parse_every_command()
+{
+    let Ok(Command::Statement(args)) = parse("tx_engine statement 4 a.csv --lines 2:9 --format text")
+    else
+    {
+        panic!("expected a statement command");
+    };
+    assert_eq!(args.inputs, ["a.csv"]);
+    assert_eq!(args.query, Some(Query::Statement(4, Some(2..=9), StatementFormat::Text)));
+    assert!(matches!(parse("tx_engine statement 4 a.csv"),
+                     Ok(Command::Statement(args)) if args.query == Some(Query::Statement(4, None,
+                                                                        StatementFormat::default()))));
+    assert_eq!(parse("tx_engine statement 4 a.csv --lines 5:2"),
+               Err(String::from("--lines 5:2 ends before it starts")));
+
+    assert!(matches!(parse("tx_engine validate a.csv --rejects r.csv"),
+                     Ok(Command::Validate(args)) if args.rejects.as_deref() == Some("r.csv")));
+    assert_eq!(parse("tx_engine replay events.csv --format jsonl"),
+               Ok(Command::Replay(ReplayArgs { events        : String::from("events.csv"),
+                                               from_snapshot : None,
+                                               format        : OutputFormat::JsonLines,
+                                               output        : None })));
+    assert_eq!(parse("tx_engine diff before.csv after"),
+               Ok(Command::Diff(DiffArgs { before : String::from("before.csv"),
+                                           after  : String::from("after"),
+                                           output : None })));
+    assert_eq!(parse("tx_engine generate --rows 20 --seed 5"),
+               Ok(Command::Generate(GenerateArgs { rows    : 20,
+                                                   clients : 10,
+                                                   seed    : Some(5),
+                                                   output  : None })));
+}
+
+#[test]
+fn parse_batch_options()
+{
+    let batch = |line : &str| match parse(line)
+    {
+        Ok(Command::Process(args)) => Ok(args.batch),
+        Ok(_) => panic!("expected a process command"),
+        Err(err) => Err(err)
+    };
+
+    assert_eq!(batch("tx_engine a.csv"), Ok(None));
+    assert_eq!(batch("tx_engine a.csv --atomic"), Ok(Some(BatchPolicy::default())));
+    assert_eq!(batch("tx_engine a.csv --max-rejected 2.5% --max-row-errors any"),
+               Ok(Some(BatchPolicy { max_row_errors       : None,
+                                     max_rejected_percent : Some(dec!(2.5)) })));
+    assert_eq!(batch("tx_engine a.csv --max-row-errors 3 --atomic"),
+               Ok(Some(BatchPolicy { max_row_errors : Some(3), ..BatchPolicy::default() })));
+
+    assert_eq!(batch("tx_engine a.csv --max-rejected 120"),
+               Err(String::from("--max-rejected expects a percentage from 0 to 100, not '120'")));
+    assert_eq!(batch("tx_engine a.csv --batch-report r.txt"),
+               Err(String::from("--batch-report needs --atomic")));
+    assert!(batch("tx_engine a.csv --atomic --journal j.log").is_err());
+    assert!(parse("tx_engine validate a.csv --atomic").is_err());
+}
+
+#[test]
+fn show_help()
+{
+    assert_eq!(parse("tx_engine --help"), Ok(Command::Help(String::from(HELP))));
+    assert_eq!(parse("tx_engine help diff"), Ok(Command::Help(String::from(DIFF_HELP))));
+
+    let Ok(Command::Help(help)) = parse("tx_engine process a.csv --help")
+    else
+    {
+        panic!("expected help");
+    };
+    assert!(help.starts_with(PROCESS_HELP));
+    assert!(help.contains("--journal <path>"));
+}
+
+#[test]
+fn explain_bad_arguments()
+{
+    for (line, expected) in
+    [
+        ("tx_engine", "expected a command or a transactions file"),
+        ("tx_engine process", "expected at least one transactions file"),
+        ("tx_engine a.csv --bogus", "'--bogus' is not an option of process, \
+                                     see 'tx_engine process --help'"),
+        ("tx_engine validate a.csv --journal j", "'--journal' is not an option of validate, \
+                                                 see 'tx_engine validate --help'"),
+        ("tx_engine a.csv --format", "--format needs a value"),
+        ("tx_engine a.csv --format xml", "'xml' is not an output format"),
+        ("tx_engine a.csv --lock-policy lax", "'lax' is not a lock policy"),
+        ("tx_engine a.csv --as-of-tx 1", "--as-of-tx expects <client>:<number>, not '1'"),
+        ("tx_engine a.csv --as-of-tx 1:2 --as-of-line 1:3",
+         "--as-of-tx and --as-of-line can't be used together"),
+        ("tx_engine - -", "stdin ('-') can only be read once"),
+        ("tx_engine a.csv --exit-code-failed 256",
+         "--exit-code-failed expects a code from 0 to 255, not '256'"),
+        ("tx_engine statement", "statement needs a client id"),
+        ("tx_engine statement x a.csv", "'x' is not a client id"),
+        ("tx_engine replay", "replay expects a single event stream file"),
+        ("tx_engine diff a.csv", "diff expects two sets of accounts, before and after"),
+        ("tx_engine generate --rows many", "--rows expects a number, not 'many'"),
+        ("tx_engine generate --clients 0", "--clients must be at least 1"),
+        ("tx_engine help nothing", "'nothing' is not a command")
+    ]
+    {
+        assert_eq!(parse(line), Err(String::from(expected)), "{line}");
+    }
+}
+
+#[test]
+fn diff_changed_accounts()
+{
+    let before = HashMap::from([(1, Account { available : dec!(5), held : dec!(0), locked : false }),
+                                (2, Account { available : dec!(1), held : dec!(0), locked : false })]);
+    let after = HashMap::from([(1, Account { available : dec!(2), held : dec!(1), locked : true }),
+                               (2, Account { available : dec!(1), held : dec!(0), locked : false }),
+                               (3, Account { available : dec!(4), held : dec!(0), locked : false })]);
+
+    let diff = diff_accounts(&before, &after);
+    assert_eq!(diff.keys().collect::<Vec<_>>(), [&1, &3]);
+    assert_eq!(diff[&1].total(), dec!(-2));
+
+    let mut csv : Vec<u8> = Vec::new();
+    write_diff(&diff, &mut csv).unwrap();
+    assert_eq!(String::from_utf8(csv).unwrap(),
+               "client,available,held,total,was_locked,locked\r\n\
+                1,-3.0000,1.0000,-2.0000,false,true\r\n\
+                3,4.0000,0.0000,4.0000,false,false\r\n");
+}
+
+#[test]
+fn generated_files_are_repeatable()
+{
+    let generate = |seed|
+    {
+        let mut csv : Vec<u8> = Vec::new();
+        generate_transactions(500, 20, seed, &mut csv).unwrap();
+        String::from_utf8(csv).unwrap()
+    };
+    let csv = generate(9);
+    assert_eq!(csv, generate(9));
+    assert_ne!(csv, generate(10));
+    assert_eq!(csv.lines().count(), 501);
+
+    // every row can be parsed, whether or not the engine accepts it.
+    let mut sink = MemorySink::new();
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    Engine::new(&mut accounts).process_transactions(&mut data, &mut sink).unwrap();
+    assert!(!sink.events.is_empty());
+    assert!(sink.events.iter().all(|(_, event)| event.category() == ErrorCategory::Transaction));
+}
@@ -0,0 +1,23 @@
+// helpers shared by the integration tests, each test file which uses
+// them declares 'mod common;'. Not every file uses every helper.
+#![allow(dead_code)]
+
+use tx_engine::engine::Engine;
+use tx_engine::generate::generate_transactions;
+use tx_engine::sink::NullSink;
+
+// a transactions file written by tx_engine::generate, as a string.
+pub fn generate_csv(rows : u32, clients : u16, seed : u64)
+-> String
+{
+    let mut csv : Vec<u8> = Vec::new();
+    generate_transactions(rows, clients, seed, &mut csv).unwrap();
+    String::from_utf8(csv).unwrap()
+}
+
+// processes the rows with the engine, ignoring its events.
+pub fn process(engine : &mut Engine, csv_data : &str)
+{
+    let mut data = csv::Reader::from_reader(csv_data.as_bytes());
+    engine.process_transactions(&mut data,&mut NullSink).unwrap();
+}
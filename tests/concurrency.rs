@@ -1,22 +1,39 @@
+#![allow(clippy::bool_assert_comparison)]
+
 // tests processing multiple transaction files
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use rust_decimal_macros::dec;
 
 use tx_engine::common::*;
+use tx_engine::sink::{MemorySink, NullSink};
+use tx_engine::registry::TxRegistry;
+use tx_engine::sharded::ShardedEngine;
+
+mod common;
+use common::generate_csv;
 
 fn do_process_csv(accounts : &mut HashMap<u16, Account>, csv : &str)
 {
     let mut data = csv::Reader::from_reader(csv.as_bytes());
     let mut engine = tx_engine::engine::Engine::new(accounts);
-    engine.process_transactions(&mut data,&mut None);
+    engine.process_transactions(&mut data,&mut NullSink).unwrap();
 }
 
 fn do_process_path(accounts : &mut HashMap<u16, Account>, path : &str)
 {
     let mut data = csv::Reader::from_path(path).unwrap();
     let mut engine = tx_engine::engine::Engine::new(accounts);
-    engine.process_transactions(&mut data,&mut None);
+    engine.process_transactions(&mut data,&mut NullSink).unwrap();
+}
+
+fn do_process_shared(accounts : &mut HashMap<u16, Account>, 
+                     registry : TxRegistry, 
+                     csv      : &str)
+{
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    let mut engine = tx_engine::engine::Engine::with_registry(accounts, registry);
+    engine.process_transactions(&mut data,&mut NullSink).unwrap();
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
@@ -123,4 +140,197 @@ async fn process_multiple_large_transactions()
     assert_eq!(account.available, dec!(22500.0));
     assert_eq!(account.locked, false);
 
-}
\ No newline at end of file
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn process_double_shared_registry()
+{
+    let csv = 
+    "type,       client,     tx,     amount
+    deposit,    1,           1,        1.0
+    deposit,    2,           2,        2.0
+    deposit,    1,           3,        2.0
+    withdrawal, 1,           4,        1.5";
+
+    let accounts : HashMap<u16, Account> = HashMap::new();
+    let accounts = Arc::new(Mutex::new(accounts));
+    let registry = TxRegistry::new();
+
+    let mut handles = vec![];
+
+    for _ in 0..2
+    {   
+        let accounts = Arc::clone(&accounts);
+        let registry = registry.clone();
+        let handle = tokio::spawn( async move {
+
+        let shared_accounts = &mut *(accounts.lock().unwrap());
+        do_process_shared(shared_accounts, registry, csv);
+
+        });
+        handles.push(handle);
+    }
+
+    for handle in handles
+    {
+        handle.await.unwrap();
+    }
+
+    let shared_accounts = &*(accounts.lock().unwrap());
+
+    let account : &Account = 
+    shared_accounts.get(&1).unwrap();
+
+    // unlike process_double, the engines share a registry, so
+    // whichever runs second finds every tx id already used and
+    // the file is only applied once.
+    assert_eq!(account.held, dec!(0.0));
+    assert_eq!(account.available, dec!(1.5));
+    assert_eq!(registry.len(), 4);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn process_dispute_across_files()
+{
+    let file_a = 
+    "type,       client,     tx,     amount
+    deposit,    1,           1,        4.0
+    deposit,    2,           2,        2.0";
+
+    let file_b = 
+    "type,       client,     tx,     amount
+    dispute,    1,           1,
+    deposit,    2,           3,        1.0";
+
+    let accounts : HashMap<u16, Account> = HashMap::new();
+    let accounts = Arc::new(Mutex::new(accounts));
+    let registry = TxRegistry::new();
+
+    // file b references a deposit from file a, so a
+    // must be processed before b is started.
+    for csv in [file_a, file_b]
+    {
+        let accounts = Arc::clone(&accounts);
+        let registry = registry.clone();
+        tokio::spawn( async move {
+
+        let shared_accounts = &mut *(accounts.lock().unwrap());
+        do_process_shared(shared_accounts, registry, csv);
+
+        }).await.unwrap();
+    }
+
+    let shared_accounts = &*(accounts.lock().unwrap());
+
+    let account : &Account = 
+    shared_accounts.get(&1).unwrap();
+    assert_eq!(account.held, dec!(4.0));
+    assert_eq!(account.available, dec!(0.0));
+    assert_eq!(registry.get(&1).unwrap().state, TxState::Disputed);
+
+    let account : &Account = 
+    shared_accounts.get(&2).unwrap();
+    assert_eq!(account.available, dec!(3.0));
+}
+
+#[test]
+fn separate_accounts_shared_registry_at_the_same_time()
+{
+    // both files deposit then dispute the same tx ids, for different
+    // clients. Whichever engine claims an id first owns it, the other's
+    // deposit is refused and so is its dispute.
+    let txs = 2000;
+    let file = |client : u16|
+    {
+        let mut csv = String::from("type,client,tx,amount\n");
+        (1..=txs).for_each(|tx| csv.push_str(&format!("deposit,{client},{tx},1.0\n")));
+        (1..=txs).for_each(|tx| csv.push_str(&format!("dispute,{client},{tx},\n")));
+        csv
+    };
+
+    let registry = TxRegistry::new();
+    let start = std::sync::Barrier::new(2);
+    let mut accounts_a : HashMap<u16, Account> = HashMap::new();
+    let mut accounts_b : HashMap<u16, Account> = HashMap::new();
+
+    std::thread::scope(|scope|
+    {
+        for (accounts, client) in [(&mut accounts_a, 1), (&mut accounts_b, 2)]
+        {
+            let registry = registry.clone();
+            let start = &start;
+            let csv = file(client);
+            scope.spawn(move ||
+            {
+                start.wait();
+                do_process_shared(accounts, registry, &csv);
+            });
+        }
+    });
+
+    // every id was used once, and each engine disputed exactly the
+    // deposits it claimed, against its own accounts.
+    assert_eq!(registry.len(), txs as usize);
+    let owned = |client : u16| (1..=txs).filter(|tx| registry.get(tx).unwrap().client == client)
+                                        .count();
+    assert_eq!(owned(1) + owned(2), txs as usize);
+    assert!((1..=txs).all(|tx| registry.get(&tx).unwrap().state == TxState::Disputed));
+
+    // an engine which claimed none of the ids has no account at all.
+    for (accounts, client) in [(&accounts_a, 1), (&accounts_b, 2)]
+    {
+        assert!(accounts.keys().all(|key| *key == client));
+        let account = accounts.get(&client).cloned().unwrap_or_default();
+        assert_eq!((account.available, account.held),
+                   (dec!(0.0), rust_decimal::Decimal::from(owned(client))));
+    }
+}
+
+#[test]
+fn refuse_dispute_of_another_engines_account()
+{
+    let registry = TxRegistry::new();
+
+    let mut accounts_a : HashMap<u16, Account> = HashMap::new();
+    do_process_shared(&mut accounts_a, registry.clone(),
+                      "type,client,tx,amount\ndeposit,1,1,4.0");
+
+    // the deposit is in the shared registry, but its account isn't in
+    // this engine's map, so there's nothing here to withhold it from.
+    let mut accounts_b : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader("type,client,tx,amount\ndispute,1,1,".as_bytes());
+    let mut sink = MemorySink::new();
+    tx_engine::engine::Engine::with_registry(&mut accounts_b, registry.clone())
+        .process_transactions(&mut data, &mut sink).unwrap();
+
+    assert_eq!(sink.events, vec![(2, ProcessError::ErrAccountNotFound(1, 1))]);
+    assert!(accounts_b.is_empty());
+    assert_eq!(accounts_a[&1].available, dec!(4.0));
+    assert_eq!(registry.get(&1).unwrap().state, TxState::Processed);
+}
+
+// a generated file, with disputes, resolves and chargebacks, leaves
+// the same accounts whether it's processed by one engine or sharded.
+#[test]
+fn sharded_matches_single_threaded()
+{
+    let csv = generate_csv(20000, 300, 7);
+
+    let mut single : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    tx_engine::engine::Engine::new(&mut single)
+        .process_transactions(&mut data,&mut NullSink).unwrap();
+
+    let mut sharded : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    ShardedEngine::new(&mut sharded, 4)
+        .process_transactions(&mut data,&mut NullSink).unwrap();
+
+    assert_eq!(single.len(), sharded.len());
+    for (client, a) in single.iter()
+    {
+        let b = &sharded[client];
+        assert_eq!((a.available, a.held, a.locked), 
+                   (b.available, b.held, b.locked), "client {client}");
+    }
+}
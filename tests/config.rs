@@ -0,0 +1,183 @@
+// tests the engine's rules, as loaded from a config
+use tx_engine::common::*;
+use tx_engine::config::EngineConfig;
+use tx_engine::engine::Engine;
+use tx_engine::events::EventKind;
+use tx_engine::sharded::ShardedEngine;
+use tx_engine::sink::{MemorySink, NullSink};
+use tx_engine::summary::RunSummary;
+use std::collections::HashMap;
+use rust_decimal_macros::dec;
+
+const CONFIG : &str = "
+# rules for the partner files
+rounding_dp = 2
+reject_negative_amounts = false   # corrections come in as negative deposits
+cross_client = \"owner\"
+withdrawal_disputes = \"reject\"
+lock_policy = \"block-all\"
+";
+
+fn run(config : &EngineConfig, csv_data : &str, accounts : &mut HashMap<u16, Account>)
+-> MemorySink
+{
+    let mut sink = MemorySink::new();
+    let mut engine = Engine::new(accounts);
+    engine.configure(config);
+    let mut data = csv::Reader::from_reader(csv_data.as_bytes());
+    engine.process_transactions(&mut data, &mut sink).unwrap();
+    sink
+}
+
+#[test]
+fn read_every_rule()
+{
+    let config = EngineConfig::from_toml(CONFIG).unwrap();
+    assert_eq!(config, EngineConfig
+    {
+        rounding_dp             : 2,
+        reject_negative_amounts : false,
+        cross_client            : CrossClientPolicy::Owner,
+        withdrawal_disputes     : WithdrawalDisputePolicy::Reject,
+        lock_policy             : LockPolicy::BlockAll
+    });
+
+    // written out, it reads back the same.
+    assert_eq!(EngineConfig::from_toml(&config.to_string()).unwrap(), config);
+    assert_eq!(EngineConfig::from_toml("").unwrap(), EngineConfig::default());
+}
+
+#[test]
+fn refuse_invalid_configs()
+{
+    for (toml, expected) in
+    [
+        ("rounding_dp = 5", "line 1: rounding_dp is 5, it must be from 0 to 4"),
+        ("rounding_dp = \"4\"", "line 1: rounding_dp should be an integer"),
+        ("\nreject_negative_amounts = 1", "line 2: reject_negative_amounts should be true or false"),
+        ("lock_policy = \"lax\"", "line 1: 'lax' is not a lock policy"),
+        ("cross_client = \"anyone\"", "line 1: 'anyone' is not a cross client policy"),
+        ("rounding = 2", "line 1: 'rounding' is not a config key"),
+        ("[engine]", "line 1: tables aren't supported, keys go at the top level"),
+        ("rounding_dp", "line 1: expected 'key = value'"),
+        ("lock_policy = block-all", "line 1: 'block-all' is not a string, integer or boolean"),
+        ("rounding_dp = 2\nrounding_dp = 3", "line 2: rounding_dp is already set on line 1")
+    ]
+    {
+        let err = EngineConfig::from_toml(toml).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), expected);
+    }
+
+    // a config built in code is checked the same way.
+    let config = EngineConfig { rounding_dp : 6, ..EngineConfig::default() };
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn round_to_the_configured_places()
+{
+    let config = EngineConfig { rounding_dp : 2, ..EngineConfig::default() };
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    run(&config, "type,client,tx,amount
+deposit,1,1,1.2345
+withdrawal,1,2,0.239
+dispute,1,1,", &mut accounts);
+
+    assert_eq!(accounts[&1].available, dec!(-0.24));
+    assert_eq!(accounts[&1].held, dec!(1.23));
+}
+
+#[test]
+fn allow_negative_amounts()
+{
+    let csv_data = "type,client,tx,amount
+deposit,1,1,5.0
+deposit,1,2,-2.0";
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let sink = run(&EngineConfig::default(), csv_data, &mut accounts);
+    assert_eq!(sink.last_entry(), Some(ProcessError::ErrAmountNegative(2)));
+    assert_eq!(accounts[&1].available, dec!(5.0));
+
+    let config = EngineConfig { reject_negative_amounts : false, ..EngineConfig::default() };
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let sink = run(&config, csv_data, &mut accounts);
+    assert!(sink.events.is_empty());
+    assert_eq!(accounts[&1].available, dec!(3.0));
+}
+
+#[test]
+fn apply_cross_client_disputes_to_the_owner()
+{
+    let csv_data = "type,client,tx,amount
+deposit,1,1,5.0
+deposit,2,2,1.0
+dispute,2,1,
+chargeback,2,1,";
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let sink = run(&EngineConfig::default(), csv_data, &mut accounts);
+    assert_eq!(sink.events.len(), 2);
+    assert_eq!(sink.events[0].1, ProcessError::ErrUnauthorisedTx(2, 1));
+
+    let config = EngineConfig { cross_client : CrossClientPolicy::Owner,
+                                ..EngineConfig::default() };
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    engine.configure(&config);
+    engine.events = Some(Vec::new());
+    let mut data = csv::Reader::from_reader(csv_data.as_bytes());
+    engine.process_transactions(&mut data, &mut NullSink).unwrap();
+
+    // the event stream records the command as applied, to the owner.
+    let applied : Vec<u16> = engine.events.as_ref().unwrap().iter().filter_map(|event|
+    {
+        match event.kind
+        {
+            EventKind::Applied { command, .. } => Some(command.client()),
+            _ => None
+        }
+    }).collect();
+    assert_eq!(applied, [1, 2, 1, 1]);
+
+    assert_eq!(accounts[&1], Account { available : dec!(0.0), held : dec!(0.0), locked : true });
+    assert_eq!(accounts[&2], Account { available : dec!(1.0), held : dec!(0.0), locked : false });
+}
+
+#[test]
+fn echo_the_config_in_the_summary()
+{
+    let config = EngineConfig::from_toml(CONFIG).unwrap();
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    engine.configure(&config);
+    engine.summary = Some(RunSummary::new());
+    let mut data = csv::Reader::from_reader("type,client,tx,amount\ndeposit,1,1,1.0".as_bytes());
+    engine.process_transactions(&mut data, &mut NullSink).unwrap();
+    assert_eq!(engine.config(), config);
+
+    let summary = engine.summary.unwrap();
+    assert_eq!(summary.config, Some(config));
+
+    let mut text : Vec<u8> = Vec::new();
+    summary.write(tx_engine::summary::SummaryFormat::Text, &mut text).unwrap();
+    assert!(String::from_utf8(text).unwrap()
+            .contains("config:          rounding_dp 2, reject_negative_amounts false, \
+                       cross_client owner, withdrawal_disputes reject, lock_policy block-all\n"));
+}
+
+#[test]
+fn sharded_engine_only_refuses_cross_client()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut sharded = ShardedEngine::new(&mut accounts, 2);
+
+    let config = EngineConfig::from_toml(CONFIG).unwrap();
+    assert!(sharded.configure(&config).is_err());
+
+    let config = EngineConfig { cross_client : CrossClientPolicy::Refuse, ..config };
+    sharded.configure(&config).unwrap();
+    assert_eq!(sharded.rounding_dp, 2);
+    assert_eq!(sharded.lock_policy, LockPolicy::BlockAll);
+}
@@ -0,0 +1,164 @@
+// tests running files against a copy of the accounts
+use tx_engine::common::*;
+use tx_engine::diff::diff_accounts;
+use tx_engine::engine::Engine;
+use tx_engine::input::Input;
+use tx_engine::registry::TxRegistry;
+use tx_engine::sink::*;
+use tx_engine::summary::SummaryFormat;
+use std::collections::HashMap;
+use rust_decimal_macros::dec;
+
+const OPENING : &str =
+"type,       client,     tx,     amount
+deposit,         1,      1,     5.0
+deposit,         2,      2,     3.0";
+
+const PARTNER_FILE : &str =
+"type,       client,     tx,     amount
+dispute,         1,      1,
+withdrawal,      2,      3,     9.0
+deposit,         3,      4,     1.5
+chargeback,      1,      1,
+deposit,         3,      4,     2.0
+refund,          2,      5,     1.0";
+
+fn reader(csv_data : &str)
+-> csv::Reader<&[u8]>
+{
+    csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(csv_data.as_bytes())
+}
+
+// the accounts and transactions after the opening file.
+fn opening_state()
+-> (HashMap<u16, Account>, TxRegistry)
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    engine.process_transactions(&mut reader(OPENING), &mut NullSink).unwrap();
+    let registry = engine.tx_history.clone();
+    (accounts, registry)
+}
+
+#[test]
+fn leave_the_engine_untouched()
+{
+    let (mut accounts, registry) = opening_state();
+    let before = accounts.clone();
+    let txs = registry.snapshot();
+
+    let mut engine = Engine::with_registry(&mut accounts, registry);
+    let dry_run = engine.dry_run_reader(&mut reader(PARTNER_FILE), &mut NullSink);
+    assert!(dry_run.result.is_ok());
+    assert_eq!(engine.tx_history.snapshot(), txs);
+    assert_eq!(engine.ledger.client_balances(3), (dec!(0), dec!(0)));
+    assert!(engine.summary.is_none());
+
+    // the dry run's deposit of tx 4 isn't known to the engine.
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut reader("type,client,tx,amount\ndispute,3,4,"),
+                                &mut sink).unwrap();
+    assert_eq!(sink.last_entry(), Some(ProcessError::ErrTxNotFound(4)));
+    assert_eq!(accounts, before);
+}
+
+#[test]
+fn report_every_rejection_and_the_deltas()
+{
+    let (mut accounts, registry) = opening_state();
+    let engine = Engine::with_registry(&mut accounts, registry);
+    let mut sink = MemorySink::new();
+    let dry_run = engine.dry_run_reader(&mut reader(PARTNER_FILE), &mut sink);
+
+    let rejected : Vec<(u64, &str)> = dry_run.rejections.iter()
+                                             .map(|rejection| (rejection.line, rejection.error.code()))
+                                             .collect();
+    assert_eq!(rejected, [(3, "insufficient_funds"), (6, "tx_id_exists"), (7, "unrecognised_tx")]);
+    assert!(dry_run.rejections.iter().all(|rejection| rejection.input.is_none()));
+
+    // the caller's sink gets the events too.
+    assert_eq!(sink.events.len(), 3);
+    assert_eq!(dry_run.summary.rows, 6);
+    assert_eq!(dry_run.summary.rejected_total(), 3);
+
+    let changes : Vec<_> = dry_run.deltas.iter()
+                                  .map(|(client, delta)| (*client, delta.available(), delta.held(),
+                                                          delta.after.locked))
+                                  .collect();
+    assert_eq!(changes, [(1, dec!(-5.0), dec!(0.0), true), (3, dec!(1.5), dec!(0.0), false)]);
+}
+
+#[test]
+fn predict_a_real_run()
+{
+    let (mut accounts, registry) = opening_state();
+    let before = accounts.clone();
+    let mut engine = Engine::with_registry(&mut accounts, registry);
+
+    let dry_run = engine.dry_run_reader(&mut reader(PARTNER_FILE), &mut NullSink);
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut reader(PARTNER_FILE), &mut sink).unwrap();
+
+    let rejected : Vec<(u64, ProcessError)> = dry_run.rejections.into_iter()
+                                                     .map(|rejection| (rejection.line, rejection.error))
+                                                     .collect();
+    assert_eq!(rejected, sink.events);
+    assert_eq!(dry_run.deltas, diff_accounts(&before, &accounts));
+}
+
+#[test]
+fn name_the_file_of_each_rejection()
+{
+    let dir = std::env::temp_dir().join(format!("tx_engine_{}_dry_run", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("monday.csv"), OPENING).unwrap();
+    std::fs::write(dir.join("tuesday.csv"), PARTNER_FILE).unwrap();
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let engine = Engine::new(&mut accounts);
+    let inputs = [Input::File(dir.join("monday.csv")), Input::File(dir.join("tuesday.csv"))];
+    let dry_run = engine.dry_run(&inputs, &mut NullSink);
+    assert!(dry_run.result.is_ok());
+
+    let tuesday = dir.join("tuesday.csv").display().to_string();
+    assert!(dry_run.rejections.iter().all(|rejection| rejection.input.as_ref() == Some(&tuesday)));
+    assert_eq!(dry_run.deltas.len(), 3);
+    assert!(accounts.is_empty());
+
+    // a file which can't be read stops the run, with what came before it.
+    let engine = Engine::new(&mut accounts);
+    let inputs = [Input::File(dir.join("monday.csv")), Input::File(dir.join("missing.csv"))];
+    let dry_run = engine.dry_run(&inputs, &mut NullSink);
+    assert!(matches!(dry_run.result, Err(ProcessError::ExternalErr(_))));
+    assert_eq!(dry_run.deltas.len(), 2);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn write_the_report()
+{
+    let (mut accounts, registry) = opening_state();
+    let engine = Engine::with_registry(&mut accounts, registry);
+    let dry_run = engine.dry_run_reader(&mut reader(PARTNER_FILE), &mut NullSink);
+
+    let mut text : Vec<u8> = Vec::new();
+    dry_run.write(SummaryFormat::Text, &mut text).unwrap();
+    let text = String::from_utf8(text).unwrap();
+    assert!(text.starts_with("rows read:       6\n"));
+    assert!(text.contains("rejections:      3\n  line 3: insufficient_funds, "));
+    assert!(text.ends_with("balance changes: 2\n\
+                            \x20 client 1: available -5.0000, held +0.0000, total -5.0000, locked\n\
+                            \x20 client 3: available +1.5000, held +0.0000, total +1.5000\n"));
+
+    let mut json : Vec<u8> = Vec::new();
+    dry_run.write(SummaryFormat::Json, &mut json).unwrap();
+    let json = String::from_utf8(json).unwrap();
+    assert!(json.starts_with("{\"summary\":{\"rows\":6,"));
+    assert!(json.contains("\"rejections\":[{\"file\":null,\"line\":3,\"code\":\"insufficient_funds\","));
+    assert!(json.ends_with(",\"deltas\":[{\"client\":1,\"available\":\"-5.0000\",\"held\":\"0.0000\",\
+                            \"total\":\"-5.0000\",\"was_locked\":false,\"locked\":true},\
+                            {\"client\":3,\"available\":\"1.5000\",\"held\":\"0.0000\",\
+                            \"total\":\"1.5000\",\"was_locked\":false,\"locked\":false}]}\n"));
+}
@@ -0,0 +1,107 @@
+// tests the errors reported while processing, and their codes
+use tx_engine::audit::AuditMode;
+use tx_engine::command::*;
+use tx_engine::common::*;
+use tx_engine::engine::Engine;
+use tx_engine::sink::MemorySink;
+use std::collections::HashMap;
+use rust_decimal_macros::dec;
+
+#[test]
+fn refuse_other_clients_tx_the_same_way()
+{
+    let csv =
+"type,       client,     tx,     amount
+deposit,         1,      1,     5.0
+dispute,         2,      1,
+resolve,         2,      1,
+chargeback,      2,      1,";
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    let mut sink = MemorySink::new();
+    Engine::new(&mut accounts).process_transactions(&mut data, &mut sink).unwrap();
+
+    assert_eq!(sink.events, vec![(3, ProcessError::ErrUnauthorisedTx(2, 1)),
+                                 (4, ProcessError::ErrUnauthorisedTx(2, 1)),
+                                 (5, ProcessError::ErrUnauthorisedTx(2, 1))]);
+    assert_eq!(accounts[&1].available, dec!(5.0));
+}
+
+#[test]
+fn refuse_rows_which_would_overflow_a_balance()
+{
+    let csv =
+"type,       client,     tx,     amount
+deposit,         1,      1,     100000000000000000000
+deposit,         1,      2,     1
+withdrawal,      1,      3,     5
+deposit,         2,      4,     79228162514264337593543950335";
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    let mut sink = MemorySink::new();
+    let mut engine = Engine::new(&mut accounts);
+    engine.process_transactions(&mut data, &mut sink).unwrap();
+
+    // the second deposit is refused and leaves no trace, so its id is
+    // free. An amount beyond MAX_AMOUNT is refused when it's parsed.
+    assert_eq!(sink.events[0], (3, ProcessError::ErrBalanceOverflow(1, 2)));
+    assert!(matches!(&sink.events[1..], [(5, ProcessError::ErrAmountTooLarge(5, _))]));
+    assert_eq!(engine.tx_state(2), None);
+    assert_eq!(accounts[&1].available, MAX_AMOUNT - dec!(5));
+    assert!(!accounts.contains_key(&2));
+
+    // held counts towards the total too, and the audit's sums of the
+    // balances stay within range.
+    let csv = format!("type,client,tx,amount\n\
+                       deposit,1,1,{MAX_AMOUNT}\n\
+                       dispute,1,1,\n\
+                       deposit,1,2,{MAX_AMOUNT}\n");
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    let mut sink = MemorySink::new();
+    let mut engine = Engine::new(&mut accounts);
+    engine.audit = AuditMode::EndOfRun;
+    engine.process_transactions(&mut data, &mut sink).unwrap();
+
+    assert_eq!(sink.events, vec![(4, ProcessError::ErrBalanceOverflow(1, 2))]);
+    assert_eq!(engine.violations, vec![]);
+    assert_eq!((accounts[&1].available, accounts[&1].held), (dec!(0), MAX_AMOUNT));
+}
+
+#[test]
+fn codes_and_categories()
+{
+    let errors = [(ProcessError::ErrBadTxId(2, String::from("deposit,1,x,1.0")),
+                   "bad_tx_id", 105, ErrorCategory::Row),
+                  (ProcessError::ErrTxNotFound(7), "tx_not_found", 201,
+                   ErrorCategory::Transaction),
+                  (ProcessError::ErrJournalFailed(7), "journal_failed", 210,
+                   ErrorCategory::Transaction),
+                  (ProcessError::ErrInvariantViolated(0, String::new()), "invariant_violated", 301,
+                   ErrorCategory::Audit),
+                  (ProcessError::ExternalErr(String::new()), "external_error", 901,
+                   ErrorCategory::External)];
+
+    for (error, code, number, category) in errors
+    {
+        assert_eq!((error.code(), error.number(), error.category()), (code, number, category));
+    }
+
+    // a refused row is a warning, a journal failure isn't.
+    assert_eq!(ProcessError::ErrTxNotFound(7).level(), LogLevel::Warn);
+    assert_eq!(ProcessError::ErrJournalFailed(7).level(), LogLevel::Error);
+}
+
+#[test]
+fn errors_display_their_message()
+{
+    let err : Box<dyn std::error::Error> = Box::new(TxError::InsufficientFunds(1, 2));
+    assert_eq!(err.to_string(),
+               "Client with id '1' has insufficient funds for transaction with id '2'.");
+
+    let err : Box<dyn std::error::Error> = Box::new(ProcessError::ErrTxFinalised(3, TxState::Resolved));
+    assert_eq!(err.to_string(), "Transaction with id '3' has been resolved and can no longer \
+                                 be disputed.");
+}
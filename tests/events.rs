@@ -0,0 +1,136 @@
+// tests the event stream, and rebuilding the engine from it
+use tx_engine::common::*;
+use tx_engine::sink::NullSink;
+use tx_engine::engine::Engine;
+use tx_engine::events::*;
+use std::collections::HashMap;
+
+mod common;
+use common::generate_csv;
+
+const CSV : &str =
+"type,       client,     tx,     amount
+deposit,         1,      1,     5.0
+deposit,         2,      2,     3.0
+withdrawal,      1,      3,     1.5
+withdrawal,      2,      4,     9.0
+dispute,         2,      2,
+chargeback,      2,      2,
+deposit,         2,      5,     1.0
+withdrawal,      1,      6,     1.0
+dispute,         1,      6,
+resolve,         1,      6,
+deposit,         1,      x,     1.0
+refund,          1,      7,     1.0
+dispute,         1,      3,";
+
+// runs the csv with events recorded, then rebuilds a second engine
+// from the events alone and checks the two ended up the same.
+fn assert_replay_matches(csv_data : &str, policy : WithdrawalDisputePolicy)
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    engine.dispute_policy = policy;
+    engine.events = Some(Vec::new());
+    let mut data = csv::Reader::from_reader(csv_data.as_bytes());
+    engine.process_transactions(&mut data,&mut NullSink).unwrap();
+
+    // through the on disk format and back.
+    let mut stream : Vec<u8> = Vec::new();
+    write_events(engine.events.as_ref().unwrap(), &mut stream).unwrap();
+    let events = read_events(stream.as_slice()).unwrap();
+    assert_eq!(&events, engine.events.as_ref().unwrap());
+
+    let mut replayed_accounts : HashMap<u16, Account> = HashMap::new();
+    let mut replayed = Engine::new(&mut replayed_accounts);
+    replayed.replay(&events).unwrap();
+
+    assert_eq!(replayed.accounts, engine.accounts);
+    assert_eq!(replayed.tx_history.snapshot(), engine.tx_history.snapshot());
+
+    let mut trial_balance : Vec<u8> = Vec::new();
+    engine.ledger.write_trial_balance(&mut trial_balance).unwrap();
+    let mut replayed_trial_balance : Vec<u8> = Vec::new();
+    replayed.ledger.write_trial_balance(&mut replayed_trial_balance).unwrap();
+    assert_eq!(replayed_trial_balance, trial_balance);
+}
+
+#[test]
+fn replay_matches_csv_run()
+{
+    assert_replay_matches(CSV, WithdrawalDisputePolicy::Withhold);
+}
+
+#[test]
+fn replay_matches_generated_runs()
+{
+    for (seed, policy) in [(1, WithdrawalDisputePolicy::Withhold),
+                           (2, WithdrawalDisputePolicy::Reverse),
+                           (3, WithdrawalDisputePolicy::Reject)]
+    {
+        assert_replay_matches(&generate_csv(2000, 20, seed), policy);
+    }
+}
+
+#[test]
+fn events_record_every_operation()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    engine.events = Some(Vec::new());
+    let mut data = csv::Reader::from_reader(CSV.as_bytes());
+    engine.process_transactions(&mut data,&mut NullSink).unwrap();
+
+    let events = engine.events.unwrap();
+    assert_eq!(events.len(), 13);
+    assert!(events.iter().enumerate().all(|(i, e)| e.seq == i as u64 + 1
+                                                && e.line == i as u64 + 2
+                                                && e.version == EVENT_VERSION));
+
+    // insufficient funds
+    assert!(matches!(&events[3].kind, EventKind::Rejected { command : Some(_), code, reason }
+                     if code == "insufficient_funds"
+                     && reason == "Client with id '2' has insufficient funds for transaction \
+                                   with id '4'."));
+    // a bad tx id, which couldn't be parsed into a command
+    assert!(matches!(&events[10].kind, EventKind::Rejected { command : None, .. }));
+    assert!(matches!(&events[12].kind, EventKind::Applied { reversed : false, .. }));
+}
+
+#[test]
+fn refuse_newer_event_version()
+{
+    let stream = "version,seq,line,outcome,type,client,tx,amount,reversed,code,reason\r\n\
+                  3,1,2,applied,deposit,1,1,5.0,false,,\r\n";
+    let err = read_events(stream.as_bytes()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn read_version_1_streams()
+{
+    // before version 2 a rejected event had no code.
+    let stream = "version,seq,line,outcome,type,client,tx,amount,reversed,reason\r\n\
+                  1,1,2,applied,deposit,1,1,5.0,false,\r\n\
+                  1,2,3,rejected,withdrawal,1,2,8.0,,\"InsufficientFunds(1, 2)\"\r\n";
+    let events = read_events(stream.as_bytes()).unwrap();
+
+    assert_eq!(events.len(), 2);
+    assert!(matches!(&events[1].kind, EventKind::Rejected { code, reason, .. }
+                     if code.is_empty() && reason == "InsufficientFunds(1, 2)"));
+}
+
+#[test]
+fn refuse_inconsistent_stream()
+{
+    // a withdrawal applied without the funds for it.
+    let stream = "version,seq,line,outcome,type,client,tx,amount,reversed,reason\r\n\
+                  1,1,2,applied,deposit,1,1,5.0,false,\r\n\
+                  1,2,3,applied,withdrawal,1,2,8.0,false,\r\n";
+    let events = read_events(stream.as_bytes()).unwrap();
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    let err = engine.replay(&events).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
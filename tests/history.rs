@@ -0,0 +1,178 @@
+// tests looking up balances as of an earlier point in the run
+use tx_engine::command::TxCommand;
+use tx_engine::common::*;
+use tx_engine::engine::Engine;
+use tx_engine::history::BalanceHistory;
+use tx_engine::journal::Journal;
+use tx_engine::record::TxKind;
+use std::collections::HashMap;
+use rust_decimal_macros::dec;
+
+mod common;
+use common::process;
+
+const CSV : &str =
+"type,       client,     tx,     amount
+deposit,         7,      1,     5.0
+deposit,         2,      2,     3.0
+withdrawal,      7,      3,     1.5
+dispute,         7,      1,
+withdrawal,      7,      4,     9.0
+resolve,         7,      1,
+deposit,         7,      5,     0.25";
+
+#[test]
+fn balance_after_tx()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    engine.history = Some(BalanceHistory::new());
+    process(&mut engine, CSV);
+
+    let account = engine.balance_after_tx(7, 3).unwrap();
+    assert_eq!((account.available, account.held), (dec!(3.5), dec!(0.0)));
+
+    // after the deposit of tx 1, not its later dispute and resolve.
+    let account = engine.balance_after_tx(7, 1).unwrap();
+    assert_eq!((account.available, account.held), (dec!(5.0), dec!(0.0)));
+
+    // tx 4 was refused, and tx 2 belongs to client 2.
+    assert_eq!(engine.balance_after_tx(7, 4), None);
+    assert_eq!(engine.balance_after_tx(7, 2), None);
+}
+
+#[test]
+fn balance_as_of_line()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    engine.history = Some(BalanceHistory::new());
+    process(&mut engine, CSV);
+
+    // before client 7's first row, it had an empty account.
+    let account = engine.balance_as_of_line(7, 0, 1).unwrap();
+    assert_eq!(account, Account::new());
+
+    // during the dispute, and the refused withdrawal after it.
+    for line in [5, 6]
+    {
+        let account = engine.balance_as_of_line(7, 0, line).unwrap();
+        assert_eq!((account.available, account.held), (dec!(-1.5), dec!(5.0)));
+    }
+
+    let account = engine.balance_as_of_line(7, 0, 100).unwrap();
+    assert_eq!(&account, &engine.accounts[&7]);
+    assert_eq!(engine.balance_as_of_line(3, 0, 100), None);
+}
+
+#[test]
+fn balance_as_of_line_over_several_inputs()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    engine.history = Some(BalanceHistory::new());
+    process(&mut engine, CSV);
+    process(&mut engine, "type,client,tx,amount\ndeposit,7,6,2.0\nwithdrawal,7,7,0.5");
+
+    // line numbers start again with the second input.
+    let account = engine.balance_as_of_line(7, 0, 3).unwrap();
+    assert_eq!(account.available, dec!(5.0));
+    let account = engine.balance_as_of_line(7, 1, 2).unwrap();
+    assert_eq!(account.available, dec!(5.75));
+    let account = engine.balance_as_of_line(7, 1, 1).unwrap();
+    assert_eq!(account.available, dec!(3.75));
+
+    let points = engine.history.as_ref().unwrap().points(7);
+    assert_eq!(points.iter().map(|point| (point.input, point.line)).collect::<Vec<_>>(),
+               [(0, 2), (0, 4), (0, 5), (0, 7), (0, 8), (1, 2), (1, 3)]);
+
+    // a statement over one input's lines, and over the whole history.
+    let statement = engine.statement(7, Some((1, 1..=2))).unwrap();
+    assert_eq!(statement.opening.available, dec!(3.75));
+    assert_eq!(statement.rows.len(), 1);
+    assert_eq!(engine.statement(7, None).unwrap().rows.len(), 7);
+}
+
+#[test]
+fn history_starts_from_opening_balances()
+{
+    let mut opening = Account::new();
+    opening.deposit(&dec!(10.0)).unwrap();
+    let mut accounts : HashMap<u16, Account> = HashMap::from([(7, opening.clone())]);
+    let mut engine = Engine::new(&mut accounts);
+    engine.history = Some(BalanceHistory::new());
+    process(&mut engine, CSV);
+
+    assert_eq!(engine.balance_as_of_line(7, 0, 1), Some(opening));
+    assert_eq!(engine.history.as_ref().unwrap().points(7).len(), 5);
+}
+
+#[test]
+fn history_mixes_replayed_and_file_rows()
+{
+    let dir = std::env::temp_dir();
+    let journal_path = dir.join(format!("tx_engine_{}_history.journal", std::process::id()));
+    let checkpoint_path = dir.join(format!("tx_engine_{}_history.checkpoint", std::process::id()));
+    let _ = std::fs::remove_file(&journal_path);
+    let _ = std::fs::remove_file(&checkpoint_path);
+
+    // the process crashes after the first four rows.
+    {
+        let prefix : String = CSV.lines().take(5).collect::<Vec<_>>().join("\n");
+        let mut accounts : HashMap<u16, Account> = HashMap::new();
+        let mut engine = Engine::new(&mut accounts);
+        engine.journal = Some(Journal::open(&journal_path, &checkpoint_path).unwrap());
+        process(&mut engine, &prefix);
+    }
+
+    // the replayed rows are recorded at their lines, a command given to
+    // apply before the next input's rows.
+    {
+        let mut accounts : HashMap<u16, Account> = HashMap::new();
+        let mut engine = Engine::new(&mut accounts);
+        engine.history = Some(BalanceHistory::new());
+        engine.journal = Some(Journal::open(&journal_path, &checkpoint_path).unwrap());
+        engine.recover().unwrap();
+        process(&mut engine, CSV);
+        // the interrupted file is complete, the next one is read whole.
+        engine.resume_after = 0;
+        engine.apply(TxCommand::new(TxKind::Deposit, 7, 9, dec!(1.0))).unwrap();
+        process(&mut engine, "type,client,tx,amount\ndeposit,7,10,2.0");
+
+        let points = engine.history.as_ref().unwrap().points(7);
+        assert_eq!(points.iter().map(|point| (point.input, point.line)).collect::<Vec<_>>(),
+                   [(0, 2), (0, 4), (0, 5), (0, 7), (0, 8), (1, 0), (1, 2)]);
+
+        let available = |input, line| engine.balance_as_of_line(7, input, line).unwrap().available;
+        assert_eq!(available(0, 4), dec!(3.5));
+        assert_eq!(available(0, 6), dec!(-1.5));
+        assert_eq!(available(1, 1), dec!(4.75));
+        assert_eq!(available(1, 2), dec!(6.75));
+        assert_eq!(engine.statement(7, Some((1, 1..=2))).unwrap().opening.available, dec!(4.75));
+    }
+
+    // the journal now holds both inputs, whose lines start again, and
+    // the points stay in order when it's replayed.
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    engine.history = Some(BalanceHistory::new());
+    engine.journal = Some(Journal::open(&journal_path, &checkpoint_path).unwrap());
+    engine.recover().unwrap();
+
+    let points = engine.history.as_ref().unwrap().points(7);
+    assert!(points.windows(2).all(|pair| (pair[0].input, pair[0].line)
+                                      <= (pair[1].input, pair[1].line)));
+    assert_eq!(engine.balance_as_of_line(7, 0, 5).unwrap().available, dec!(-1.5));
+    assert_eq!(engine.balance_as_of_line(7, 0, 8).unwrap().available, dec!(6.75));
+}
+
+#[test]
+fn no_history_kept_by_default()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    process(&mut engine, CSV);
+
+    assert_eq!(engine.balance_after_tx(7, 3), None);
+    assert_eq!(engine.balance_as_of_line(7, 0, 100), None);
+}
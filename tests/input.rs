@@ -0,0 +1,132 @@
+// tests reading several transactions files against the same accounts
+use tx_engine::common::*;
+use tx_engine::engine::Engine;
+use tx_engine::input::*;
+use tx_engine::rejects::RejectsReport;
+use tx_engine::sink::*;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use rust_decimal_macros::dec;
+
+const MONDAY : &str =
+"type,       client,     tx,     amount
+deposit,         1,      1,     5.0
+deposit,         2,      2,     3.0";
+
+const TUESDAY : &str =
+"type,       client,     tx,     amount
+dispute,         1,      1,
+withdrawal,      2,      3,     9.0";
+
+const WEDNESDAY : &str =
+"type,       client,     tx,     amount
+resolve,         1,      1,
+deposit,         2,      2,     1.0";
+
+// a fresh directory holding the files named, for a test.
+fn temp_dir(name : &str, files : &[(&str, &str)])
+-> PathBuf
+{
+    let dir = std::env::temp_dir().join(format!("tx_engine_{}_{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    for (file, contents) in files
+    {
+        std::fs::write(dir.join(file), contents).unwrap();
+    }
+    dir
+}
+
+fn arg(dir : &std::path::Path, name : &str)
+-> String
+{
+    dir.join(name).to_string_lossy().to_string()
+}
+
+#[test]
+fn expand_globs_in_order()
+{
+    let dir = temp_dir("globs", &[("2-tuesday.csv", TUESDAY),
+                                  ("1-monday.csv", MONDAY),
+                                  ("notes.txt", "")]);
+
+    let inputs = expand_inputs(&[arg(&dir, "*.csv"), String::from("-")]).unwrap();
+    assert_eq!(inputs, [Input::File(dir.join("1-monday.csv")),
+                        Input::File(dir.join("2-tuesday.csv")),
+                        Input::Stdin]);
+
+    let err = expand_inputs(&[arg(&dir, "*.json")]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    assert!(err.to_string().ends_with("*.json: no files match"));
+}
+
+#[test]
+fn share_the_accounts_between_files()
+{
+    let dir = temp_dir("shared", &[("monday.csv", MONDAY), ("tuesday.csv", TUESDAY)]);
+    let inputs = expand_inputs(&[arg(&dir, "monday.csv"), arg(&dir, "tuesday.csv")]).unwrap();
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    Engine::new(&mut accounts).process_inputs(&inputs, &mut NullSink).unwrap();
+
+    // tuesday's dispute is of monday's deposit.
+    assert_eq!(accounts[&1].available, dec!(0.0));
+    assert_eq!(accounts[&1].held, dec!(5.0));
+    assert_eq!(accounts[&2].available, dec!(3.0));
+}
+
+#[test]
+fn attribute_rejections_to_their_file()
+{
+    let dir = temp_dir("attributed", &[("monday.csv", MONDAY),
+                                       ("tuesday.csv", TUESDAY),
+                                       ("wednesday.csv", WEDNESDAY)]);
+    let inputs = expand_inputs(&[arg(&dir, "*day.csv")]).unwrap();
+    let log_path = dir.join("events.log");
+
+    let mut report = RejectsReport::new(Vec::new()).unwrap();
+    {
+        let logger = Logger::new(&log_path).unwrap();
+        let mut sink = FanOut::new(vec![Box::new(logger), Box::new(&mut report)]);
+
+        let mut accounts : HashMap<u16, Account> = HashMap::new();
+        Engine::new(&mut accounts).process_inputs(&inputs, &mut sink).unwrap();
+    }
+
+    let report = report.into_inner().unwrap();
+    let rejected : Vec<(String, String, String)> =
+        csv::Reader::from_reader(report.as_slice())
+            .records()
+            .map(|entry|
+            {
+                let entry = entry.unwrap();
+                (entry[0].to_string(), entry[1].to_string(), entry[2].to_string())
+            })
+            .collect();
+    assert_eq!(rejected, [(arg(&dir, "tuesday.csv"), String::from("3"),
+                           String::from("insufficient_funds")),
+                          (arg(&dir, "wednesday.csv"), String::from("3"),
+                           String::from("tx_id_exists"))]);
+
+    // each refused row is logged with its file.
+    let log = std::fs::read_to_string(&log_path).unwrap();
+    let files : Vec<bool> = log.lines()
+                               .filter(|entry| entry.contains("\"level\":\"warn\""))
+                               .zip(["tuesday.csv\",", "wednesday.csv\","])
+                               .map(|(entry, file)| entry.contains(file))
+                               .collect();
+    assert_eq!(files, [true, true]);
+}
+
+#[test]
+fn stop_at_a_file_which_cant_be_opened()
+{
+    let dir = temp_dir("missing", &[("monday.csv", MONDAY)]);
+    let inputs = [Input::File(dir.join("monday.csv")), Input::File(dir.join("tuesday.csv"))];
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let result = Engine::new(&mut accounts).process_inputs(&inputs, &mut NullSink);
+
+    assert!(matches!(result, Err(ProcessError::ExternalErr(msg)) if msg.contains("tuesday.csv: ")));
+    assert_eq!(accounts[&1].available, dec!(5.0));
+}
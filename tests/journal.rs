@@ -0,0 +1,263 @@
+#![allow(clippy::bool_assert_comparison)]
+
+// tests the write-ahead journal and recovering from a crash
+use tx_engine::common::*;
+use tx_engine::sink::NullSink;
+use tx_engine::journal::*;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use rust_decimal_macros::dec;
+
+const CSV : &str =
+"type,       client,     tx,     amount
+deposit,         1,      1,     5.0
+withdrawal,      1,      2,     8.0
+deposit,         2,      3,     3.0
+dispute,         2,      3,
+deposit,         1,      4,     4.0
+resolve,         2,      3,
+withdrawal,      1,      5,     8.0
+dispute,         1,      4,";
+
+// a fresh pair of journal and checkpoint paths for a test.
+fn temp_paths(name : &str)
+-> (PathBuf, PathBuf)
+{
+    let dir = std::env::temp_dir();
+    let journal = dir.join(format!("tx_engine_{}_{name}.journal", std::process::id()));
+    let checkpoint = dir.join(format!("tx_engine_{}_{name}.checkpoint", std::process::id()));
+    let _ = std::fs::remove_file(&journal);
+    let _ = std::fs::remove_file(&checkpoint);
+    (journal, checkpoint)
+}
+
+fn uninterrupted()
+-> HashMap<u16, Account>
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(CSV.as_bytes());
+    tx_engine::engine::Engine::new(&mut accounts)
+        .process_transactions(&mut data,&mut NullSink).unwrap();
+    accounts
+}
+
+fn assert_same(a : &HashMap<u16, Account>, b : &HashMap<u16, Account>)
+{
+    assert_eq!(a.len(), b.len());
+    for (client, x) in a.iter()
+    {
+        let y = &b[client];
+        assert_eq!((x.available, x.held, x.locked), 
+                   (y.available, y.held, y.locked), "client {client}");
+    }
+}
+
+#[test]
+fn recover_after_crash_matches_uninterrupted_run()
+{
+    let (journal_path, checkpoint_path) = temp_paths("crash");
+
+    // the process "crashes" after the first five rows, the
+    // accounts it was working on are lost along with it.
+    {
+        let prefix : String = CSV.lines().take(6).collect::<Vec<_>>().join("\n");
+        let mut accounts : HashMap<u16, Account> = HashMap::new();
+        let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+        engine.journal = Some(Journal::open(&journal_path, &checkpoint_path).unwrap());
+
+        let mut data = csv::Reader::from_reader(prefix.as_bytes());
+        engine.process_transactions(&mut data,&mut NullSink).unwrap();
+    }
+
+    // rerun the whole file after recovering. The withdrawal of tx 2
+    // was refused before the crash, and must not be retried against
+    // the recovered balance (which would now cover it).
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    engine.journal = Some(Journal::open(&journal_path, &checkpoint_path).unwrap());
+    assert_eq!(engine.recover().unwrap(), 6);
+
+    let mut data = csv::Reader::from_reader(CSV.as_bytes());
+    engine.process_transactions(&mut data,&mut NullSink).unwrap();
+    assert_eq!(engine.tx_state(4), Some(TxState::Disputed));
+    assert!(!engine.tx_history.contains_key(&2));
+
+    assert_same(&accounts, &uninterrupted());
+}
+
+#[test]
+fn recover_from_checkpoint_and_journal()
+{
+    let (journal_path, checkpoint_path) = temp_paths("checkpoint");
+
+    // the first file is completed and checkpointed, the second
+    // crashes after its deposit is journaled.
+    {
+        let mut accounts : HashMap<u16, Account> = HashMap::new();
+        let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+        engine.journal = Some(Journal::open(&journal_path, &checkpoint_path).unwrap());
+
+        let mut data = csv::Reader::from_reader(CSV.as_bytes());
+        engine.process_transactions(&mut data,&mut NullSink).unwrap();
+        engine.checkpoint().unwrap();
+        assert_eq!(std::fs::read_to_string(&journal_path).unwrap(), "journal,1\n");
+
+        let second = "type,client,tx,amount\ndeposit,3,10,1.5";
+        let mut data = csv::Reader::from_reader(second.as_bytes());
+        engine.process_transactions(&mut data,&mut NullSink).unwrap();
+    }
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    engine.journal = Some(Journal::open(&journal_path, &checkpoint_path).unwrap());
+    engine.recover().unwrap();
+    assert_eq!(engine.tx_state(4), Some(TxState::Disputed));
+    assert_eq!(engine.tx_state(3), Some(TxState::Resolved));
+
+    let mut expected = uninterrupted();
+    let mut account = Account::new();
+    account.deposit(&dec!(1.5)).unwrap();
+    expected.insert(3, account);
+    assert_same(&accounts, &expected);
+}
+
+#[test]
+fn recover_after_crash_between_checkpoint_and_journal()
+{
+    let (journal_path, checkpoint_path) = temp_paths("between");
+
+    // the checkpoint is written, but the process crashes before the
+    // journal it includes is emptied.
+    {
+        let mut accounts : HashMap<u16, Account> = HashMap::new();
+        let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+        engine.journal = Some(Journal::open(&journal_path, &checkpoint_path).unwrap());
+
+        let mut data = csv::Reader::from_reader(CSV.as_bytes());
+        engine.process_transactions(&mut data,&mut NullSink).unwrap();
+        let journaled = std::fs::read(&journal_path).unwrap();
+        engine.checkpoint().unwrap();
+        std::fs::write(&journal_path, journaled).unwrap();
+    }
+
+    // the journal's entries aren't replayed again, and the rows
+    // processed after recovering are journaled for the next one.
+    {
+        let mut accounts : HashMap<u16, Account> = HashMap::new();
+        let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+        engine.journal = Some(Journal::open(&journal_path, &checkpoint_path).unwrap());
+        assert_eq!(engine.recover().unwrap(), 0);
+        assert_same(engine.accounts, &uninterrupted());
+
+        let second = "type,client,tx,amount\ndeposit,3,10,1.5";
+        let mut data = csv::Reader::from_reader(second.as_bytes());
+        engine.process_transactions(&mut data,&mut NullSink).unwrap();
+    }
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    engine.journal = Some(Journal::open(&journal_path, &checkpoint_path).unwrap());
+    assert_eq!(engine.recover().unwrap(), 2);
+    assert_eq!(engine.accounts[&3].available, dec!(1.5));
+    assert_eq!(engine.accounts.len(), uninterrupted().len() + 1);
+}
+
+#[test]
+fn recover_ignores_torn_final_entry()
+{
+    let (journal_path, checkpoint_path) = temp_paths("torn");
+    std::fs::write(&journal_path, "2,deposit,1,1,5.0\n3,deposit,1,2,2").unwrap();
+
+    // the final entry parses, but without its '\n' it may have been
+    // cut short (from 2.5, say), so it's dropped.
+    let entries = read_journal(&journal_path).unwrap();
+    assert_eq!(entries.len(), 1);
+
+    // a final entry cut off before its amount can't be read.
+    std::fs::write(&journal_path, "2,deposit,1,1,5.0\n3,deposit,1,2,").unwrap();
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    engine.journal = Some(Journal::open(&journal_path, &checkpoint_path).unwrap());
+    assert_eq!(engine.recover().unwrap(), 2);
+
+    let account : &Account = 
+    engine.accounts.get(&1).unwrap();
+    assert_eq!(account.available, dec!(5.0));
+    assert_eq!(account.locked, false);
+    assert!(!engine.tx_history.contains_key(&2));
+
+    // the torn entry was cut off, so the next one is read whole.
+    let mut data = csv::Reader::from_reader("type,client,tx,amount\n\
+                                             deposit,1,1,5.0\n\
+                                             deposit,1,2,2.5".as_bytes());
+    engine.process_transactions(&mut data, &mut NullSink).unwrap();
+    assert_eq!(std::fs::read_to_string(&journal_path).unwrap(),
+               "2,deposit,1,1,5.0\n3,deposit,1,2,2.5\n");
+    assert_eq!(engine.accounts[&1].available, dec!(7.5));
+}
+
+#[test]
+fn recover_rejects_corrupt_journal()
+{
+    let (journal_path, checkpoint_path) = temp_paths("corrupt");
+    std::fs::write(&journal_path, "2,deposit,1,1,five\n3,deposit,1,2,2.0\n").unwrap();
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    engine.journal = Some(Journal::open(&journal_path, &checkpoint_path).unwrap());
+
+    let err = engine.recover().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn recover_at_the_journaled_lines()
+{
+    let (journal_path, checkpoint_path) = temp_paths("lines");
+    std::fs::write(&journal_path, "2,deposit,1,1,5.0\n4,withdrawal,1,2,1.0\n").unwrap();
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    engine.events = Some(Vec::new());
+    engine.journal = Some(Journal::open(&journal_path, &checkpoint_path).unwrap());
+    assert_eq!(engine.recover().unwrap(), 4);
+
+    let lines : Vec<u64> = engine.events.as_ref().unwrap().iter().map(|e| e.line).collect();
+    assert_eq!(lines, vec![2, 4]);
+
+    // an entry which can't be replayed is reported by its message.
+    let (journal_path, checkpoint_path) = temp_paths("unreplayable");
+    std::fs::write(&journal_path, "2,withdrawal,1,2,1.0\n").unwrap();
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    engine.journal = Some(Journal::open(&journal_path, &checkpoint_path).unwrap());
+    assert_eq!(engine.recover().unwrap_err().to_string(),
+               "journaled withdrawal of tx 2 could not be replayed: Client with id '1' \
+                has insufficient funds for transaction with id '2'.");
+}
+
+#[test]
+fn recover_on_top_of_opening_balances()
+{
+    let (journal_path, checkpoint_path) = temp_paths("opening");
+    let mut opening = Account::new();
+    opening.deposit(&dec!(10.0)).unwrap();
+
+    // a run starting from opening balances crashes before its first checkpoint.
+    {
+        let mut accounts : HashMap<u16, Account> = HashMap::from([(1, opening.clone())]);
+        let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+        engine.journal = Some(Journal::open(&journal_path, &checkpoint_path).unwrap());
+
+        let mut data = csv::Reader::from_reader("type,client,tx,amount\nwithdrawal,1,1,4.0".as_bytes());
+        engine.process_transactions(&mut data,&mut NullSink).unwrap();
+    }
+
+    let mut accounts : HashMap<u16, Account> = HashMap::from([(1, opening)]);
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    engine.journal = Some(Journal::open(&journal_path, &checkpoint_path).unwrap());
+    assert_eq!(engine.recover().unwrap(), 2);
+    assert_eq!(engine.accounts[&1].available, dec!(6.0));
+}
@@ -0,0 +1,129 @@
+// tests the double-entry general ledger behind the accounts
+use tx_engine::common::*;
+use tx_engine::engine::Engine;
+use tx_engine::ledger::*;
+use std::collections::HashMap;
+use rust_decimal_macros::dec;
+
+mod common;
+use common::process;
+
+const CSV : &str =
+"type,       client,     tx,     amount
+deposit,         1,      1,     5.0
+deposit,         2,      2,     3.0
+withdrawal,      1,      3,     1.5
+dispute,         2,      2,
+chargeback,      2,      2,
+deposit,         1,      4,     0.25
+dispute,         1,      4,";
+
+#[test]
+fn ledger_balances()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    process(&mut engine, CSV);
+
+    let ledger = &engine.ledger;
+    assert_eq!(ledger.imbalance(), dec!(0));
+    assert_eq!(ledger.balance(LedgerAccount::PlatformCash), dec!(6.75));
+    assert_eq!(ledger.balance(LedgerAccount::ChargebacksPayable), dec!(-3.0));
+    assert_eq!(ledger.client_balances(1), (dec!(3.5), dec!(0.25)));
+    assert_eq!(ledger.client_balances(2), (dec!(0), dec!(0)));
+
+    for (client, account) in engine.accounts.iter()
+    {
+        assert_eq!(ledger.client_balances(*client), (account.available, account.held));
+    }
+}
+
+#[test]
+fn ledger_entries_are_balanced_postings()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    engine.ledger.entries = Some(Vec::new());
+    process(&mut engine, CSV);
+
+    let entries = engine.ledger.entries.as_ref().unwrap();
+    assert_eq!(entries.len(), 7);
+    assert_eq!(entries[4], LedgerEntry
+    {
+        tx      : 2,
+        debit   : LedgerAccount::ClientHeld(2),
+        credit  : LedgerAccount::ChargebacksPayable,
+        amount  : dec!(3.0)
+    });
+}
+
+#[test]
+fn refused_commands_are_not_posted()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    engine.ledger.entries = Some(Vec::new());
+    process(&mut engine,
+"type,       client,     tx,     amount
+deposit,         1,      1,     1.0
+withdrawal,      1,      2,     5.0
+resolve,         1,      1,");
+
+    assert_eq!(engine.ledger.entries.as_ref().unwrap().len(), 1);
+    assert_eq!(engine.ledger.balance(LedgerAccount::PlatformCash), dec!(1.0));
+}
+
+#[test]
+fn opening_balances_are_posted()
+{
+    let mut opening = Account::new();
+    opening.deposit(&dec!(10.0)).unwrap();
+    opening.withhold(&dec!(2.0)).unwrap();
+    let mut accounts : HashMap<u16, Account> = HashMap::from([(1, opening)]);
+    let mut engine = Engine::new(&mut accounts);
+    process(&mut engine, CSV);
+
+    let ledger = &engine.ledger;
+    assert_eq!(ledger.imbalance(), dec!(0));
+    assert_eq!(ledger.balance(LedgerAccount::OpeningBalances), dec!(10.0));
+    assert_eq!(ledger.client_balances(1), (engine.accounts[&1].available,
+                                           engine.accounts[&1].held));
+}
+
+#[test]
+fn trial_balance_export()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    process(&mut engine, CSV);
+
+    let mut out : Vec<u8> = Vec::new();
+    engine.ledger.write_trial_balance(&mut out).unwrap();
+
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "account,debit,credit\r\n\
+                platform cash,6.7500,0.0000\r\n\
+                client 1 available,0.0000,3.5000\r\n\
+                client 2 available,0.0000,0.0000\r\n\
+                client 1 held,0.0000,0.2500\r\n\
+                client 2 held,0.0000,0.0000\r\n\
+                chargebacks payable,0.0000,3.0000\r\n\
+                total,6.7500,6.7500\r\n");
+}
+
+#[test]
+fn refuse_a_trial_balance_too_large_to_write()
+{
+    // the external accounts aren't bounded by MAX_AMOUNT.
+    let mut ledger = GeneralLedger::new();
+    for tx in 1..=3
+    {
+        ledger.post(LedgerEntry { tx, debit : LedgerAccount::PlatformCash,
+                                  credit : LedgerAccount::ChargebacksPayable,
+                                  amount : dec!(40000000000000000000000000000) });
+    }
+
+    let err = ledger.write_trial_balance(&mut Vec::new()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(ledger.imbalance(), dec!(0));
+}
@@ -0,0 +1,91 @@
+// tests writing the final account balances
+use tx_engine::common::*;
+use tx_engine::output::*;
+use std::collections::HashMap;
+use rust_decimal_macros::dec;
+
+fn sample_accounts()
+-> HashMap<u16, Account>
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+
+    let mut account = Account::new();
+    account.deposit(&dec!(2.0)).unwrap();
+    accounts.insert(12, account);
+
+    let mut account = Account::new();
+    account.deposit(&dec!(1.5)).unwrap();
+    account.withhold(&dec!(0.5)).unwrap();
+    account.lock();
+    accounts.insert(3, account);
+
+    accounts
+}
+
+#[test]
+fn write_csv_sorted_by_client()
+{
+    let mut out : Vec<u8> = Vec::new();
+    write_accounts(&sample_accounts(), OutputFormat::Csv, &mut out).unwrap();
+
+    assert_eq!(String::from_utf8(out).unwrap(),
+    "client,available,held,total,locked\r\n\
+     3,1.0000,0.5000,1.5000,true\r\n\
+     12,2.0000,0.0000,2.0000,false\r\n");
+}
+
+#[test]
+fn write_the_largest_balances()
+{
+    // the largest amount a row may carry, in both balances.
+    let csv = format!("type,client,tx,amount\n\
+                       deposit,1,1,{MAX_AMOUNT}\n\
+                       dispute,1,1,\n\
+                       deposit,1,2,1\n\
+                       deposit,2,3,-{MAX_AMOUNT}\n");
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    engine.reject_negative = false;
+    engine.process_transactions(&mut data, &mut tx_engine::sink::NullSink).unwrap();
+
+    let mut out : Vec<u8> = Vec::new();
+    write_accounts(&accounts, OutputFormat::Csv, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+    "client,available,held,total,locked\r\n\
+     1,0.0000,100000000000000000000.0000,100000000000000000000.0000,false\r\n\
+     2,-100000000000000000000.0000,0.0000,-100000000000000000000.0000,false\r\n");
+}
+
+#[test]
+fn write_json()
+{
+    let mut out : Vec<u8> = Vec::new();
+    write_accounts(&sample_accounts(), OutputFormat::Json, &mut out).unwrap();
+
+    assert_eq!(String::from_utf8(out).unwrap(),
+    "[\n  \
+     {\"client\":3,\"available\":1.0000,\"held\":0.5000,\"total\":1.5000,\"locked\":true},\n  \
+     {\"client\":12,\"available\":2.0000,\"held\":0.0000,\"total\":2.0000,\"locked\":false}\n\
+     ]\n");
+}
+
+#[test]
+fn write_json_lines()
+{
+    let mut out : Vec<u8> = Vec::new();
+    write_accounts(&sample_accounts(), OutputFormat::JsonLines, &mut out).unwrap();
+
+    assert_eq!(String::from_utf8(out).unwrap(),
+    "{\"client\":3,\"available\":1.0000,\"held\":0.5000,\"total\":1.5000,\"locked\":true}\n\
+     {\"client\":12,\"available\":2.0000,\"held\":0.0000,\"total\":2.0000,\"locked\":false}\n");
+}
+
+#[test]
+fn write_empty_json()
+{
+    let mut out : Vec<u8> = Vec::new();
+    write_accounts(&HashMap::new(), OutputFormat::Json, &mut out).unwrap();
+
+    assert_eq!(String::from_utf8(out).unwrap(), "[\n]\n");
+}
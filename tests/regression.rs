@@ -1,4 +1,7 @@
+#![allow(clippy::bool_assert_comparison)]
+
 use tx_engine::common::*;
+use tx_engine::sink::{MemorySink, NullSink};
 use std::collections::HashMap;
 use rust_decimal_macros::dec;
 
@@ -13,18 +16,18 @@ fn process_deposit()
     let mut accounts : HashMap<u16, Account> = HashMap::new();
     let mut data = csv::Reader::from_reader(csv.as_bytes());
     let mut engine = tx_engine::engine::Engine::new(&mut accounts);
-    engine.process_transactions(&mut data,&mut None);
+    engine.process_transactions(&mut data,&mut NullSink).unwrap();
 
     let account : &Account = 
     engine.accounts.get(&1).unwrap();
 
-    let tx : &Tx = 
+    let tx : Tx = 
     engine.tx_history.get(&1).unwrap();
 
     assert_eq!(account.held, dec!(0.0));
     assert_eq!(account.available, dec!(5.0));
     assert_eq!(account.locked, false);
-    assert_eq!(tx.disputed, false);
+    assert_eq!(tx.state, TxState::Processed);
 
 }
 
@@ -40,18 +43,18 @@ fn process_withdrawal()
     let mut accounts : HashMap<u16, Account> = HashMap::new();
     let mut data = csv::Reader::from_reader(csv.as_bytes());
     let mut engine = tx_engine::engine::Engine::new(&mut accounts);
-    engine.process_transactions(&mut data,&mut None);
+    engine.process_transactions(&mut data,&mut NullSink).unwrap();
 
     let account : &Account = 
     engine.accounts.get(&1).unwrap();
 
-    let tx : &Tx = 
+    let tx : Tx = 
     engine.tx_history.get(&1).unwrap();
 
     assert_eq!(account.held, dec!(0.0));
     assert_eq!(account.available, dec!(1.0));
     assert_eq!(account.locked, false);
-    assert_eq!(tx.disputed, false);
+    assert_eq!(tx.state, TxState::Processed);
 }
 
 #[test]
@@ -66,22 +69,22 @@ fn process_withdrawal_insufficient_amount()
     let mut accounts : HashMap<u16, Account> = HashMap::new();
     let mut data = csv::Reader::from_reader(csv.as_bytes());
     let mut engine = tx_engine::engine::Engine::new(&mut accounts);
-    let mut logger = Logger::new(&"tests/testlog.txt".to_string());
-    engine.process_transactions(&mut data,&mut logger);
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
 
     let account : &Account = 
     engine.accounts.get(&1).unwrap();
 
-    let tx : &Tx = 
+    let tx : Tx = 
     engine.tx_history.get(&1).unwrap();
 
     assert_eq!(account.held, dec!(0.0));
     assert_eq!(account.available, dec!(5.0));
     assert_eq!(account.locked, false);
-    assert_eq!(tx.disputed, false);
+    assert_eq!(tx.state, TxState::Processed);
 
-    let last_event = logger.unwrap().last_entry();
-    assert_eq!(last_event, ProcessEvent::ErrInsufficientfunds(1,2));
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrInsufficientfunds(1,2));
 }
 
 #[test]
@@ -97,15 +100,15 @@ fn process_dispute_deposit()
     let mut accounts : HashMap<u16, Account> = HashMap::new();
     let mut data = csv::Reader::from_reader(csv.as_bytes());
     let mut engine = tx_engine::engine::Engine::new(&mut accounts);
-    engine.process_transactions(&mut data,&mut None);
+    engine.process_transactions(&mut data,&mut NullSink).unwrap();
 
     // dispute for client 1 should be successful
     let account : &Account = 
     engine.accounts.get(&1).unwrap();
 
-    let tx : &Tx = 
+    let tx : Tx = 
     engine.tx_history.get(&3).unwrap();
-    assert_eq!(tx.disputed, true);
+    assert_eq!(tx.state, TxState::Disputed);
 
     assert_eq!(account.held, dec!(5.0));
     assert_eq!(account.available, dec!(0.0));
@@ -127,8 +130,8 @@ fn process_invalid_tx_dispute_deposit()
     let mut accounts : HashMap<u16, Account> = HashMap::new();
     let mut data = csv::Reader::from_reader(csv.as_bytes());
     let mut engine = tx_engine::engine::Engine::new(&mut accounts);
-    let mut logger = Logger::new(&"tests/testlog.txt".to_string());
-    engine.process_transactions(&mut data,&mut logger);
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
 
     // dispute for client 2 is unsuccessful because
     // it references a non existent tx id (5).
@@ -142,8 +145,8 @@ fn process_invalid_tx_dispute_deposit()
     assert_eq!(account.locked, false);
 
     // check log to assert non existent tx id event ackknowledged
-    let last_event = logger.unwrap().last_entry();
-    assert_eq!(last_event, ProcessEvent::ErrTxNotFound(5));
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrTxNotFound(5));
 
 }
 
@@ -163,14 +166,14 @@ fn process_dispute_withdrawal()
     let mut accounts : HashMap<u16, Account> = HashMap::new();
     let mut data = csv::Reader::from_reader(csv.as_bytes());
     let mut engine = tx_engine::engine::Engine::new(&mut accounts);
-    engine.process_transactions(&mut data,&mut None);
+    engine.process_transactions(&mut data,&mut NullSink).unwrap();
 
     let account : &Account = 
     engine.accounts.get(&1).unwrap();
 
-    let tx : &Tx = 
+    let tx : Tx = 
     engine.tx_history.get(&4).unwrap();
-    assert_eq!(tx.disputed, true);
+    assert_eq!(tx.state, TxState::Disputed);
 
     assert_eq!(account.held, dec!(4.0));
     assert_eq!(account.available, dec!(-3.0));
@@ -193,8 +196,8 @@ fn process_invalid_tx_dispute_withdrawal()
         let mut accounts : HashMap<u16, Account> = HashMap::new();
         let mut data = csv::Reader::from_reader(csv.as_bytes());
         let mut engine = tx_engine::engine::Engine::new(&mut accounts);
-        let mut logger = Logger::new(&"tests/testlog.txt".to_string());
-        engine.process_transactions(&mut data,&mut logger);
+        let mut sink = MemorySink::new();
+        engine.process_transactions(&mut data,&mut sink).unwrap();
 
         // dispute for client 2 is unsuccessful because
         // it references a non existent tx id (7).
@@ -208,8 +211,8 @@ fn process_invalid_tx_dispute_withdrawal()
         assert_eq!(account.locked, false);
 
         // check log to assert non existent tx id event ackknowledged
-        let last_event = logger.unwrap().last_entry();
-        assert_eq!(last_event, ProcessEvent::ErrTxNotFound(7));
+        let last_event = sink.last_entry().unwrap();
+        assert_eq!(last_event, ProcessError::ErrTxNotFound(7));
 }
 
 #[test]
@@ -224,16 +227,16 @@ fn process_resolve_deposit()
     let mut accounts : HashMap<u16, Account> = HashMap::new();
     let mut data = csv::Reader::from_reader(csv.as_bytes());
     let mut engine = tx_engine::engine::Engine::new(&mut accounts);
-    engine.process_transactions(&mut data,&mut None);
+    engine.process_transactions(&mut data,&mut NullSink).unwrap();
 
     // resolve dispute for client 1 should be successful
     
     let account : &Account = 
     engine.accounts.get(&1).unwrap();
 
-    let tx : &Tx = 
+    let tx : Tx = 
     engine.tx_history.get(&5).unwrap();
-    assert_eq!(tx.disputed, false);
+    assert_eq!(tx.state, TxState::Resolved);
 
     assert_eq!(account.held, dec!(0.0));
     assert_eq!(account.available, dec!(5.0));
@@ -255,16 +258,16 @@ fn process_resolve_withdrawal()
     let mut accounts : HashMap<u16, Account> = HashMap::new();
     let mut data = csv::Reader::from_reader(csv.as_bytes());
     let mut engine = tx_engine::engine::Engine::new(&mut accounts);
-    engine.process_transactions(&mut data,&mut None);
+    engine.process_transactions(&mut data,&mut NullSink).unwrap();
 
     // resolve dispute for client 1 should be successful
     
     let account : &Account = 
     engine.accounts.get(&1).unwrap();
 
-    let tx : &Tx = 
+    let tx : Tx = 
     engine.tx_history.get(&3).unwrap();
-    assert_eq!(tx.disputed, false);
+    assert_eq!(tx.state, TxState::Resolved);
 
     assert_eq!(account.held, dec!(0.0));
     assert_eq!(account.available, dec!(3.0));
@@ -288,8 +291,8 @@ fn process_invalid_tx_resolve_deposit()
     let mut accounts : HashMap<u16, Account> = HashMap::new();
     let mut data = csv::Reader::from_reader(csv.as_bytes());
     let mut engine = tx_engine::engine::Engine::new(&mut accounts);
-    let mut logger = Logger::new(&"tests/testlog.txt".to_string());
-    engine.process_transactions(&mut data,&mut logger);
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
 
     // resolve for client 2 is unsuccessful because
     // it references tx id (7), which doesn't exist yet - 
@@ -300,16 +303,16 @@ fn process_invalid_tx_resolve_deposit()
     let account : &Account = 
     engine.accounts.get(&2).unwrap();
 
-    let tx : &Tx = 
+    let tx : Tx = 
     engine.tx_history.get(&6).unwrap();
-    assert_eq!(tx.disputed, true);
+    assert_eq!(tx.state, TxState::Disputed);
 
     assert_eq!(account.held, dec!(7.0));
     assert_eq!(account.available, dec!(0.0));
     assert_eq!(account.locked, false);
 
-    let last_event = logger.unwrap().last_entry();
-    assert_eq!(last_event, ProcessEvent::ErrTxNotFound(7));
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrTxNotFound(7));
 }
 
 #[test]
@@ -324,8 +327,8 @@ fn process_undisputed_tx_resolve_deposit()
     let mut accounts : HashMap<u16, Account> = HashMap::new();
     let mut data = csv::Reader::from_reader(csv.as_bytes());
     let mut engine = tx_engine::engine::Engine::new(&mut accounts);
-    let mut logger = Logger::new(&"tests/testlog.txt".to_string());
-    engine.process_transactions(&mut data,&mut logger);
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
 
     // resolve for client 2 is unsuccessful because
     // it references tx id (7), which is undisputed.
@@ -334,16 +337,16 @@ fn process_undisputed_tx_resolve_deposit()
     let account : &Account = 
     engine.accounts.get(&1).unwrap();
 
-    let tx : &Tx = 
+    let tx : Tx = 
     engine.tx_history.get(&5).unwrap();
-    assert_eq!(tx.disputed, false);
+    assert_eq!(tx.state, TxState::Processed);
 
     assert_eq!(account.held, dec!(0.0));
     assert_eq!(account.available, dec!(5.0));
     assert_eq!(account.locked, false);
 
-    let last_event = logger.unwrap().last_entry();
-    assert_eq!(last_event, ProcessEvent::ErrTxNotDisputed(5));
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrTxNotDisputed(5));
 }
 
 
@@ -359,15 +362,15 @@ fn process_chargeback_deposit()
     let mut accounts : HashMap<u16, Account> = HashMap::new();
     let mut data = csv::Reader::from_reader(csv.as_bytes());
     let mut engine = tx_engine::engine::Engine::new(&mut accounts);
-    engine.process_transactions(&mut data,&mut None);
+    engine.process_transactions(&mut data,&mut NullSink).unwrap();
 
     // chargeback for client 1 should be successful
     let account : &Account = 
     engine.accounts.get(&1).unwrap();
 
-    let tx : &Tx = 
+    let tx : Tx = 
     engine.tx_history.get(&3).unwrap();
-    assert_eq!(tx.disputed, false);
+    assert_eq!(tx.state, TxState::ChargedBack);
 
     assert_eq!(account.held, dec!(0.0));
     assert_eq!(account.available, dec!(0.0));
@@ -387,15 +390,15 @@ fn process_chargeback_withdrawal()
     let mut accounts : HashMap<u16, Account> = HashMap::new();
     let mut data = csv::Reader::from_reader(csv.as_bytes());
     let mut engine = tx_engine::engine::Engine::new(&mut accounts);
-    engine.process_transactions(&mut data,&mut None);
+    engine.process_transactions(&mut data,&mut NullSink).unwrap();
 
     // chargeback for client 1 should be successful
     let account : &Account = 
     engine.accounts.get(&1).unwrap();
 
-    let tx : &Tx = 
+    let tx : Tx = 
     engine.tx_history.get(&4).unwrap();
-    assert_eq!(tx.disputed, false);
+    assert_eq!(tx.state, TxState::ChargedBack);
 
     assert_eq!(account.held, dec!(0.0));
     assert_eq!(account.available, dec!(-1.0));
@@ -415,23 +418,23 @@ fn process_invalid_tx_chargeback_withdrawal()
     let mut accounts : HashMap<u16, Account> = HashMap::new();
     let mut data = csv::Reader::from_reader(csv.as_bytes());
     let mut engine = tx_engine::engine::Engine::new(&mut accounts);
-    let mut logger = Logger::new(&"tests/testlog.txt".to_string());
-    engine.process_transactions(&mut data,&mut logger);
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
 
     // chargeback for client 1 should be successful
     let account : &Account = 
     engine.accounts.get(&1).unwrap();
 
-    let tx : &Tx = 
+    let tx : Tx = 
     engine.tx_history.get(&4).unwrap();
-    assert_eq!(tx.disputed, true);
+    assert_eq!(tx.state, TxState::Disputed);
 
     assert_eq!(account.held, dec!(3.0));
     assert_eq!(account.available, dec!(-1.0));
     assert_eq!(account.locked, false);
 
-    let last_event = logger.unwrap().last_entry();
-    assert_eq!(last_event, ProcessEvent::ErrTxNotFound(7));
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrTxNotFound(7));
 }
 
 #[test]
@@ -446,24 +449,24 @@ fn process_undisputed_chargeback_deposit()
     let mut accounts : HashMap<u16, Account> = HashMap::new();
     let mut data = csv::Reader::from_reader(csv.as_bytes());
     let mut engine = tx_engine::engine::Engine::new(&mut accounts);
-    let mut logger = Logger::new(&"tests/testlog.txt".to_string());
-    engine.process_transactions(&mut data,&mut logger);
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
 
     // chargeback for client 1 should fail
     // because the referenced tx is undisputed
     let account : &Account = 
     engine.accounts.get(&1).unwrap();
 
-    let tx : &Tx = 
+    let tx : Tx = 
     engine.tx_history.get(&3).unwrap();
-    assert_eq!(tx.disputed, false);
+    assert_eq!(tx.state, TxState::Processed);
 
     assert_eq!(account.held, dec!(0.0));
     assert_eq!(account.available, dec!(2.0));
     assert_eq!(account.locked, false);
 
-    let last_event = logger.unwrap().last_entry();
-    assert_eq!(last_event, ProcessEvent::ErrTxNotDisputed(3));
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrTxNotDisputed(3));
 }
 
 #[test]
@@ -478,24 +481,24 @@ fn process_undisputed_chargeback_withdrawal()
     let mut accounts : HashMap<u16, Account> = HashMap::new();
     let mut data = csv::Reader::from_reader(csv.as_bytes());
     let mut engine = tx_engine::engine::Engine::new(&mut accounts);
-    let mut logger = Logger::new(&"tests/testlog.txt".to_string());
-    engine.process_transactions(&mut data,&mut logger);
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
 
     // chargeback for client 1 should fail
     // because the referenced tx is undisputed
     let account : &Account = 
     engine.accounts.get(&1).unwrap();
 
-    let tx : &Tx = 
+    let tx : Tx = 
     engine.tx_history.get(&4).unwrap();
-    assert_eq!(tx.disputed, false);
+    assert_eq!(tx.state, TxState::Processed);
 
     assert_eq!(account.held, dec!(0.0));
     assert_eq!(account.available, dec!(2.0));
     assert_eq!(account.locked, false);
 
-    let last_event = logger.unwrap().last_entry();
-    assert_eq!(last_event, ProcessEvent::ErrTxNotDisputed(4));
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrTxNotDisputed(4));
 }
 
 
@@ -511,8 +514,8 @@ fn process_invalid_auth_chargeback()
     let mut accounts : HashMap<u16, Account> = HashMap::new();
     let mut data = csv::Reader::from_reader(csv.as_bytes());
     let mut engine = tx_engine::engine::Engine::new(&mut accounts);
-    let mut logger = Logger::new(&"tests/testlog.txt".to_string());
-    engine.process_transactions(&mut data,&mut logger);
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
 
     // chargeback for client 2 is unsuccessful because
     // it references tx id (3), which it doesn't own.
@@ -521,16 +524,16 @@ fn process_invalid_auth_chargeback()
     let account : &Account = 
     engine.accounts.get(&1).unwrap();
 
-    let tx : &Tx = 
+    let tx : Tx = 
     engine.tx_history.get(&3).unwrap();
-    assert_eq!(tx.disputed, false);
+    assert_eq!(tx.state, TxState::Processed);
 
     assert_eq!(account.held, dec!(0.0));
     assert_eq!(account.available, dec!(5.0));
     assert_eq!(account.locked, false);
 
-    let last_event = logger.unwrap().last_entry();
-    assert_eq!(last_event, ProcessEvent::ErrUnauthorisedTx(2,3));
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrUnauthorisedTx(2,3));
 }
 
 #[test]
@@ -544,19 +547,16 @@ fn process_negative_deposit()
     let mut accounts : HashMap<u16, Account> = HashMap::new();
     let mut data = csv::Reader::from_reader(csv.as_bytes());
     let mut engine = tx_engine::engine::Engine::new(&mut accounts);
-    let mut logger = Logger::new(&"tests/testlog.txt".to_string());
-    engine.process_transactions(&mut data,&mut logger);
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
 
     // deposit for client 1 should be fail
     // because the amount is negative.
     //( therefore tx wont exist.)
-    if let Some(_) = engine.tx_history.get(&3)
-    {
-        panic!()
-    }
+    assert!(!engine.tx_history.contains_key(&3));
  
-    let last_event = logger.unwrap().last_entry();
-    assert_eq!(last_event, ProcessEvent::ErrAmountNegative(3));
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrAmountNegative(3));
 
 
 }
@@ -573,8 +573,8 @@ fn process_negative_withdrawal()
     let mut accounts : HashMap<u16, Account> = HashMap::new();
     let mut data = csv::Reader::from_reader(csv.as_bytes());
     let mut engine = tx_engine::engine::Engine::new(&mut accounts);
-    let mut logger = Logger::new(&"tests/testlog.txt".to_string());
-    engine.process_transactions(&mut data,&mut logger);
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
 
     // withdrawal for client 1 should be fail
     // because the amount is negative
@@ -586,8 +586,8 @@ fn process_negative_withdrawal()
     assert_eq!(account.available, dec!(5.0));
     assert_eq!(account.locked, false);
 
-    let last_event = logger.unwrap().last_entry();
-    assert_eq!(last_event, ProcessEvent::ErrAmountNegative(4));   
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrAmountNegative(4));   
 }
 
 
@@ -603,8 +603,8 @@ fn process_invalid_type_()
     let mut accounts : HashMap<u16, Account> = HashMap::new();
     let mut data = csv::Reader::from_reader(csv.as_bytes());
     let mut engine = tx_engine::engine::Engine::new(&mut accounts);
-    let mut logger = Logger::new(&"tests/testlog.txt".to_string());
-    engine.process_transactions(&mut data,&mut logger);
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
 
     // withdrawal for client 1 should be fail
     // because the the type has a typo.
@@ -615,8 +615,9 @@ fn process_invalid_type_()
     assert_eq!(account.available, dec!(5.0));
     assert_eq!(account.locked, false);
 
-    let last_event = logger.unwrap().last_entry();
-    assert_eq!(last_event, ProcessEvent::ErrUnrecognisedTx(1,"withdfrawal"
+    let last_event = sink.last_entry().unwrap();
+    // the error carries the line of the row.
+    assert_eq!(last_event, ProcessError::ErrUnrecognisedTx(3,"withdfrawal"
                                                              .to_string()));   
 }
 
@@ -632,8 +633,8 @@ fn process_duplicate_tx_ids_()
     let mut accounts : HashMap<u16, Account> = HashMap::new();
     let mut data = csv::Reader::from_reader(csv.as_bytes());
     let mut engine = tx_engine::engine::Engine::new(&mut accounts);
-    let mut logger = Logger::new(&"tests/testlog.txt".to_string());
-    engine.process_transactions(&mut data,&mut logger);
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
 
     // withdrawal for client 1 should be fail
     // because it uses a tx id which already exists.
@@ -644,6 +645,434 @@ fn process_duplicate_tx_ids_()
     assert_eq!(account.available, dec!(5.0));
     assert_eq!(account.locked, false);
 
-    let last_event = logger.unwrap().last_entry();
-    assert_eq!(last_event, ProcessEvent::ErrTxIdExists(3));   
-}
\ No newline at end of file
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrTxIdExists(3));   
+}
+#[test]
+fn process_bad_client_id()
+{
+
+    let csv =
+    "type,       client,     tx,     amount
+    deposit,         1,      3,     5.0
+    deposit,     70000,      4,     5.0";
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
+
+    // the second deposit is skipped because the
+    // client id doesn't fit in a u16.
+    assert!(!engine.tx_history.contains_key(&4));
+
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrBadClientId(3,
+               "    deposit,     70000,      4,     5.0".to_string()));
+}
+
+#[test]
+fn process_bad_tx_id()
+{
+
+    let csv =
+    "type,       client,     tx,     amount
+    deposit,         1,     -3,     5.0";
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
+
+    assert!(engine.accounts.is_empty());
+
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrBadTxId(2,
+               "    deposit,         1,     -3,     5.0".to_string()));
+}
+
+#[test]
+fn process_missing_and_invalid_amounts()
+{
+
+    let csv =
+    "type,       client,     tx,     amount
+    deposit,         1,      3,     5.0
+    withdrawal,      1,      4,
+    deposit,         1,      5,     five";
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
+
+    // neither the withdrawal nor the second deposit 
+    // can be applied, but the first deposit stands.
+    let account : &Account = 
+    engine.accounts.get(&1).unwrap();
+
+    assert_eq!(account.available, dec!(5.0));
+    assert!(!engine.tx_history.contains_key(&4));
+    assert!(!engine.tx_history.contains_key(&5));
+
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrInvalidAmount(4,
+               "    deposit,         1,      5,     five".to_string()));
+}
+
+#[test]
+fn process_wrong_column_count()
+{
+
+    let csv =
+    "type,       client,     tx,     amount
+    deposit,         1,      3,     5.0,    extra
+    deposit,         1,      4,     2.0";
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
+
+    // the row with an extra column is skipped, and
+    // processing carries on with the next row.
+    let account : &Account = 
+    engine.accounts.get(&1).unwrap();
+
+    assert_eq!(account.available, dec!(2.0));
+    assert!(!engine.tx_history.contains_key(&3));
+
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrColumnCount(2,
+               "    deposit,         1,      3,     5.0,    extra".to_string()));
+}
+
+#[test]
+fn process_locked_account_default_policy()
+{
+
+    let csv =
+    "type,       client,     tx,     amount
+    deposit,         1,      3,     5.0
+    deposit,         1,      4,     2.0
+    dispute,         1,      4,
+    dispute,         1,      3,
+    chargeback,      1,      3,
+    resolve,         1,      4,
+    deposit,         1,      5,     9.0";
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
+
+    // once locked by the chargeback, the open dispute on
+    // tx 4 can still be resolved but the deposit is refused.
+    let account : &Account = 
+    engine.accounts.get(&1).unwrap();
+
+    assert_eq!(account.held, dec!(0.0));
+    assert_eq!(account.available, dec!(2.0));
+    assert_eq!(account.locked, true);
+    assert!(!engine.tx_history.contains_key(&5));
+
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrAccountLocked(1, 5));
+}
+
+#[test]
+fn process_locked_account_block_withdrawals()
+{
+
+    let csv =
+    "type,       client,     tx,     amount
+    deposit,         1,      3,     5.0
+    deposit,         1,      4,     2.0
+    dispute,         1,      3,
+    chargeback,      1,      3,
+    deposit,         1,      5,     1.0
+    withdrawal,      1,      6,     1.0";
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    engine.lock_policy = LockPolicy::BlockWithdrawals;
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
+
+    // deposits are still accepted, the withdrawal is refused.
+    let account : &Account = 
+    engine.accounts.get(&1).unwrap();
+
+    assert_eq!(account.held, dec!(0.0));
+    assert_eq!(account.available, dec!(3.0));
+    assert_eq!(account.locked, true);
+
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrAccountLocked(1, 6));
+}
+
+#[test]
+fn process_locked_account_block_all()
+{
+
+    let csv =
+    "type,       client,     tx,     amount
+    deposit,         1,      3,     5.0
+    deposit,         1,      4,     2.0
+    dispute,         1,      4,
+    dispute,         1,      3,
+    chargeback,      1,      3,
+    resolve,         1,      4,";
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    engine.lock_policy = LockPolicy::BlockAll;
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
+
+    // even the open dispute on tx 4 can't be resolved.
+    let account : &Account = 
+    engine.accounts.get(&1).unwrap();
+
+    assert_eq!(account.held, dec!(2.0));
+    assert_eq!(account.available, dec!(0.0));
+    assert_eq!(account.locked, true);
+
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrAccountLocked(1, 4));
+}
+
+#[test]
+fn process_locked_account_unenforced()
+{
+
+    let csv =
+    "type,       client,     tx,     amount
+    deposit,         1,      3,     5.0
+    dispute,         1,      3,
+    chargeback,      1,      3,
+    deposit,         1,      5,     4.0
+    withdrawal,      1,      6,     1.0";
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    engine.lock_policy = LockPolicy::Unenforced;
+    engine.process_transactions(&mut data,&mut NullSink).unwrap();
+
+    let account : &Account = 
+    engine.accounts.get(&1).unwrap();
+
+    assert_eq!(account.held, dec!(0.0));
+    assert_eq!(account.available, dec!(3.0));
+    assert_eq!(account.locked, true);
+}
+
+#[test]
+fn process_double_dispute()
+{
+
+    let csv =
+    "type,       client,     tx,     amount
+    deposit,         1,      3,     5.0
+    dispute,         1,      3,
+    dispute,         1,      3,";
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
+
+    // the second dispute must not withhold the funds again.
+    let account : &Account = 
+    engine.accounts.get(&1).unwrap();
+
+    assert_eq!(account.held, dec!(5.0));
+    assert_eq!(account.available, dec!(0.0));
+    assert_eq!(engine.tx_state(3), Some(TxState::Disputed));
+
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrTxAlreadyDisputed(3));
+}
+
+#[test]
+fn process_dispute_after_chargeback()
+{
+
+    let csv =
+    "type,       client,     tx,     amount
+    deposit,         1,      3,     5.0
+    deposit,         1,      4,     5.0
+    dispute,         1,      3,
+    chargeback,      1,      3,
+    dispute,         1,      3,
+    chargeback,      1,      3,";
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    // don't let the lock get in the way of the transition checks.
+    engine.lock_policy = LockPolicy::Unenforced;
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
+
+    // a charged back transaction can't be disputed or charged back again.
+    let account : &Account = 
+    engine.accounts.get(&1).unwrap();
+
+    assert_eq!(account.held, dec!(0.0));
+    assert_eq!(account.available, dec!(5.0));
+    assert_eq!(account.locked, true);
+    assert_eq!(engine.tx_state(3), Some(TxState::ChargedBack));
+
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrTxFinalised(3, TxState::ChargedBack));
+}
+
+#[test]
+fn process_chargeback_after_resolve()
+{
+
+    let csv =
+    "type,       client,     tx,     amount
+    deposit,         1,      3,     5.0
+    dispute,         1,      3,
+    resolve,         1,      3,
+    chargeback,      1,      3,";
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
+
+    let account : &Account = 
+    engine.accounts.get(&1).unwrap();
+
+    assert_eq!(account.held, dec!(0.0));
+    assert_eq!(account.available, dec!(5.0));
+    assert_eq!(account.locked, false);
+    assert_eq!(engine.tx_state(3), Some(TxState::Resolved));
+
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrTxFinalised(3, TxState::Resolved));
+}
+
+#[test]
+fn process_withdrawal_dispute_withhold()
+{
+    let csv =
+    "type,       client,     tx,     amount
+    deposit,         1,      3,     5.0
+    withdrawal,      1,      4,     4.0
+    dispute,         1,      4,
+    deposit,         2,      5,     5.0
+    withdrawal,      2,      6,     4.0
+    dispute,         2,      6,
+    chargeback,      2,      6,";
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    engine.dispute_policy = WithdrawalDisputePolicy::Withhold;
+    engine.process_transactions(&mut data,&mut NullSink).unwrap();
+
+    // as a deposit: the amount moves from available to held.
+    let account : &Account = 
+    engine.accounts.get(&1).unwrap();
+    assert_eq!(account.held, dec!(4.0));
+    assert_eq!(account.available, dec!(-3.0));
+
+    // and the chargeback removes it from held.
+    let account : &Account = 
+    engine.accounts.get(&2).unwrap();
+    assert_eq!(account.held, dec!(0.0));
+    assert_eq!(account.available, dec!(-3.0));
+    assert_eq!(account.locked, true);
+}
+
+#[test]
+fn process_withdrawal_dispute_reverse()
+{
+    let csv =
+    "type,       client,     tx,     amount
+    deposit,         1,      3,     5.0
+    withdrawal,      1,      4,     4.0
+    dispute,         1,      4,
+    deposit,         2,      5,     5.0
+    withdrawal,      2,      6,     4.0
+    dispute,         2,      6,
+    resolve,         2,      6,
+    deposit,         3,      7,     5.0
+    withdrawal,      3,      8,     4.0
+    dispute,         3,      8,
+    chargeback,      3,      8,";
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    engine.dispute_policy = WithdrawalDisputePolicy::Reverse;
+    engine.audit = tx_engine::audit::AuditMode::EveryTx;
+    engine.process_transactions(&mut data,&mut NullSink).unwrap();
+
+    // the withdrawal is reversed into held, available is untouched.
+    let account : &Account = 
+    engine.accounts.get(&1).unwrap();
+    assert_eq!(account.held, dec!(4.0));
+    assert_eq!(account.available, dec!(1.0));
+    assert_eq!(account.locked, false);
+
+    // resolved: the withdrawal stands.
+    let account : &Account = 
+    engine.accounts.get(&2).unwrap();
+    assert_eq!(account.held, dec!(0.0));
+    assert_eq!(account.available, dec!(1.0));
+    assert_eq!(account.locked, false);
+
+    // charged back: the withdrawal is reversed for good.
+    let account : &Account = 
+    engine.accounts.get(&3).unwrap();
+    assert_eq!(account.held, dec!(0.0));
+    assert_eq!(account.available, dec!(5.0));
+    assert_eq!(account.locked, true);
+
+    assert_eq!(engine.violations, vec![]);
+}
+
+#[test]
+fn process_withdrawal_dispute_reject()
+{
+    let csv =
+    "type,       client,     tx,     amount
+    deposit,         1,      3,     5.0
+    withdrawal,      1,      4,     4.0
+    dispute,         1,      3,
+    resolve,         1,      3,
+    dispute,         1,      4,";
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(csv.as_bytes());
+    let mut engine = tx_engine::engine::Engine::new(&mut accounts);
+    engine.dispute_policy = WithdrawalDisputePolicy::Reject;
+    let mut sink = MemorySink::new();
+    engine.process_transactions(&mut data,&mut sink).unwrap();
+
+    // deposits can still be disputed, the withdrawal can't.
+    let account : &Account = 
+    engine.accounts.get(&1).unwrap();
+    assert_eq!(account.held, dec!(0.0));
+    assert_eq!(account.available, dec!(1.0));
+    assert_eq!(engine.tx_state(3), Some(TxState::Resolved));
+    assert_eq!(engine.tx_state(4), Some(TxState::Processed));
+
+    let last_event = sink.last_entry().unwrap();
+    assert_eq!(last_event, ProcessError::ErrWithdrawalNotDisputable(4));
+}
@@ -0,0 +1,78 @@
+// tests the report of refused rows
+use tx_engine::audit::AuditMode;
+use tx_engine::common::*;
+use tx_engine::engine::Engine;
+use tx_engine::rejects::RejectsReport;
+use tx_engine::sharded::ShardedEngine;
+use tx_engine::sink::NullSink;
+use std::collections::HashMap;
+use rust_decimal_macros::dec;
+
+const CSV : &str =
+"type,       client,     tx,     amount
+deposit,         1,      1,     5.0
+withdrawal,      1,      2,     9.0
+dispute,         2,      7,
+deposit,         1,      x,     1.0
+deposit,         2,      3,     1.0";
+
+#[test]
+fn report_every_refused_row()
+{
+    let mut report = RejectsReport::new(Vec::new()).unwrap();
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(CSV.as_bytes());
+    Engine::new(&mut accounts).process_transactions(&mut data, &mut report).unwrap();
+
+    let report = String::from_utf8(report.into_inner().unwrap()).unwrap();
+    assert_eq!(report,
+"file,line,code,reason,row\r
+,3,insufficient_funds,Client with id '1' has insufficient funds for transaction with id '2'.,\"withdrawal,      1,      2,     9.0\"\r
+,4,tx_not_found,Transaction with id '7' is not found.,\"dispute,         2,      7,\"\r
+,5,bad_tx_id,\"In csv, line 5: 'deposit,         1,      x,     1.0' does not have a valid transaction id.\",\"deposit,         1,      x,     1.0\"\r
+");
+}
+
+#[test]
+fn sharded_rows_are_rebuilt_from_the_command()
+{
+    let mut report = RejectsReport::new(Vec::new()).unwrap();
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(CSV.as_bytes());
+    ShardedEngine::new(&mut accounts, 2).process_transactions(&mut data, &mut report).unwrap();
+
+    let report = report.into_inner().unwrap();
+    let rows : Vec<String> = csv::Reader::from_reader(report.as_slice())
+                             .records()
+                             .map(|entry| entry.unwrap()[4].to_string())
+                             .collect();
+    assert_eq!(rows, ["withdrawal,1,2,9.0", "dispute,2,7,",
+                      "deposit,         1,      x,     1.0"]);
+}
+
+#[test]
+fn leave_out_events_not_about_a_row()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    engine.audit = AuditMode::EndOfRun;
+    let mut data = csv::Reader::from_reader(CSV.as_bytes());
+    engine.process_transactions(&mut data, &mut NullSink).unwrap();
+
+    // held funds out of thin air, found by the audit at the end of the next run.
+    engine.accounts.get_mut(&1).unwrap().held = dec!(2.0);
+
+    let mut report = RejectsReport::new(Vec::new()).unwrap();
+    let mut data = csv::Reader::from_reader(
+"type,       client,     tx,     amount
+withdrawal,      2,      8,     5.0".as_bytes());
+    engine.process_transactions(&mut data, &mut report).unwrap();
+    assert!(!engine.violations.is_empty());
+
+    let report = String::from_utf8(report.into_inner().unwrap()).unwrap();
+    assert_eq!(report.lines().count(), 2);
+    assert!(report.contains("insufficient_funds"));
+    assert!(!report.contains("invariant_violated"));
+}
@@ -0,0 +1,219 @@
+// tests the event sinks, and the structured log written by Logger
+use tx_engine::common::*;
+use tx_engine::engine::Engine;
+use tx_engine::sharded::ShardedEngine;
+use tx_engine::sink::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+const CSV : &str =
+"type,       client,     tx,     amount
+deposit,         1,      1,     5.0
+withdrawal,      1,      2,     9.0
+dispute,         2,      7,
+deposit,         1,      x,     1.0";
+
+// a fresh log file for a test.
+fn temp_log(name : &str)
+-> PathBuf
+{
+    let path = std::env::temp_dir().join(format!("tx_engine_{}_{name}.log",
+                                                 std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn logger_at(path : &Path)
+-> Logger
+{
+    Logger::new(path).unwrap()
+}
+
+#[test]
+fn one_json_object_per_event()
+{
+    let path = temp_log("json");
+    let mut logger = logger_at(&path);
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(CSV.as_bytes());
+    Engine::new(&mut accounts).process_transactions(&mut data, &mut logger).unwrap();
+
+    let log = std::fs::read_to_string(&path).unwrap();
+    let entries : Vec<&str> = log.lines().collect();
+    assert_eq!(entries.len(), 4);
+    assert!(entries.iter().all(|e| e.starts_with("{\"timestamp\":\"") && e.ends_with('}')));
+
+    assert!(entries[0].contains("\"level\":\"warn\",\"code\":\"insufficient_funds\",\"number\":202,\
+                                 \"client\":1,\"tx\":2,\"line\":3,\"file\":null,\"message\":\"Client with id '1' \
+                                 has insufficient funds for transaction with id '2'.\"}"));
+    assert!(entries[1].contains("\"code\":\"tx_not_found\",\"number\":201,\
+                                 \"client\":null,\"tx\":7,\"line\":4,"));
+    assert!(entries[2].contains("\"code\":\"bad_tx_id\",\"number\":105,\
+                                 \"client\":null,\"tx\":null,\"line\":5,"));
+    assert!(entries[2].contains("does not have a valid transaction id"));
+    assert!(entries[3].contains("\"level\":\"info\",\"code\":\"input_complete\",\"number\":null,\
+                                 \"client\":null,\"tx\":null,\"line\":null,\"file\":null,\"message\":\
+                                 \"Processed 4 rows, 3 of which were refused.\"}"));
+}
+
+#[test]
+fn log_the_applied_rows_at_debug()
+{
+    let path = temp_log("debug");
+    let mut logger = logger_at(&path);
+    logger.min_level = LogLevel::Debug;
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(CSV.as_bytes());
+    Engine::new(&mut accounts).process_transactions(&mut data, &mut logger).unwrap();
+
+    let log = std::fs::read_to_string(&path).unwrap();
+    let levels : Vec<&str> = log.lines()
+                                .map(|entry| entry.split("\"level\":\"").nth(1).unwrap())
+                                .map(|rest| &rest[..rest.find('"').unwrap()])
+                                .collect();
+    assert_eq!(levels, ["debug", "warn", "warn", "warn", "info"]);
+    assert!(log.lines().next().unwrap().contains("\"code\":\"tx_applied\",\"number\":null,\
+                                                   \"client\":1,\"tx\":1,\"line\":2,"));
+
+    // the notes can be left out, as the refused rows can.
+    let path = temp_log("warn");
+    let mut logger = logger_at(&path);
+    logger.min_level = LogLevel::Warn;
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(CSV.as_bytes());
+    Engine::new(&mut accounts).process_transactions(&mut data, &mut logger).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+}
+
+#[test]
+fn skip_events_below_min_level()
+{
+    let path = temp_log("level");
+    let mut logger = logger_at(&path);
+    logger.min_level = LogLevel::Error;
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(CSV.as_bytes());
+    Engine::new(&mut accounts).process_transactions(&mut data, &mut logger).unwrap();
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+}
+
+#[test]
+fn sharded_events_carry_their_line()
+{
+    let path = temp_log("sharded");
+    let mut logger = logger_at(&path);
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(CSV.as_bytes());
+    ShardedEngine::new(&mut accounts, 2).process_transactions(&mut data, &mut logger).unwrap();
+
+    let log = std::fs::read_to_string(&path).unwrap();
+    let lines : Vec<bool> = log.lines()
+                               .zip(["\"line\":3,", "\"line\":4,", "\"line\":5,"])
+                               .map(|(entry, line)| entry.contains(line))
+                               .collect();
+    assert_eq!(lines, [true, true, true]);
+}
+
+#[test]
+fn surface_failed_writes()
+{
+    // writes to /dev/full always fail with no space left.
+    if !std::path::Path::new("/dev/full").exists()
+    {
+        return;
+    }
+    let mut logger = Logger::new(Path::new("/dev/full")).unwrap();
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(CSV.as_bytes());
+    let result = Engine::new(&mut accounts).process_transactions(&mut data, &mut logger);
+
+    // the run still completes, and reports the failure.
+    assert!(matches!(result, Err(ProcessError::ExternalErr(msg))
+                     if msg.starts_with("an event could not be recorded")));
+    assert_eq!(accounts[&1].available, rust_decimal_macros::dec!(5.0));
+}
+
+#[test]
+fn report_why_the_log_file_could_not_be_opened()
+{
+    let path = temp_log("missing").join("events.log");
+    let err = Logger::new(&path).err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+}
+
+#[test]
+fn memory_sink_keeps_every_event()
+{
+    let mut sink = MemorySink::new();
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut data = csv::Reader::from_reader(CSV.as_bytes());
+    Engine::new(&mut accounts).process_transactions(&mut data, &mut sink).unwrap();
+
+    assert_eq!(sink.events, vec![(3, ProcessError::ErrInsufficientfunds(1, 2)),
+                                 (4, ProcessError::ErrTxNotFound(7)),
+                                 (5, ProcessError::ErrBadTxId(5, String::from(
+                                      "deposit,         1,      x,     1.0")))]);
+}
+
+#[test]
+fn fan_out_to_every_sink()
+{
+    let path = temp_log("fan_out");
+    let mut first = MemorySink::new();
+    let mut second = MemorySink::new();
+    {
+        let mut sink = FanOut::new(vec![Box::new(&mut first),
+                                        Box::new(logger_at(&path)),
+                                        Box::new(&mut second)]);
+
+        let mut accounts : HashMap<u16, Account> = HashMap::new();
+        let mut data = csv::Reader::from_reader(CSV.as_bytes());
+        Engine::new(&mut accounts).process_transactions(&mut data, &mut sink).unwrap();
+    }
+
+    // the log has the input's completion as well, at info.
+    assert_eq!(first.events.len(), 3);
+    assert_eq!(first.events, second.events);
+    assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 4);
+}
+
+#[test]
+fn fan_out_past_a_failed_sink()
+{
+    if !std::path::Path::new("/dev/full").exists()
+    {
+        return;
+    }
+    let mut memory = MemorySink::new();
+    let result =
+    {
+        let mut sink = FanOut::new(vec![Box::new(Logger::new(Path::new("/dev/full")).unwrap()),
+                                        Box::new(&mut memory)]);
+
+        let mut accounts : HashMap<u16, Account> = HashMap::new();
+        let mut data = csv::Reader::from_reader(CSV.as_bytes());
+        Engine::new(&mut accounts).process_transactions(&mut data, &mut sink)
+    };
+
+    assert!(matches!(result, Err(ProcessError::ExternalErr(_))));
+    assert_eq!(memory.events.len(), 3);
+}
+
+#[test]
+fn json_entry_escapes_the_message()
+{
+    let timestamp = chrono::DateTime::parse_from_rfc3339("2022-10-03T11:43:25+01:00").unwrap();
+    let event = ProcessError::ExternalErr(String::from("a \"quoted\"\tpath\nand a \\"));
+    assert_eq!(json_entry(&timestamp, None, 0, &event),
+               "{\"timestamp\":\"2022-10-03T11:43:25+01:00\",\"level\":\"error\",\
+                \"code\":\"external_error\",\"number\":901,\"client\":null,\"tx\":null,\
+                \"line\":null,\"file\":null,\"message\":\"a \\\"quoted\\\"\\tpath\\nand a \\\\\"}");
+}
@@ -0,0 +1,149 @@
+#![allow(clippy::bool_assert_comparison)]
+
+// tests starting a run from the snapshot saved at the end of another
+use tx_engine::common::*;
+use tx_engine::engine::Engine;
+use tx_engine::registry::TxRegistry;
+use tx_engine::snapshot::*;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use rust_decimal_macros::dec;
+
+mod common;
+use common::process;
+
+// a fresh snapshot directory for a test.
+fn temp_dir(name : &str)
+-> PathBuf
+{
+    let dir = std::env::temp_dir()
+              .join(format!("tx_engine_{}_{name}_snapshot", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn dispute_tx_from_previous_day()
+{
+    let dir = temp_dir("previous_day");
+
+    // day one
+    {
+        let mut accounts : HashMap<u16, Account> = HashMap::new();
+        let mut engine = Engine::new(&mut accounts);
+        process(&mut engine,
+"type,       client,     tx,     amount
+deposit,         1,      1,     5.0
+deposit,         1,      2,     2.5
+withdrawal,      1,      3,     1.0");
+
+        let snapshot = Snapshot
+        {
+            accounts    : engine.accounts.clone(),
+            tx_history  : engine.tx_history.snapshot()
+        };
+        snapshot.write_dir(&dir).unwrap();
+    }
+
+    // day two starts from day one's closing balances
+    let snapshot = Snapshot::read_dir(&dir).unwrap();
+    let mut accounts = snapshot.accounts;
+    let mut engine = Engine::with_registry(&mut accounts,
+                                           TxRegistry::from_map(snapshot.tx_history));
+    process(&mut engine,
+"type,       client,     tx,     amount
+dispute,         1,      1,
+deposit,         1,      2,     9.0");
+
+    let acc = &engine.accounts[&1];
+    assert_eq!(acc.available, dec!(1.5));
+    assert_eq!(acc.held, dec!(5.0));
+    assert_eq!(engine.tx_state(1), Some(TxState::Disputed));
+
+    // tx 2 was used on day one, so day two's deposit was refused
+    assert_eq!(engine.tx_history.get(&2).unwrap().amount, dec!(2.5));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn snapshot_round_trip()
+{
+    let dir = temp_dir("round_trip");
+
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    process(&mut engine,
+"type,       client,     tx,     amount
+deposit,         1,      1,     5.1234
+deposit,         2,      2,     3.0
+dispute,         2,      2,
+chargeback,      2,      2,
+deposit,         3,      3,     1.0
+dispute,         3,      3,");
+
+    let snapshot = Snapshot
+    {
+        accounts    : engine.accounts.clone(),
+        tx_history  : engine.tx_history.snapshot()
+    };
+    snapshot.write_dir(&dir).unwrap();
+    let read = Snapshot::read_dir(&dir).unwrap();
+
+    assert_eq!(read.tx_history.len(), 3);
+    for (id, tx) in snapshot.tx_history.iter()
+    {
+        let other = &read.tx_history[id];
+        assert_eq!((tx.client, tx.amount, tx.state), (other.client, other.amount, other.state));
+    }
+    assert_eq!(read.accounts.len(), 3);
+    for (client, acc) in snapshot.accounts.iter()
+    {
+        let other = &read.accounts[client];
+        assert_eq!((acc.available, acc.held, acc.locked),
+                   (other.available, other.held, other.locked));
+    }
+    assert_eq!(read.accounts[&2].locked, true);
+    assert_eq!(read.accounts[&3].held, dec!(1.0));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn read_padded_accounts_without_history()
+{
+    let dir = temp_dir("padded");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join(ACCOUNTS_FILE),
+"client,  available,  held,  total,  locked
+     1,     0.5000, 0.0000, 0.5000,   true
+     2,     2.0000, 1.0000, 3.0000,  false").unwrap();
+
+    let snapshot = Snapshot::read_dir(&dir).unwrap();
+    assert_eq!(snapshot.tx_history.len(), 0);
+    assert_eq!(snapshot.accounts[&1].locked, true);
+    assert_eq!(snapshot.accounts[&2].held, dec!(1.0));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn refuse_inconsistent_accounts()
+{
+    // the total doesn't match available + held
+    let data = "client,available,held,total,locked\n1,1.0,1.0,3.0,false\n";
+    assert!(read_accounts(data.as_bytes()).is_err());
+
+    // a balance beyond MAX_AMOUNT, whose total can't be held
+    let data = "client,available,held,total,locked\n\
+                1,79228162514264337593543950335,1,79228162514264337593543950335,false\n";
+    assert!(read_accounts(data.as_bytes()).is_err());
+
+    // the same client twice
+    let data = "client,available,held,total,locked\n1,1.0,0,1.0,false\n1,1.0,0,1.0,false\n";
+    assert!(read_accounts(data.as_bytes()).is_err());
+
+    // an unknown tx state
+    let data = "tx,client,amount,state\n1,1,1.0,pending\n";
+    assert!(read_tx_history(data.as_bytes()).is_err());
+}
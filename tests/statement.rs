@@ -0,0 +1,102 @@
+// tests per client statements, with their running balances
+use tx_engine::common::*;
+use tx_engine::sink::NullSink;
+use tx_engine::engine::Engine;
+use tx_engine::history::BalanceHistory;
+use tx_engine::statement::*;
+use std::collections::HashMap;
+use rust_decimal_macros::dec;
+
+const CSV : &str =
+"type,       client,     tx,     amount
+deposit,         7,      1,     5.0
+deposit,         2,      2,     3.0
+withdrawal,      7,      3,     1.5
+dispute,         7,      1,
+withdrawal,      7,      4,     9.0
+resolve,         7,      1,
+deposit,         7,      5,     0.25
+dispute,         7,      5,
+chargeback,      7,      5,";
+
+fn engine_with_history(accounts : &mut HashMap<u16, Account>)
+-> Engine<'_>
+{
+    let mut engine = Engine::new(accounts);
+    engine.history = Some(BalanceHistory::new());
+    let mut data = csv::Reader::from_reader(CSV.as_bytes());
+    engine.process_transactions(&mut data,&mut NullSink).unwrap();
+    engine
+}
+
+#[test]
+fn statement_csv()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let engine = engine_with_history(&mut accounts);
+    let statement = engine.statement(7, None).unwrap();
+
+    let mut out : Vec<u8> = Vec::new();
+    statement.write(StatementFormat::Csv, &mut out).unwrap();
+
+    // the refused withdrawal of tx 4 isn't on the statement.
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "line,type,tx,amount,available,held,total,locked\r\n\
+                ,opening balance,,,0.0000,0.0000,0.0000,false\r\n\
+                2,deposit,1,5.0000,5.0000,0.0000,5.0000,false\r\n\
+                4,withdrawal,3,1.5000,3.5000,0.0000,3.5000,false\r\n\
+                5,dispute,1,5.0000,-1.5000,5.0000,3.5000,false\r\n\
+                7,resolve,1,5.0000,3.5000,0.0000,3.5000,false\r\n\
+                8,deposit,5,0.2500,3.7500,0.0000,3.7500,false\r\n\
+                9,dispute,5,0.2500,3.5000,0.2500,3.7500,false\r\n\
+                10,chargeback,5,0.2500,3.5000,0.0000,3.5000,true\r\n\
+                ,closing balance,,,3.5000,0.0000,3.5000,true\r\n");
+}
+
+#[test]
+fn statement_range()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let engine = engine_with_history(&mut accounts);
+    let statement = engine.statement(7, Some((0, 5..=8))).unwrap();
+
+    assert_eq!(statement.opening.available, dec!(3.5));
+    assert_eq!(statement.rows.len(), 3);
+    assert_eq!(statement.closing().available, dec!(3.75));
+
+    // a range with nothing in it still has its balances.
+    let statement = engine.statement(7, Some((0, 11..=20))).unwrap();
+    assert!(statement.rows.is_empty());
+    assert_eq!(statement.opening, engine.accounts[&7]);
+    assert_eq!(statement.closing(), &engine.accounts[&7]);
+
+    assert_eq!(engine.statement(3, Some((0, 0..=20))), None);
+}
+
+#[test]
+fn statement_text()
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let engine = engine_with_history(&mut accounts);
+    let statement = engine.statement(7, Some((0, 2..=5))).unwrap();
+
+    let mut out : Vec<u8> = Vec::new();
+    statement.write(StatementFormat::Text, &mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+
+    assert_eq!(text,
+"Statement for client 7, lines 2 to 5
+
+  line  type                    tx        amount     available          held         total
+        opening balance                                 0.0000        0.0000        0.0000
+     2  deposit                  1        5.0000        5.0000        0.0000        5.0000
+     4  withdrawal               3        1.5000        3.5000        0.0000        3.5000
+     5  dispute                  1        5.0000       -1.5000        5.0000        3.5000
+        closing balance                                -1.5000        5.0000        3.5000
+");
+
+    // without a range the statement covers every line.
+    let mut out : Vec<u8> = Vec::new();
+    engine.statement(7, None).unwrap().write(StatementFormat::Text, &mut out).unwrap();
+    assert!(String::from_utf8(out).unwrap().starts_with("Statement for client 7, all lines\n"));
+}
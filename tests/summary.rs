@@ -0,0 +1,83 @@
+// tests the run summary
+use tx_engine::common::*;
+use tx_engine::engine::Engine;
+use tx_engine::sink::NullSink;
+use tx_engine::summary::*;
+use std::collections::HashMap;
+use std::time::Duration;
+
+const CSV : &str =
+"type,       client,     tx,     amount
+deposit,         1,      1,     5.0
+deposit,         2,      2,     3.0
+withdrawal,      1,      3,     9.0
+dispute,         2,      2,
+chargeback,      2,      2,
+deposit,         2,      4,     1.0
+dispute,         3,      7,
+deposit,         1,      x,     1.0";
+
+fn summarise(csv_data : &str, summary : RunSummary)
+-> RunSummary
+{
+    let mut accounts : HashMap<u16, Account> = HashMap::new();
+    let mut engine = Engine::new(&mut accounts);
+    engine.summary = Some(summary);
+    let mut data = csv::Reader::from_reader(csv_data.as_bytes());
+    engine.process_transactions(&mut data, &mut NullSink).unwrap();
+    engine.summary.unwrap()
+}
+
+#[test]
+fn count_every_row()
+{
+    let summary = summarise(CSV, RunSummary::new());
+
+    assert_eq!(summary.rows, 8);
+    assert_eq!(summary.accepted.get("deposit"), Some(&2));
+    assert_eq!(summary.accepted.get("dispute"), Some(&1));
+    assert_eq!(summary.accepted.get("chargeback"), Some(&1));
+    assert_eq!(summary.accepted_total(), 4);
+
+    assert_eq!(summary.rejected.get("insufficient_funds"), Some(&1));
+    assert_eq!(summary.rejected.get("account_locked"), Some(&1));
+    assert_eq!(summary.rejected.get("tx_not_found"), Some(&1));
+    assert_eq!(summary.rejected.get("bad_tx_id"), Some(&1));
+    assert_eq!(summary.rejected_total(), 4);
+
+    // the row which couldn't be parsed isn't for any client.
+    assert_eq!(summary.clients.len(), 3);
+    assert_eq!(summary.locked, 1);
+    assert_eq!(summary.violations, 0);
+    assert!(summary.elapsed > Duration::ZERO);
+}
+
+#[test]
+fn add_up_over_several_files()
+{
+    let first = summarise(CSV, RunSummary::new());
+    let both = summarise(CSV, first.clone());
+
+    assert_eq!(both.rows, 16);
+    assert_eq!(both.rejected_total(), first.rejected_total() * 2);
+    assert!(both.elapsed > first.elapsed);
+}
+
+#[test]
+fn write_summary_as_json()
+{
+    let mut summary = summarise(CSV, RunSummary::new());
+    summary.elapsed = Duration::from_millis(2);
+
+    let mut json : Vec<u8> = Vec::new();
+    summary.write(SummaryFormat::Json, &mut json).unwrap();
+    assert_eq!(String::from_utf8(json).unwrap(),
+               "{\"rows\":8,\"accepted\":{\"chargeback\":1,\"deposit\":2,\"dispute\":1},\
+                \"accepted_total\":4,\"rejected\":{\"account_locked\":1,\"bad_tx_id\":1,\
+                \"insufficient_funds\":1,\"tx_not_found\":1},\"rejected_total\":4,\"rolled_back\":0,\
+                \"clients\":3,\"locked\":1,\"violations\":0,\"elapsed_secs\":0.002000,\
+                \"rows_per_second\":4000.0,\"config\":{\"rounding_dp\":4,\
+                \"reject_negative_amounts\":true,\"cross_client\":\"refuse\",\
+                \"withdrawal_disputes\":\"withhold\",\"lock_policy\":\
+                \"block-all-but-settlement\"}}\n");
+}